@@ -1391,7 +1391,7 @@ fn roc_run_native<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
 #[cfg(feature = "run-wasm32")]
 fn run_wasm<I: Iterator<Item = S>, S: AsRef<[u8]>>(wasm_path: &std::path::Path, args: I) {
     use bumpalo::collections::Vec;
-    use roc_wasm_interp::{DefaultImportDispatcher, Instance};
+    use roc_wasm_interp::{DefaultImportDispatcher, ImportDispatcher, Instance};
 
     let bytes = std::fs::read(wasm_path).unwrap();
     let arena = Bump::new();
@@ -1406,12 +1406,13 @@ fn run_wasm<I: Iterator<Item = S>, S: AsRef<[u8]>>(wasm_path: &std::path::Path,
 
     let mut instance = Instance::from_bytes(&arena, &bytes, import_dispatcher, false).unwrap();
 
-    instance
-        .call_export("_start", [])
-        .unwrap()
-        .unwrap()
-        .expect_i32()
-        .unwrap();
+    let result = instance.call_export("_start", []).unwrap();
+
+    if let Some(exit_code) = instance.import_dispatcher.exit_code() {
+        std::process::exit(exit_code);
+    }
+
+    result.unwrap().expect_i32().unwrap();
 }
 
 #[cfg(not(feature = "run-wasm32"))]
@@ -1634,7 +1634,7 @@ mod cli_run {
 #[cfg(feature = "wasm32-cli-run")]
 fn run_wasm(wasm_path: &std::path::Path, stdin: &[&str]) -> String {
     use bumpalo::Bump;
-    use roc_wasm_interp::{DefaultImportDispatcher, Instance, Value, WasiFile};
+    use roc_wasm_interp::{DefaultImportDispatcher, ImportDispatcher, Instance, Value, WasiFile};
 
     let wasm_bytes = std::fs::read(wasm_path).unwrap();
     let arena = Bump::new();
@@ -1659,6 +1659,10 @@ fn run_wasm(wasm_path: &std::path::Path, stdin: &[&str]) -> String {
 
     let result = instance.call_export("_start", []);
 
+    // An exit code from `proc_exit` counts the same as one returned from `_start`
+    let exit_code = instance.import_dispatcher.exit_code();
+    let result = result.map(|value| value.or(exit_code.map(Value::I32)));
+
     match result {
         Ok(Some(Value::I32(0))) => match &instance.import_dispatcher.wasi.files[1] {
             WasiFile::WriteOnly(fake_stdout) => String::from_utf8(fake_stdout.clone())
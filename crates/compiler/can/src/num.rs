@@ -5,6 +5,7 @@ use roc_problem::can::Problem;
 use roc_problem::can::RuntimeError::*;
 use roc_problem::can::{FloatErrorKind, IntErrorKind};
 use roc_region::all::Region;
+use roc_types::num::lower_bound_of_int_literal;
 pub use roc_types::num::{FloatBound, FloatWidth, IntBound, IntLitWidth, NumBound, SignDemand};
 use roc_types::subs::VarStore;
 
@@ -311,51 +312,3 @@ fn from_str_radix(src: &str, radix: u32) -> Result<ParsedNumResult, IntErrorKind
         }
     }
 }
-
-fn lower_bound_of_int_literal(result: i128) -> IntLitWidth {
-    use IntLitWidth::*;
-    if result >= 0 {
-        // Positive
-        let result = result as u128;
-        if result > U64.max_value() {
-            I128
-        } else if result > I64.max_value() {
-            U64
-        } else if result > F64.max_value() {
-            I64
-        } else if result > U32.max_value() {
-            F64
-        } else if result > I32.max_value() {
-            U32
-        } else if result > F32.max_value() {
-            I32
-        } else if result > U16.max_value() {
-            F32
-        } else if result > I16.max_value() {
-            U16
-        } else if result > U8.max_value() {
-            I16
-        } else if result > I8.max_value() {
-            U8
-        } else {
-            I8
-        }
-    } else {
-        // Negative
-        if result < I64.min_value() {
-            I128
-        } else if result < F64.min_value() {
-            I64
-        } else if result < I32.min_value() {
-            F64
-        } else if result < F32.min_value() {
-            I32
-        } else if result < I16.min_value() {
-            F32
-        } else if result < I8.min_value() {
-            I16
-        } else {
-            I8
-        }
-    }
-}
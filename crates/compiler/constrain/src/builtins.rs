@@ -343,14 +343,9 @@ impl TypedNumericBound for IntBound {
         match self {
             IntBound::None => NumericBound::None,
             IntBound::Exact(w) => NumericBound::IntExact(*w),
-            IntBound::AtLeast {
-                sign: SignDemand::NoDemand,
-                width,
-            } => NumericBound::Range(NumericRange::IntAtLeastEitherSign(*width)),
-            IntBound::AtLeast {
-                sign: SignDemand::Signed,
-                width,
-            } => NumericBound::Range(NumericRange::IntAtLeastSigned(*width)),
+            IntBound::AtLeast { sign, width } => {
+                NumericBound::Range(NumericRange::new(true, *sign, *width))
+            }
         }
     }
 }
@@ -368,14 +363,9 @@ impl TypedNumericBound for NumBound {
     fn numeric_bound(&self) -> NumericBound {
         match self {
             NumBound::None => NumericBound::None,
-            &NumBound::AtLeastIntOrFloat {
-                sign: SignDemand::NoDemand,
-                width,
-            } => NumericBound::Range(NumericRange::NumAtLeastEitherSign(width)),
-            &NumBound::AtLeastIntOrFloat {
-                sign: SignDemand::Signed,
-                width,
-            } => NumericBound::Range(NumericRange::NumAtLeastSigned(width)),
+            &NumBound::AtLeastIntOrFloat { sign, width } => {
+                NumericBound::Range(NumericRange::new(false, sign, width))
+            }
         }
     }
 }
@@ -384,7 +374,7 @@ impl TypedNumericBound for SingleQuoteBound {
     fn numeric_bound(&self) -> NumericBound {
         match self {
             &SingleQuoteBound::AtLeast { width } => {
-                NumericBound::Range(NumericRange::IntAtLeastEitherSign(width))
+                NumericBound::Range(NumericRange::new(true, SignDemand::NoDemand, width))
             }
         }
     }
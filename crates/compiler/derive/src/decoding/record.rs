@@ -803,6 +803,10 @@ fn state_record_update(
 ///             Ok f1 -> Ok { f1, f0 }
 ///             Err _ -> Err TooShort
 ///     Err _ -> Err TooShort
+///
+/// A field that never appeared in the input is decoded from empty bytes, so the field's type
+/// decides whether it's required. Most decoders fail on empty input, making the whole record fail,
+/// but a type whose decoder succeeds on empty input (like an optional value) gets its default.
 pub(super) fn finalizer(
     env: &mut Env,
     state_record_var: Variable,
@@ -38,6 +38,9 @@ impl FlatDecodable {
         match *subs.get_content_without_compacting(var) {
             Content::Structure(flat_type) => match flat_type {
                 FlatType::Apply(sym, _) => match sym {
+                    // Strings and lists are handed straight to the format's `string` and `list`
+                    // decoders, so whether the result copies the input bytes or slices into
+                    // them is up to the format. Derived code never copies them itself.
                     Symbol::LIST_LIST => Ok(Key(FlatDecodableKey::List())),
                     Symbol::STR_STR => Ok(Immediate(Symbol::DECODE_STRING)),
                     _ => Err(Underivable),
@@ -73,13 +76,14 @@ impl FlatDecodable {
                     Ok(Key(FlatDecodableKey::Tuple(elems_iter.count() as _)))
                 }
                 FlatType::TagUnion(_tags, _ext) | FlatType::RecursiveTagUnion(_, _tags, _ext) => {
-                    Err(Underivable) // yet
+                    // Not derivable yet: DecoderFormatting has no way to decode tags.
+                    Err(Underivable)
                 }
                 FlatType::FunctionOrTagUnion(_name_index, _, _) => {
                     Err(Underivable) // yet
                 }
                 FlatType::EmptyRecord => Ok(Key(FlatDecodableKey::Record(vec![]))),
-                FlatType::EmptyTuple => todo!(),
+                FlatType::EmptyTuple => unreachable!("Somehow Decoding derivation got an expression that's an empty tuple, which shouldn't be possible!"),
                 FlatType::EmptyTagUnion => {
                     Err(Underivable) // yet
                 }
@@ -2407,11 +2407,9 @@ pub fn is_unresolved_var(subs: &Subs, var: Variable) -> bool {
 
 #[inline(always)]
 pub fn is_any_float_range(subs: &Subs, var: Variable) -> bool {
-    use {Content::*, NumericRange::*};
-    let content = subs.get_content_without_compacting(var);
     matches!(
-        content,
-        RangedNumber(NumAtLeastEitherSign(..) | NumAtLeastSigned(..)),
+        subs.get_content_without_compacting(var),
+        Content::RangedNumber(range) if range.admits_float()
     )
 }
 
@@ -2032,6 +2032,83 @@ mod solve_expr {
         );
     }
 
+    #[test]
+    fn annotated_num_floatingpoint_binary32_int_literal() {
+        infer_eq(
+            indoc!(
+                r"
+                   float : Num (FloatingPoint Binary32)
+                   float = 5
+
+                   float
+                "
+            ),
+            "F32",
+        );
+    }
+
+    #[test]
+    fn annotated_frac_binary32_int_literal() {
+        infer_eq(
+            indoc!(
+                r"
+                   float : Frac Binary32
+                   float = 5
+
+                   float
+                "
+            ),
+            "F32",
+        );
+    }
+
+    #[test]
+    fn annotated_num_integer_unsigned8() {
+        infer_eq(
+            indoc!(
+                r"
+                   int : Num (Integer Unsigned8)
+                   int = 255
+
+                   int
+                "
+            ),
+            "U8",
+        );
+    }
+
+    #[test]
+    fn annotated_str_int_literal() {
+        let (type_problems, _, actual) = infer_eq_help(indoc!(
+            r"
+                str : Str
+                str = 5
+
+                str
+            "
+        ))
+        .unwrap();
+
+        assert!(type_problems.contains("TYPE MISMATCH"), "{type_problems}");
+        assert_eq!(actual, "<type mismatch>");
+    }
+
+    #[test]
+    fn annotated_record_int_literal() {
+        let (type_problems, _, actual) = infer_eq_help(indoc!(
+            r"
+                rec : { x : U8 }
+                rec = 5
+
+                rec
+            "
+        ))
+        .unwrap();
+
+        assert!(type_problems.contains("TYPE MISMATCH"), "{type_problems}");
+        assert_eq!(actual, "<type mismatch>");
+    }
+
     #[test]
     fn qualified_annotation_f64() {
         infer_eq(
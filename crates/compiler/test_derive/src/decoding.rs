@@ -6,7 +6,7 @@
 
 use crate::{
     test_key_eq, test_key_neq,
-    util::{check_derivable, check_immediate, check_underivable, derive_test},
+    util::{check_derivable, check_derived, check_immediate, check_underivable, derive_test},
     v,
 };
 use insta::assert_snapshot;
@@ -32,6 +32,8 @@ test_key_eq! {
         v!((v!(U8), v!(U16),)), v!((v!(U8), v!(U16),))
     same_tuple_fields_diff_types:
         v!((v!(U8), v!(U16),)), v!((v!(U32), v!(U64),))
+    uniform_tuple_and_mixed_tuple:
+        v!((v!(U8), v!(U8), v!(U8), v!(U8),)), v!((v!(STR), v!(U8), v!(F64), v!(U8),))
 
     list_list_diff_types:
         v!(Symbol::LIST_LIST v!(STR)), v!(Symbol::LIST_LIST v!(U8))
@@ -69,11 +71,63 @@ fn immediates() {
     check_immediate(Decoder, v!(STR), Symbol::DECODE_STRING);
 }
 
+#[test]
+fn bool() {
+    check_immediate(
+        Decoder,
+        v!(@Symbol::BOOL_BOOL => v!([ True, False ])),
+        Symbol::DECODE_BOOL,
+    );
+}
+
+#[test]
+fn opaque_decodes_real_type() {
+    // Like `Username := Str`
+    check_immediate(
+        Decoder,
+        v!(@Symbol::ATTR_ATTR => v!(STR)),
+        Symbol::DECODE_STRING,
+    );
+    check_derivable(
+        Decoder,
+        v!(@Symbol::ATTR_ATTR => v!({ name: v!(STR), })),
+        DeriveKey::Decoder(FlatDecodableKey::Record(vec!["name".into()])),
+    );
+}
+
 #[test]
 fn optional_record_field_derive_error() {
     check_underivable(Decoder, v!({ ?a: v!(U8), }), DeriveError::Underivable);
 }
 
+#[test]
+fn recursive_tag_union_derive_error() {
+    // Like `Expr : [Lit U8, Add Expr Expr]`. DecoderFormatting has no way to decode tags yet,
+    // so this must be rejected up front rather than recursing into the type forever.
+    check_underivable(
+        Decoder,
+        v!([ Lit v!(U8), Add v!(^expr) v!(^expr) ] as expr),
+        DeriveError::Underivable,
+    );
+    check_underivable(
+        Decoder,
+        v!(@Symbol::ATTR_ATTR => v!([ Lit v!(U8), Add v!(^expr) v!(^expr) ] as expr)),
+        DeriveError::Underivable,
+    );
+}
+
+#[test]
+fn str_and_bytes_use_format_decoders() {
+    // No derived code sits between the format and the decoded value, so a format that
+    // slices its input rather than copying it is not undone by the derived decoder.
+    check_immediate(Decoder, v!(STR), Symbol::DECODE_STRING);
+    check_derivable(
+        Decoder,
+        v!(Symbol::LIST_LIST v!(U8)),
+        DeriveKey::Decoder(FlatDecodableKey::List()),
+    );
+}
+
 #[test]
 fn derivable_record_ext_flex_var() {
     check_derivable(
@@ -184,6 +238,41 @@ fn record_2_fields() {
     })
 }
 
+#[test]
+fn tuple_shares_decoder_across_element_types() {
+    // The derived decoder is generic over its elements, so a tuple of the same type
+    // throughout reuses the one derived for its arity rather than getting its own.
+    check_derivable(
+        Decoder,
+        v!((v!(U8), v!(U8), v!(U8), v!(U8),)),
+        DeriveKey::Decoder(FlatDecodableKey::Tuple(4)),
+    );
+    check_derivable(
+        Decoder,
+        v!((v!(STR), v!(U8), v!(F64), v!(U8),)),
+        DeriveKey::Decoder(FlatDecodableKey::Tuple(4)),
+    );
+}
+
+#[test]
+fn record_fields_decoded_in_sorted_order() {
+    let derived = check_derived(Decoder, v!({ c: v!(U8), a: v!(STR), b: v!(U8), }));
+
+    // One decoder for each field, whatever order the type lists them in, then Skip for the rest
+    let branches = ["\"a\" ->", "\"b\" ->", "\"c\" ->", "_ -> Skip"];
+    let positions: Vec<_> = branches
+        .iter()
+        .map(|branch| {
+            assert_eq!(derived.matches(branch).count(), 1, "{branch} in {derived}");
+            derived.find(branch).unwrap()
+        })
+        .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "{derived}");
+
+    // Every field must be present for the record to be built
+    assert_eq!(derived.matches("Err TooShort").count(), 3, "{derived}");
+}
+
 #[test]
 fn tuple_2_fields() {
     derive_test(Decoder, v!((v!(STR), v!(U8),)), |golden| {
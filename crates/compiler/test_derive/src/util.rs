@@ -368,8 +368,7 @@ fn check_derived_typechecks_and_golden(
     source_var: Variable,
     derived_program: &str,
     specialization_lsets: SpecializationLambdaSets,
-    check_golden: impl Fn(&str),
-) {
+) -> String {
     // constrain the derived
     let mut types = Types::new();
     let mut constraints = Constraints::new();
@@ -491,7 +490,7 @@ fn check_derived_typechecks_and_golden(
         panic!("Derived does not typecheck:\n{buf}\nDerived def:\n{derived_program}");
     }
 
-    let golden = assemble_derived_golden(
+    assemble_derived_golden(
         subs,
         test_module,
         interns,
@@ -499,9 +498,7 @@ fn check_derived_typechecks_and_golden(
         derived_program,
         def_var,
         specialization_lsets,
-    );
-
-    check_golden(&golden)
+    )
 }
 
 fn get_key(builtin: DeriveBuiltin, subs: &Subs, var: Variable) -> DeriveKey {
@@ -512,6 +509,16 @@ fn get_key(builtin: DeriveBuiltin, subs: &Subs, var: Variable) -> DeriveKey {
 }
 
 pub(crate) fn derive_test<S>(builtin: DeriveBuiltin, synth_input: S, check_golden: impl Fn(&str))
+where
+    S: FnOnce(&mut Subs) -> Variable,
+{
+    check_golden(&check_derived(builtin, synth_input))
+}
+
+/// Derive `builtin` for a type, check that the derived code typechecks, and return it
+/// pretty-printed after its type and specialization lambda sets, like [derive_test] snapshots.
+/// For assertions about one part of the derived code, rather than a snapshot of all of it.
+pub(crate) fn check_derived<S>(builtin: DeriveBuiltin, synth_input: S) -> String
 where
     S: FnOnce(&mut Subs) -> Variable,
 {
@@ -587,6 +594,5 @@ where
         source_var,
         &derived_program,
         specialization_lsets,
-        check_golden,
-    );
+    )
 }
@@ -1024,6 +1024,33 @@ fn decode_derive_decoder_for_opaque() {
         RocStr
     )
 }
+
+#[test]
+#[cfg(all(
+    any(feature = "gen-llvm", feature = "gen-wasm"),
+    not(debug_assertions) // https://github.com/roc-lang/roc/issues/3898
+))]
+fn decode_derive_decoder_for_opaque_str() {
+    assert_evals_to!(
+        &formatdoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            {TAG_LEN_ENCODER_FMT}
+
+            Username := Str implements [Decoding]
+
+            main =
+                when Str.toUtf8 "s5 alice " |> Decode.fromBytes tagLenFmt is
+                    Ok (@Username name) -> name
+                    _ -> "FAIL"
+            "#
+        ),
+        RocStr::from("alice"),
+        RocStr
+    )
+}
+
 #[test]
 #[cfg(any(feature = "gen-llvm", feature = "gen-wasm"))]
 fn decode_use_stdlib_custom_list() {
@@ -1136,6 +1163,30 @@ mod decode_immediate {
         )
     }
 
+    #[test]
+    #[cfg(feature = "gen-llvm")]
+    fn bool_invalid_byte() {
+        assert_evals_to!(
+            &formatdoc!(
+                r#"
+                app "test" provides [main] to "./platform"
+
+                {TAG_LEN_ENCODER_FMT}
+
+                fromStr : Str -> Result Bool _
+                fromStr = \s -> Str.toUtf8 s |> Decode.fromBytes tagLenFmt
+
+                main =
+                    fromStr "n1 " == Ok Bool.true
+                    && fromStr "n0 " == Ok Bool.false
+                    && fromStr "n2 " == Err TooShort
+                "#
+            ),
+            true,
+            bool
+        )
+    }
+
     macro_rules! num_immediate {
         ($($num:expr, $typ:ident, $expected_type:ident)*) => {$(
             #[test]
@@ -1437,6 +1488,88 @@ fn decode_record_of_record() {
     )
 }
 
+#[test]
+#[cfg(all(
+    any(feature = "gen-llvm", feature = "gen-wasm"),
+    not(debug_assertions) // https://github.com/roc-lang/roc/issues/3898
+))]
+fn decode_record_inspected() {
+    // `Inspect.toStr` renders the whole decoded value canonically, with record fields sorted,
+    // so one string comparison checks every field instead of matching each one.
+    assert_evals_to!(
+        &formatdoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            {TAG_LEN_ENCODER_FMT}
+
+            decodePerson : List U8 -> Result {{ name : Str, age : U8, scores : List U16 }} _
+            decodePerson = \bytes -> Decode.fromBytes bytes tagLenFmt
+
+            main =
+                when Str.toUtf8 "r3 s6 scores l2 n7 n300 s4 name s5 alice s3 age n30 " |> decodePerson is
+                    Ok person -> Inspect.toStr person
+                    _ -> "something went wrong"
+            "#
+        ),
+        RocStr::from(r#"{age: 30, name: "alice", scores: [7, 300]}"#),
+        RocStr
+    )
+}
+
+#[test]
+#[cfg(all(
+    any(feature = "gen-llvm", feature = "gen-wasm"),
+    not(debug_assertions) // https://github.com/roc-lang/roc/issues/3898
+))]
+fn decode_record_missing_field_uses_default() {
+    assert_evals_to!(
+        &formatdoc!(
+            r#"
+            app "test" provides [main] to "./platform"
+
+            {TAG_LEN_ENCODER_FMT}
+
+            # Decodes to None when the field is missing, since missing fields are decoded from empty input
+            Opt := [Some Str, None] implements [Decoding {{decoder: optDecoder}}]
+
+            optDecoder =
+                Decode.custom \bytes, fmt ->
+                    if List.isEmpty bytes then
+                        {{result: Ok (@Opt None), rest: []}}
+                    else
+                        when Decode.decodeWith bytes Decode.decoder fmt is
+                            {{result, rest}} ->
+                                when result is
+                                    Ok s -> {{result: Ok (@Opt (Some s)), rest}}
+                                    Err e -> {{result: Err e, rest}}
+
+            decode : Str -> Result {{name: Str, nickname: Opt}} _
+            decode = \input -> Str.toUtf8 input |> Decode.fromBytes tagLenFmt
+
+            main =
+                present =
+                    when decode "r2 s4 name s2 ab s8 nickname s2 cd " is
+                        Ok {{name: "ab", nickname: @Opt (Some "cd")}} -> "present "
+                        _ -> "FAIL "
+                absent =
+                    when decode "r1 s4 name s2 ab " is
+                        Ok {{name: "ab", nickname: @Opt None}} -> "absent "
+                        _ -> "FAIL "
+                # Str can't be decoded from empty input, so it's still required
+                required =
+                    when decode "r1 s8 nickname s2 cd " is
+                        Err TooShort -> "required"
+                        _ -> "FAIL"
+
+                Str.concat present absent |> Str.concat required
+            "#
+        ),
+        RocStr::from("present absent required"),
+        RocStr
+    )
+}
+
 #[test]
 #[cfg(all(
     any(feature = "gen-llvm", feature = "gen-wasm"),
@@ -9,7 +9,7 @@ use roc_packaging::cache::RocCacheDir;
 use roc_reporting::report::DEFAULT_PALETTE_HTML;
 use roc_solve::FunctionKind;
 use roc_std::RocStr;
-use roc_wasm_interp::{wasi, ImportDispatcher, Instance, WasiDispatcher};
+use roc_wasm_interp::{wasi, ImportDispatcher, Instance, WasiDispatcher, WasiResult};
 use roc_wasm_module::{Export, ExportType, Value, WasmModule};
 use std::marker::PhantomData;
 use std::path::PathBuf;
@@ -200,7 +200,10 @@ impl<'a> ImportDispatcher for TestDispatcher<'a> {
         memory: &mut [u8],
     ) -> Option<Value> {
         if module_name == wasi::MODULE_NAME {
-            self.wasi.dispatch(function_name, arguments, memory)
+            match self.wasi.dispatch(function_name, arguments, memory) {
+                WasiResult::Return(value) => value,
+                WasiResult::Exit(exit_code) => panic!("The program called proc_exit({exit_code})"),
+            }
         } else if module_name == "env" && function_name == "send_panic_msg_to_rust" {
             let msg_ptr = arguments[0].expect_i32().unwrap();
             let panic_tag = arguments[1].expect_i32().unwrap();
@@ -17,7 +17,7 @@ use roc_mono::ir::{
     Call, CallType, Expr, Literal, Proc, ProcLayout, SelfRecursive, Stmt, UpdateModeId,
 };
 use roc_mono::layout::{LambdaName, Layout, Niche, STLayoutInterner};
-use roc_wasm_interp::{wasi, ImportDispatcher, Instance, WasiDispatcher};
+use roc_wasm_interp::{wasi, ImportDispatcher, Instance, WasiDispatcher, WasiResult};
 use roc_wasm_module::{Value, WasmModule};
 
 const LINKING_TEST_HOST_WASM: &str = "build/wasm_linking_test_host.wasm";
@@ -192,7 +192,10 @@ impl ImportDispatcher for TestDispatcher<'_> {
         memory: &mut [u8],
     ) -> Option<Value> {
         if module_name == wasi::MODULE_NAME {
-            self.wasi.dispatch(function_name, arguments, memory)
+            match self.wasi.dispatch(function_name, arguments, memory) {
+                WasiResult::Return(value) => value,
+                WasiResult::Exit(exit_code) => panic!("The program called proc_exit({exit_code})"),
+            }
         } else if module_name == "env" {
             match function_name {
                 "js_called_directly_from_roc" => Some(Value::I32(0x01)),
@@ -1,3 +1,9 @@
+//! Bounds on number literals, and the numeric types that satisfy them.
+//!
+//! This is plain computation, using only `core` apart from the conversions to [Symbol]
+//! and [Variable]. Keep it that way, so that it could move to its own `no_std` crate
+//! if tooling outside the compiler needs it; the lint below catches `std` paths that `core` has.
+#![deny(clippy::std_instead_of_core)]
 use roc_module::symbol::Symbol;
 
 use crate::subs::Variable;
@@ -13,11 +19,60 @@ pub enum NumericRange {
 }
 
 impl NumericRange {
-    pub fn contains_float_width(&self, _width: FloatWidth) -> bool {
-        // we don't currently check the float width
-        true
+    /// The range of a literal that must be at least `width` wide, and signed if `sign` demands it.
+    /// `int_only` rules out floats, for literals like `0x10` that can only be integers.
+    ///
+    /// | `int_only` | `sign`     | variant                |
+    /// |------------|------------|------------------------|
+    /// | `true`     | `Signed`   | `IntAtLeastSigned`     |
+    /// | `true`     | `NoDemand` | `IntAtLeastEitherSign` |
+    /// | `false`    | `Signed`   | `NumAtLeastSigned`     |
+    /// | `false`    | `NoDemand` | `NumAtLeastEitherSign` |
+    pub fn new(int_only: bool, sign: SignDemand, width: IntLitWidth) -> Self {
+        use NumericRange::*;
+        match (int_only, sign) {
+            (true, SignDemand::Signed) => IntAtLeastSigned(width),
+            (true, SignDemand::NoDemand) => IntAtLeastEitherSign(width),
+            (false, SignDemand::Signed) => NumAtLeastSigned(width),
+            (false, SignDemand::NoDemand) => NumAtLeastEitherSign(width),
+        }
+    }
+
+    /// The range of an unsuffixed number literal with this integer value, like the one
+    /// canonicalization gives to `5` or `-5`. It could still turn out to be a float,
+    /// so it's a `Num` range, which demands a sign if the value is negative.
+    pub fn from_int_literal(value: i128) -> Self {
+        let sign = if value < 0 {
+            SignDemand::Signed
+        } else {
+            SignDemand::NoDemand
+        };
+        NumericRange::new(false, sign, lower_bound_of_int_literal(value))
+    }
+
+    /// Could a number in this range be fractional? Only `Num` ranges can; `Int` ranges come from
+    /// literals like `0x10` that can only be integers. This matches what `Frac` unifies with.
+    pub fn admits_float(&self) -> bool {
+        use NumericRange::*;
+        match self {
+            IntAtLeastSigned(_) | IntAtLeastEitherSign(_) => false,
+            NumAtLeastSigned(_) | NumAtLeastEitherSign(_) => true,
+        }
+    }
+
+    /// Checks if every integer in this range can be represented exactly by `width`.
+    /// `F32` and `F64` are limited by their precision, to +/- 2^24 and 2^53.
+    /// `Dec` holds any integer in the `i128` range, like [IntLitWidth::max_value] says.
+    pub fn contains_float_width(&self, width: FloatWidth) -> bool {
+        self.contains_int_width(width.into())
     }
 
+    /// Checks if every integer in this range can be represented by `width`.
+    /// A range that demands a sign only contains negative numbers, so only the negative side of
+    /// `width` is considered; otherwise only the positive side is.
+    ///
+    /// For example `IntAtLeastEitherSign(U64)` (a literal above `I64::MAX`) contains `U64` but
+    /// not `I64`, while `IntAtLeastEitherSign(I64)` contains both.
     pub fn contains_int_width(&self, width: IntLitWidth) -> bool {
         use NumericRange::*;
 
@@ -28,14 +83,40 @@ impl NumericRange {
             NumAtLeastEitherSign(width) => (SignDemand::NoDemand, width),
         };
 
-        let (actual_signedness, _) = width.signedness_and_width();
+        let is_negative = range_signedness == SignDemand::Signed;
 
-        if let (IntSignedness::Unsigned, SignDemand::Signed) = (actual_signedness, range_signedness)
-        {
-            return false;
-        }
+        width.is_superset(at_least_width, is_negative)
+    }
+
+    /// The same kind of range, at the next wider standard integer width, or `None` if it's
+    /// already the widest. A range that demands a sign only widens to signed widths. Otherwise
+    /// the next width must hold more non-negative numbers, so `I8` widens to `U8`, and `U8` to
+    /// `I16`. That's the order [lower_bound_of_int_literal] picks widths in.
+    pub fn widen(&self) -> Option<NumericRange> {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        let (int_only, sign) = match self {
+            IntAtLeastSigned(_) => (true, SignDemand::Signed),
+            IntAtLeastEitherSign(_) => (true, SignDemand::NoDemand),
+            NumAtLeastSigned(_) => (false, SignDemand::Signed),
+            NumAtLeastEitherSign(_) => (false, SignDemand::NoDemand),
+        };
+        let is_negative = sign == SignDemand::Signed;
+        let current = self.min_width();
 
-        width.signedness_and_width().1 >= at_least_width.signedness_and_width().1
+        // Float widths aren't in the table, so they can widen to any wider integer width
+        let widths = [I8, U8, I16, U16, I32, U32, I64, U64, I128, U128];
+        let after_current = widths
+            .iter()
+            .position(|width| *width == current)
+            .map_or(0, |i| i + 1);
+        widths[after_current..]
+            .iter()
+            .filter(|width| !is_negative || width.is_signed())
+            .find(|width| {
+                width.is_superset(&current, is_negative) && !current.is_superset(width, is_negative)
+            })
+            .map(|width| NumericRange::new(int_only, sign, *width))
     }
 
     pub fn min_width(&self) -> IntLitWidth {
@@ -84,6 +165,60 @@ impl NumericRange {
         }
     }
 
+    /// Combine `self` and `other` into a range with the wider of their two widths, which only
+    /// demands a sign, or rules out floats, if both ranges do.
+    ///
+    /// [NumericRange::intersection] picks the wider width too, but keeps the demands of either range.
+    /// When only one range demands a sign, this isn't a least upper bound: the unsigned widths it
+    /// allows can't hold that range's negative numbers. For example,
+    /// `IntAtLeastSigned(I8).union(&IntAtLeastEitherSign(U8))` is `IntAtLeastEitherSign(U8)`.
+    pub fn union(&self, other: &Self) -> Self {
+        use NumericRange::*;
+        let int_only = !self.admits_float() && !other.admits_float();
+        let sign = match (self, other) {
+            (
+                IntAtLeastSigned(_) | NumAtLeastSigned(_),
+                IntAtLeastSigned(_) | NumAtLeastSigned(_),
+            ) => SignDemand::Signed,
+            _ => SignDemand::NoDemand,
+        };
+
+        // Without a sign demand, only the positive side of each width is considered
+        let is_negative = sign == SignDemand::Signed;
+        let (left, right) = (self.min_width(), other.min_width());
+        let width = if left.is_superset(&right, is_negative) {
+            left
+        } else {
+            right
+        };
+        NumericRange::new(int_only, sign, width)
+    }
+
+    /// Whether the two ranges allow exactly the same types, even if they're different variants.
+    /// For example, no float type holds every `U128`, so `NumAtLeastEitherSign(U128)` allows
+    /// only `U128`, just like `IntAtLeastEitherSign(U128)`.
+    pub fn represents_same_set(&self, other: &Self) -> bool {
+        let (mine, theirs) = (self.variable_slice(), other.variable_slice());
+        mine.len() == theirs.len() && mine.iter().all(|var| theirs.contains(var))
+    }
+
+    /// Whether every type this range allows is also allowed by `other`, and `other` allows more.
+    /// So narrowing `other` to `self` actually rules something out.
+    pub fn is_tighter_than(&self, other: &Self) -> bool {
+        let (mine, theirs) = (self.variable_slice(), other.variable_slice());
+        mine.len() < theirs.len() && mine.iter().all(|var| theirs.contains(var))
+    }
+
+    /// Narrows `self` against another range that it's being unified with.
+    /// The narrower of the two ranges is the one to keep, if they intersect at all.
+    pub fn narrow_to(&self, other: &NumericRange) -> MatchResult {
+        match self.intersection(other) {
+            Some(r) if r == *self => MatchResult::RangeInContent,
+            Some(_) => MatchResult::ContentInRange,
+            None => MatchResult::NoIntersection,
+        }
+    }
+
     pub fn variable_slice(&self) -> &'static [Variable] {
         use NumericRange::*;
 
@@ -153,6 +288,29 @@ impl NumericRange {
     }
 }
 
+/// The outcome of narrowing one [NumericRange] against another.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchResult {
+    /// The range is contained in the other content, so the range should be kept.
+    /// Unification succeeds, and both variables get the range.
+    RangeInContent,
+    /// The other content is contained in the range, so the other content should be kept.
+    /// Unification succeeds, and both variables get the other content.
+    ContentInRange,
+    /// No number satisfies both. Unification fails with a type mismatch.
+    NoIntersection,
+}
+
+impl MatchResult {
+    /// Whether the two sides can be unified at all, whichever one is kept.
+    pub fn is_compatible(&self) -> bool {
+        match self {
+            MatchResult::RangeInContent | MatchResult::ContentInRange => true,
+            MatchResult::NoIntersection => false,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum IntSignedness {
     Unsigned,
@@ -165,6 +323,11 @@ impl IntSignedness {
     }
 }
 
+/// Largest integer that F32 represents exactly, along with every integer below it: 2^24
+const F32_MAX_EXACT_INT: i128 = 1 << 24;
+/// Largest integer that F64 represents exactly, along with every integer below it: 2^53
+const F64_MAX_EXACT_INT: i128 = 1 << 53;
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum IntLitWidth {
     U8,
@@ -188,6 +351,31 @@ pub enum IntLitWidth {
 }
 
 impl IntLitWidth {
+    /// Every width, integers first in the order of [IntLitWidth::integer_widths],
+    /// then `F32`, `F64` and `Dec`, which can hold progressively wider ranges of integers.
+    pub const fn all() -> &'static [IntLitWidth] {
+        use IntLitWidth::*;
+        &[
+            U8, I8, U16, I16, U32, I32, U64, I64, U128, I128, F32, F64, Dec,
+        ]
+    }
+
+    /// The integer widths, from narrow to wide. At each bit width, the unsigned width comes
+    /// before the signed one, since it can hold more non-negative numbers.
+    pub const fn integer_widths() -> &'static [IntLitWidth] {
+        use IntLitWidth::*;
+        &[U8, I8, U16, I16, U32, I32, U64, I64, U128, I128]
+    }
+
+    /// The width of a built-in numeric variable like [Variable::U8], or `None` for any other variable.
+    /// The inverse of [int_lit_width_to_variable].
+    pub fn from_variable(var: Variable) -> Option<IntLitWidth> {
+        Self::all()
+            .iter()
+            .copied()
+            .find(|width| int_lit_width_to_variable(*width) == var)
+    }
+
     /// Returns the `IntSignedness` and bit width of a variant.
     pub fn signedness_and_width(&self) -> (IntSignedness, u32) {
         use IntLitWidth::*;
@@ -245,10 +433,8 @@ impl IntLitWidth {
             I32 => i32::MAX as u128,
             I64 => i64::MAX as u128,
             I128 => i128::MAX as u128,
-            // Max int value without losing precision: 2^24
-            F32 => 16_777_216,
-            // Max int value without losing precision: 2^53
-            F64 => 9_007_199_254_740_992,
+            F32 => F32_MAX_EXACT_INT as u128,
+            F64 => F64_MAX_EXACT_INT as u128,
             // Max int value without losing precision: I128::MAX
             Dec => i128::MAX as u128,
         }
@@ -263,19 +449,48 @@ impl IntLitWidth {
             I32 => i32::MIN as i128,
             I64 => i64::MIN as i128,
             I128 => i128::MIN,
-            // Min int value without losing precision: -2^24
-            F32 => -16_777_216,
-            // Min int value without losing precision: -2^53
-            F64 => -9_007_199_254_740_992,
+            F32 => -F32_MAX_EXACT_INT,
+            F64 => -F64_MAX_EXACT_INT,
             // Min int value without losing precision: I128::MIN
             Dec => i128::MIN,
         }
     }
 
+    /// The inclusive range of integers this width represents exactly, as `(min, max)`.
+    /// The upper bound for `U128` is capped at `i128::MAX`.
+    pub fn exact_int_range(&self) -> (i128, i128) {
+        let max = self.max_value().min(i128::MAX as u128) as i128;
+        (self.min_value(), max)
+    }
+
+    /// How many distinct integers this width represents exactly: `2^bits` for the integer widths,
+    /// and every integer from [IntLitWidth::min_value] to [IntLitWidth::max_value] for the others.
+    /// Saturates at `u128::MAX` for the 128-bit widths, whose count doesn't fit.
+    pub fn value_count(&self) -> u128 {
+        self.max_value()
+            .saturating_add(self.min_value().unsigned_abs())
+            .saturating_add(1)
+    }
+
+    /// Checks if `value` is in the range of integers this width can represent.
+    pub fn fits(&self, value: i128) -> bool {
+        if value < 0 {
+            value >= self.min_value()
+        } else {
+            value as u128 <= self.max_value()
+        }
+    }
+
     /// Checks if `self` represents superset of integers that `lower_bound` represents, on a particular
     /// side of the integers relative to 0.
     ///
     /// If `is_negative` is true, the negative side is checked; otherwise the positive side is checked.
+    ///
+    /// For the 64-bit widths (`U64` also covers what used to be `Nat`):
+    /// - on the positive side, `U64` is a superset of both `U64` and `I64`,
+    ///   but `I64` is only a superset of `I64`, since it can't hold `I64::MAX + 1..=U64::MAX`.
+    /// - on the negative side, `I64` is a superset of both, since the negative side of an unsigned
+    ///   width is empty, but `U64` is only a superset of `U64`.
     pub fn is_superset(&self, lower_bound: &Self, is_negative: bool) -> bool {
         use IntSignedness::*;
 
@@ -338,6 +553,43 @@ pub enum FloatWidth {
     F64,
 }
 
+impl FloatWidth {
+    /// Ranks the widths by precision: `F32 < F64 < Dec`.
+    /// `Dec` comes last because it has the most significant bits (and holds the most integers
+    /// exactly), even though `F64` can represent much larger magnitudes.
+    /// This is the same order as the float widths in [IntLitWidth::all].
+    pub const fn rank(&self) -> u8 {
+        match self {
+            FloatWidth::F32 => 0,
+            FloatWidth::F64 => 1,
+            FloatWidth::Dec => 2,
+        }
+    }
+}
+
+impl PartialOrd for FloatWidth {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloatWidth {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl From<FloatWidth> for IntLitWidth {
+    /// The width for integers that `width` stores without losing precision
+    fn from(width: FloatWidth) -> Self {
+        match width {
+            FloatWidth::F32 => IntLitWidth::F32,
+            FloatWidth::F64 => IntLitWidth::F64,
+            FloatWidth::Dec => IntLitWidth::Dec,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum SignDemand {
     /// Can be signed or unsigned.
@@ -396,6 +648,67 @@ impl SingleQuoteBound {
     }
 }
 
+/// Finds the narrowest standard integer width that can hold `value`, e.g. to suggest in an
+/// error message when a literal overflows. When no sign is demanded, unsigned widths are
+/// preferred over signed widths of the same size.
+pub fn suggest_width(value: i128, demanded_sign: SignDemand) -> Option<IntLitWidth> {
+    IntLitWidth::integer_widths()
+        .iter()
+        .copied()
+        .filter(|width| demanded_sign == SignDemand::NoDemand || width.is_signed())
+        .find(|width| width.fits(value))
+}
+
+/// The narrowest width, including the float widths, that a number literal with this integer
+/// value could have. A negative value demands a signed width.
+pub fn lower_bound_of_int_literal(result: i128) -> IntLitWidth {
+    use IntLitWidth::*;
+    if result >= 0 {
+        // Positive
+        let result = result as u128;
+        if result > U64.max_value() {
+            I128
+        } else if result > I64.max_value() {
+            U64
+        } else if result > F64.max_value() {
+            I64
+        } else if result > U32.max_value() {
+            F64
+        } else if result > I32.max_value() {
+            U32
+        } else if result > F32.max_value() {
+            I32
+        } else if result > U16.max_value() {
+            F32
+        } else if result > I16.max_value() {
+            U16
+        } else if result > U8.max_value() {
+            I16
+        } else if result > I8.max_value() {
+            U8
+        } else {
+            I8
+        }
+    } else {
+        // Negative
+        if result < I64.min_value() {
+            I128
+        } else if result < F64.min_value() {
+            I64
+        } else if result < I32.min_value() {
+            F64
+        } else if result < F32.min_value() {
+            I32
+        } else if result < I16.min_value() {
+            F32
+        } else if result < I8.min_value() {
+            I16
+        } else {
+            I8
+        }
+    }
+}
+
 pub const fn int_lit_width_to_variable(w: IntLitWidth) -> Variable {
     match w {
         IntLitWidth::U8 => Variable::U8,
@@ -469,3 +782,529 @@ const SIGNED_INT_VARIABLES: &[Variable] = &[
     Variable::I64,
     Variable::I128,
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exact_int_range() {
+        use IntLitWidth::*;
+        assert_eq!(F32.exact_int_range(), (-16_777_216, 16_777_216));
+        assert_eq!(
+            F64.exact_int_range(),
+            (-9_007_199_254_740_992, 9_007_199_254_740_992)
+        );
+        assert_eq!(Dec.exact_int_range(), (i128::MIN, i128::MAX));
+        assert_eq!(U8.exact_int_range(), (0, 255));
+        assert_eq!(U128.exact_int_range(), (0, i128::MAX));
+
+        // Every integer in the range survives a round trip through the float type
+        assert_eq!((16_777_216 as f32) as i128, 16_777_216);
+        assert_ne!((16_777_217 as f32) as i128, 16_777_217);
+        assert_ne!(
+            (9_007_199_254_740_993_i64 as f64) as i64,
+            9_007_199_254_740_993
+        );
+    }
+
+    #[test]
+    fn value_count() {
+        use IntLitWidth::*;
+        assert_eq!(U8.value_count(), 256);
+        assert_eq!(I8.value_count(), 256);
+        assert_eq!(I64.value_count(), 1 << 64);
+        assert_eq!(U128.value_count(), u128::MAX);
+        assert_eq!(I128.value_count(), u128::MAX);
+        assert_eq!(F32.value_count(), 2 * (1 << 24) + 1);
+        assert_eq!(F64.value_count(), 2 * (1 << 53) + 1);
+    }
+
+    #[test]
+    fn from_int_literal() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        let range = NumericRange::from_int_literal;
+        assert_eq!(range(0), NumAtLeastEitherSign(I8));
+        assert_eq!(range(5), NumAtLeastEitherSign(I8));
+        assert_eq!(range(-5), NumAtLeastSigned(I8));
+        assert_eq!(range(200), NumAtLeastEitherSign(U8));
+        assert_eq!(range(-200), NumAtLeastSigned(I16));
+        assert_eq!(range(40_000), NumAtLeastEitherSign(U16));
+        assert_eq!(range(1 << 24), NumAtLeastEitherSign(F32));
+        assert_eq!(range((1 << 24) + 1), NumAtLeastEitherSign(I32));
+        assert_eq!(range(u32::MAX as i128), NumAtLeastEitherSign(U32));
+        assert_eq!(range(1 << 60), NumAtLeastEitherSign(I64));
+        assert_eq!(range(u64::MAX as i128), NumAtLeastEitherSign(U64));
+        assert_eq!(range(i64::MIN as i128), NumAtLeastSigned(I64));
+        assert_eq!(range(i128::MIN), NumAtLeastSigned(I128));
+        assert_eq!(range(i128::MAX), NumAtLeastEitherSign(I128));
+    }
+
+    #[test]
+    fn widen() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+
+        let mut range = IntAtLeastSigned(I8);
+        let mut steps = vec![range];
+        while let Some(wider) = range.widen() {
+            range = wider;
+            steps.push(range);
+        }
+        assert_eq!(
+            steps,
+            [I8, I16, I32, I64, I128].map(IntAtLeastSigned).to_vec()
+        );
+
+        let mut range = IntAtLeastEitherSign(I8);
+        let mut steps = vec![range];
+        while let Some(wider) = range.widen() {
+            range = wider;
+            steps.push(range);
+        }
+        assert_eq!(
+            steps,
+            [I8, U8, I16, U16, I32, U32, I64, U64, I128, U128]
+                .map(IntAtLeastEitherSign)
+                .to_vec()
+        );
+
+        assert_eq!(
+            NumAtLeastEitherSign(U8).widen(),
+            Some(NumAtLeastEitherSign(I16))
+        );
+        assert_eq!(
+            NumAtLeastEitherSign(F32).widen(),
+            Some(NumAtLeastEitherSign(I32))
+        );
+        assert_eq!(
+            NumAtLeastEitherSign(F64).widen(),
+            Some(NumAtLeastEitherSign(I64))
+        );
+        assert_eq!(NumAtLeastSigned(I64).widen(), Some(NumAtLeastSigned(I128)));
+        assert_eq!(IntAtLeastEitherSign(U128).widen(), None);
+        assert_eq!(NumAtLeastSigned(I128).widen(), None);
+    }
+
+    #[test]
+    fn represents_same_set() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+
+        // Structurally different, but neither allows anything except U128
+        assert_ne!(NumAtLeastEitherSign(U128), IntAtLeastEitherSign(U128));
+        assert!(NumAtLeastEitherSign(U128).represents_same_set(&IntAtLeastEitherSign(U128)));
+        assert!(!NumAtLeastEitherSign(U128).is_tighter_than(&IntAtLeastEitherSign(U128)));
+        assert!(!IntAtLeastEitherSign(U128).is_tighter_than(&NumAtLeastEitherSign(U128)));
+
+        // Dec holds every I128, so the Num range allows one more type
+        assert!(!NumAtLeastSigned(I128).represents_same_set(&IntAtLeastSigned(I128)));
+        assert!(IntAtLeastSigned(I128).is_tighter_than(&NumAtLeastSigned(I128)));
+
+        assert!(IntAtLeastSigned(I8).represents_same_set(&IntAtLeastSigned(I8)));
+        assert!(!IntAtLeastSigned(I8).is_tighter_than(&IntAtLeastSigned(I8)));
+        assert!(IntAtLeastSigned(I16).is_tighter_than(&IntAtLeastEitherSign(I8)));
+        assert!(!IntAtLeastEitherSign(I8).is_tighter_than(&IntAtLeastSigned(I16)));
+        assert!(IntAtLeastSigned(I16).is_tighter_than(&IntAtLeastEitherSign(U8)));
+        // Each allows an 8-bit type the other doesn't, so neither is tighter
+        assert!(!IntAtLeastSigned(I8).is_tighter_than(&IntAtLeastEitherSign(U8)));
+        assert!(!IntAtLeastEitherSign(U8).is_tighter_than(&IntAtLeastSigned(I8)));
+    }
+
+    #[test]
+    fn contains_float_width() {
+        use FloatWidth::*;
+        let range = NumericRange::from_int_literal;
+
+        let big = 10i128.pow(20);
+        for value in [big, -big] {
+            assert!(!range(value).contains_float_width(F32));
+            assert!(!range(value).contains_float_width(F64));
+            assert!(range(value).contains_float_width(Dec));
+        }
+        assert!(range(i128::MAX).contains_float_width(Dec));
+        assert!(range(i128::MIN).contains_float_width(Dec));
+        assert!(!NumericRange::IntAtLeastEitherSign(IntLitWidth::U128).contains_float_width(Dec));
+
+        assert!(range(1 << 24).contains_float_width(F32));
+        assert!(!range((1 << 24) + 1).contains_float_width(F32));
+        assert!(range((1 << 24) + 1).contains_float_width(F64));
+        assert!(range(-(1 << 53)).contains_float_width(F64));
+        assert!(!range(-(1 << 53) - 1).contains_float_width(F64));
+    }
+
+    #[test]
+    fn float_width_order_and_conversion() {
+        use FloatWidth::*;
+        assert!(F32 < F64);
+        assert!(F64 < Dec);
+        assert_eq!([Dec, F64, F32].iter().max(), Some(&Dec));
+
+        let mut widths = [Dec, F64, F32];
+        widths.sort();
+        let int_widths: Vec<IntLitWidth> = widths.iter().map(|w| (*w).into()).collect();
+        assert_eq!(
+            int_widths,
+            [IntLitWidth::F32, IntLitWidth::F64, IntLitWidth::Dec]
+        );
+        assert_eq!(int_widths, &IntLitWidth::all()[10..]);
+    }
+
+    #[test]
+    fn from_variable() {
+        for width in IntLitWidth::all() {
+            let var = int_lit_width_to_variable(*width);
+            assert_eq!(IntLitWidth::from_variable(var), Some(*width));
+        }
+        assert_eq!(IntLitWidth::from_variable(Variable::STR), None);
+        assert_eq!(IntLitWidth::from_variable(Variable::EMPTY_RECORD), None);
+    }
+
+    #[test]
+    fn admits_float() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        assert!(!IntAtLeastSigned(I8).admits_float());
+        assert!(!IntAtLeastEitherSign(U8).admits_float());
+        assert!(NumAtLeastSigned(I8).admits_float());
+        assert!(NumAtLeastEitherSign(U8).admits_float());
+    }
+
+    #[test]
+    fn is_superset_64_bit_positive() {
+        use IntLitWidth::*;
+        assert!(U64.is_superset(&U64, false));
+        assert!(U64.is_superset(&I64, false));
+        assert!(!I64.is_superset(&U64, false));
+        assert!(I64.is_superset(&I64, false));
+    }
+
+    #[test]
+    fn is_superset_64_bit_negative() {
+        use IntLitWidth::*;
+        assert!(U64.is_superset(&U64, true));
+        assert!(!U64.is_superset(&I64, true));
+        assert!(I64.is_superset(&U64, true));
+        assert!(I64.is_superset(&I64, true));
+    }
+
+    #[test]
+    fn is_superset_matches_ranges() {
+        // On each side of 0, a width is a superset of another iff its range reaches at least as far
+        for width in IntLitWidth::all() {
+            for lower_bound in IntLitWidth::all() {
+                assert_eq!(
+                    width.is_superset(lower_bound, false),
+                    width.max_value() >= lower_bound.max_value(),
+                    "{width:?} is_superset {lower_bound:?} on the positive side"
+                );
+                assert_eq!(
+                    width.is_superset(lower_bound, true),
+                    width.min_value() <= lower_bound.min_value(),
+                    "{width:?} is_superset {lower_bound:?} on the negative side"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn is_superset_signedness() {
+        use IntLitWidth::*;
+        let unsigned = [U8, U16, U32, U64, U128];
+        let signed = [I8, I16, I32, I64, I128];
+
+        for (u, i) in unsigned.iter().zip(signed.iter()) {
+            // Positive side: an unsigned width holds everything its signed counterpart does, and more
+            assert!(u.is_superset(i, false), "{u:?} {i:?}");
+            assert!(!i.is_superset(u, false), "{i:?} {u:?}");
+            // Negative side: it's the other way around, since unsigned widths hold no negatives
+            assert!(!u.is_superset(i, true), "{u:?} {i:?}");
+            assert!(i.is_superset(u, true), "{i:?} {u:?}");
+        }
+        // A signed width needs strictly more bits than an unsigned one on the positive side
+        for (u, i) in unsigned.iter().zip(signed.iter().skip(1)) {
+            assert!(i.is_superset(u, false), "{i:?} {u:?}");
+            assert!(!u.is_superset(i, false), "{u:?} {i:?}");
+        }
+    }
+
+    #[test]
+    fn contains_int_width_64_bit_either_sign() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        for range in [IntAtLeastEitherSign(U64), NumAtLeastEitherSign(U64)] {
+            assert!(range.contains_int_width(U64));
+            assert!(!range.contains_int_width(I64));
+            assert!(range.contains_int_width(I128));
+        }
+        for range in [IntAtLeastEitherSign(I64), NumAtLeastEitherSign(I64)] {
+            assert!(range.contains_int_width(U64));
+            assert!(range.contains_int_width(I64));
+            assert!(!range.contains_int_width(U32));
+        }
+    }
+
+    #[test]
+    fn contains_int_width_64_bit_signed() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        for range in [IntAtLeastSigned(I64), NumAtLeastSigned(I64)] {
+            assert!(!range.contains_int_width(U64));
+            assert!(range.contains_int_width(I64));
+            assert!(!range.contains_int_width(U128));
+            assert!(range.contains_int_width(I128));
+        }
+    }
+
+    #[test]
+    fn int_lit_width_all() {
+        use IntLitWidth::*;
+        let all = IntLitWidth::all();
+        for width in all {
+            // Exhaustive, so adding a variant fails to compile until it's added to `all`
+            match width {
+                U8 | U16 | U32 | U64 | U128 | I8 | I16 | I32 | I64 | I128 | F32 | F64 | Dec => {}
+            }
+            assert_eq!(all.iter().filter(|w| *w == width).count(), 1, "{width:?}");
+        }
+        assert_eq!(all.len(), 13);
+
+        let integers = IntLitWidth::integer_widths();
+        assert_eq!(integers, &all[..integers.len()]);
+        for float in [F32, F64, Dec] {
+            assert!(!integers.contains(&float));
+        }
+        for pair in integers.windows(2) {
+            let (_, narrower) = pair[0].signedness_and_width();
+            let (_, wider) = pair[1].signedness_and_width();
+            assert!(narrower <= wider, "{pair:?}");
+        }
+    }
+
+    #[test]
+    fn numeric_range_new() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        use SignDemand::*;
+        assert_eq!(NumericRange::new(true, Signed, I16), IntAtLeastSigned(I16));
+        assert_eq!(
+            NumericRange::new(true, NoDemand, U8),
+            IntAtLeastEitherSign(U8)
+        );
+        assert_eq!(NumericRange::new(false, Signed, I32), NumAtLeastSigned(I32));
+        assert_eq!(
+            NumericRange::new(false, NoDemand, U64),
+            NumAtLeastEitherSign(U64)
+        );
+    }
+
+    #[test]
+    fn contains_int_width_agrees_with_variable_slice() {
+        let all_widths = IntLitWidth::integer_widths();
+        for &at_least in all_widths {
+            for range in [
+                NumericRange::IntAtLeastEitherSign(at_least),
+                NumericRange::IntAtLeastSigned(at_least),
+            ] {
+                if range == NumericRange::IntAtLeastSigned(at_least) && !at_least.is_signed() {
+                    continue;
+                }
+                let slice = range.variable_slice();
+                for &width in all_widths {
+                    assert_eq!(
+                        range.contains_int_width(width),
+                        slice.contains(&int_lit_width_to_variable(width)),
+                        "{range:?} {width:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_to_range_in_content() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        let range = IntAtLeastEitherSign(U16);
+        assert_eq!(
+            range.narrow_to(&NumAtLeastEitherSign(U8)),
+            MatchResult::RangeInContent
+        );
+        assert_eq!(range.narrow_to(&range), MatchResult::RangeInContent);
+    }
+
+    #[test]
+    fn narrow_to_content_in_range() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        let range = NumAtLeastEitherSign(U8);
+        assert_eq!(
+            range.narrow_to(&IntAtLeastEitherSign(U16)),
+            MatchResult::ContentInRange
+        );
+        assert_eq!(
+            range.narrow_to(&IntAtLeastEitherSign(U8)),
+            MatchResult::ContentInRange
+        );
+    }
+
+    #[test]
+    fn narrow_to_no_intersection() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        assert_eq!(
+            IntAtLeastSigned(I8).narrow_to(&IntAtLeastEitherSign(U8)),
+            MatchResult::NoIntersection
+        );
+        assert_eq!(
+            NumAtLeastEitherSign(U128).narrow_to(&NumAtLeastSigned(I8)),
+            MatchResult::NoIntersection
+        );
+    }
+
+    #[test]
+    fn union_same_kind() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        assert_eq!(
+            IntAtLeastSigned(I8).union(&IntAtLeastSigned(I32)),
+            IntAtLeastSigned(I32)
+        );
+        assert_eq!(
+            NumAtLeastEitherSign(U64).union(&NumAtLeastEitherSign(U16)),
+            NumAtLeastEitherSign(U64)
+        );
+    }
+
+    #[test]
+    fn union_mixed_sign() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        // Only one of them demands a sign, so only the positive side of the widths counts
+        assert_eq!(
+            IntAtLeastSigned(I8).union(&IntAtLeastEitherSign(U8)),
+            IntAtLeastEitherSign(U8)
+        );
+        assert_eq!(
+            IntAtLeastSigned(I32).union(&IntAtLeastEitherSign(U8)),
+            IntAtLeastEitherSign(I32)
+        );
+        assert_eq!(
+            NumAtLeastSigned(I16).union(&NumAtLeastEitherSign(U32)),
+            NumAtLeastEitherSign(U32)
+        );
+    }
+
+    #[test]
+    fn union_int_and_num() {
+        use IntLitWidth::*;
+        use NumericRange::*;
+        assert_eq!(
+            IntAtLeastSigned(I8).union(&NumAtLeastSigned(I32)),
+            NumAtLeastSigned(I32)
+        );
+        assert_eq!(
+            IntAtLeastEitherSign(U8).union(&NumAtLeastSigned(I8)),
+            NumAtLeastEitherSign(U8)
+        );
+    }
+
+    #[test]
+    fn union_takes_wider_width_and_looser_demands() {
+        let all_ranges: std::vec::Vec<NumericRange> = IntLitWidth::integer_widths()
+            .iter()
+            .flat_map(|&width| {
+                let signed = [true, false].map(|int_only| {
+                    width
+                        .is_signed()
+                        .then(|| NumericRange::new(int_only, SignDemand::Signed, width))
+                });
+                let either_sign = [true, false]
+                    .map(|int_only| Some(NumericRange::new(int_only, SignDemand::NoDemand, width)));
+                signed.into_iter().chain(either_sign).flatten()
+            })
+            .collect();
+
+        let is_signed = |range: &NumericRange| {
+            matches!(
+                range,
+                NumericRange::IntAtLeastSigned(_) | NumericRange::NumAtLeastSigned(_)
+            )
+        };
+
+        for a in &all_ranges {
+            assert_eq!(a.union(a), *a);
+            for b in &all_ranges {
+                let union = a.union(b);
+                assert_eq!(union, b.union(a), "{a:?} {b:?}");
+                // The width is one of the two, and holds the numbers of both
+                let width = union.min_width();
+                assert!(width == a.min_width() || width == b.min_width());
+                for x in [a, b] {
+                    assert!(width.is_superset(&x.min_width(), false), "{a:?} {b:?}");
+                    if is_signed(&union) {
+                        assert!(width.is_superset(&x.min_width(), true), "{a:?} {b:?}");
+                    }
+                }
+                // It's only as strict as the more permissive of the two
+                assert_eq!(union.admits_float(), a.admits_float() || b.admits_float());
+                assert_eq!(is_signed(&union), is_signed(a) && is_signed(b));
+            }
+        }
+    }
+
+    #[test]
+    fn match_result_is_compatible() {
+        assert!(MatchResult::RangeInContent.is_compatible());
+        assert!(MatchResult::ContentInRange.is_compatible());
+        assert!(!MatchResult::NoIntersection.is_compatible());
+    }
+
+    #[test]
+    fn suggest_width_256() {
+        assert_eq!(
+            suggest_width(256, SignDemand::NoDemand),
+            Some(IntLitWidth::U16)
+        );
+        assert_eq!(
+            suggest_width(256, SignDemand::Signed),
+            Some(IntLitWidth::I16)
+        );
+    }
+
+    #[test]
+    fn suggest_width_negative_must_be_signed() {
+        assert_eq!(
+            suggest_width(-1, SignDemand::NoDemand),
+            Some(IntLitWidth::I8)
+        );
+        assert_eq!(suggest_width(-1, SignDemand::Signed), Some(IntLitWidth::I8));
+    }
+
+    #[test]
+    fn suggest_width_i128() {
+        let value = i64::MIN as i128 - 1;
+        assert_eq!(
+            suggest_width(value, SignDemand::NoDemand),
+            Some(IntLitWidth::I128)
+        );
+        assert_eq!(
+            suggest_width(i128::MIN, SignDemand::Signed),
+            Some(IntLitWidth::I128)
+        );
+    }
+
+    #[test]
+    fn suggest_width_u128() {
+        let value = u64::MAX as i128 + 1;
+        assert_eq!(
+            suggest_width(value, SignDemand::NoDemand),
+            Some(IntLitWidth::U128)
+        );
+        assert_eq!(
+            suggest_width(value, SignDemand::Signed),
+            Some(IntLitWidth::I128)
+        );
+    }
+}
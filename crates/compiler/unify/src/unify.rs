@@ -8,7 +8,7 @@ use roc_error_macros::{internal_error, todo_lambda_erasure};
 use roc_module::ident::{Lowercase, TagName};
 use roc_module::symbol::{ModuleId, Symbol};
 use roc_solve_schema::UnificationMode;
-use roc_types::num::{FloatWidth, IntLitWidth, NumericRange};
+use roc_types::num::{FloatWidth, IntLitWidth, MatchResult, NumericRange};
 use roc_types::subs::Content::{self, *};
 use roc_types::subs::{
     AliasVariables, Descriptor, ErrorTypeContext, FlatType, GetSubsSlice, LambdaSet, Mark,
@@ -605,15 +605,10 @@ fn check_and_merge_valid_range<M: MetaCollector>(
     }
 
     match content {
-        RangedNumber(other_range) => match range.intersection(&other_range) {
-            Some(r) => {
-                if r == range {
-                    merge(env, ctx, RangedNumber(range))
-                } else {
-                    merge(env, ctx, RangedNumber(other_range))
-                }
-            }
-            None => not_in_range_mismatch(),
+        RangedNumber(other_range) => match range.narrow_to(&other_range) {
+            MatchResult::RangeInContent => merge(env, ctx, RangedNumber(range)),
+            MatchResult::ContentInRange => merge(env, ctx, RangedNumber(other_range)),
+            MatchResult::NoIntersection => not_in_range_mismatch(),
         },
         Alias(symbol, args, _real_var, kind) => match symbol {
             Symbol::NUM_I8 | Symbol::NUM_SIGNED8 => {
@@ -683,6 +678,8 @@ fn check_and_merge_valid_range<M: MetaCollector>(
             _ => mismatch!(),
         },
 
+        // Anything that isn't a number, like a `Str` or a record, has no range to check against.
+        // It's reported as a plain type mismatch rather than a literal that's out of range.
         _ => mismatch!(),
     }
 }
@@ -1,7 +1,7 @@
 use bumpalo::Bump;
 use regex::Regex;
 use roc_wasm_interp::{
-    wasi, DefaultImportDispatcher, ImportDispatcher, Instance, Value, WasiDispatcher,
+    wasi, DefaultImportDispatcher, ImportDispatcher, Instance, Value, WasiDispatcher, WasiResult,
 };
 
 const COMPILER_BYTES: &[u8] =
@@ -32,8 +32,13 @@ impl<'a> ImportDispatcher for CompilerDispatcher<'a> {
         };
 
         if module_name == wasi::MODULE_NAME {
-            self.wasi
+            match self
+                .wasi
                 .dispatch(function_name, arguments, compiler_memory)
+            {
+                WasiResult::Return(value) => value,
+                WasiResult::Exit(exit_code) => panic!("The program called proc_exit({exit_code})"),
+            }
         } else if module_name == "env" {
             match function_name {
                 "test_create_app" => {
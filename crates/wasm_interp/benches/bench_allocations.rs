@@ -0,0 +1,55 @@
+//! Count the heap allocations of a deeply recursive call, to show what reserving the value stack
+//! saves. This has its own bench target so the counting allocator doesn't slow down the timed
+//! benches in bench_interp.
+
+mod modules;
+
+use bumpalo::Bump;
+use roc_wasm_interp::{DefaultImportDispatcher, Instance};
+use roc_wasm_module::Value;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Recurse 10k calls deep, optionally reserving the value stack first
+fn count_allocations(name: &str, value_stack_capacity: Option<usize>) {
+    const DEPTH: i32 = 10_000;
+
+    let module_arena = Bump::new();
+    let module = modules::recursive_module(&module_arena);
+
+    let arena = Bump::new();
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    if let Some(capacity) = value_stack_capacity {
+        inst = inst.with_value_stack_capacity(capacity);
+    }
+    inst.call_export("sum", [Value::I32(DEPTH)]).unwrap();
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+    println!("{name}: {allocations} heap allocations per run");
+}
+
+fn main() {
+    count_allocations("interp recursion", None);
+    count_allocations("interp recursion, reserved stack", Some(64 * 1024));
+}
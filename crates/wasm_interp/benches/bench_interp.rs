@@ -0,0 +1,132 @@
+mod modules;
+
+use bumpalo::Bump;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use roc_wasm_interp::{DefaultImportDispatcher, Instance};
+use roc_wasm_module::{
+    opcodes::OpCode, Export, ExportType, SerialBuffer, Signature, Value, ValueType, WasmModule,
+};
+
+/// A single exported function `arith(n: i32) -> i32` that runs an arithmetic-heavy loop
+///
+///     total = 0
+///     do {
+///         n = n - 1
+///         total = (total * 31) ^ (n + 7)
+///     } while n != 0
+///     return total
+fn arith_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    let var_n = 0;
+    let var_total = 1;
+
+    let buf = &mut module.code.bytes;
+    let fn_len_index = buf.encode_padded_u32(0);
+
+    // (local i32)
+    buf.push(1);
+    buf.push(1);
+    buf.push(ValueType::I32 as u8);
+
+    buf.push(OpCode::LOOP as u8);
+    buf.push(ValueType::VOID);
+
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(var_n);
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(1);
+    buf.push(OpCode::I32SUB as u8);
+    buf.push(OpCode::SETLOCAL as u8);
+    buf.encode_u32(var_n);
+
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(var_total);
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(31);
+    buf.push(OpCode::I32MUL as u8);
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(var_n);
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(7);
+    buf.push(OpCode::I32ADD as u8);
+    buf.push(OpCode::I32XOR as u8);
+    buf.push(OpCode::SETLOCAL as u8);
+    buf.encode_u32(var_total);
+
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(var_n);
+    buf.push(OpCode::BRIF as u8);
+    buf.encode_u32(0);
+    buf.push(OpCode::END as u8);
+
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(var_total);
+    buf.push(OpCode::END as u8);
+
+    buf.overwrite_padded_u32(fn_len_index, (buf.len() - fn_len_index) as u32);
+
+    module.code.function_offsets.push(0);
+    module.code.function_count = 1;
+    module.add_function_signature(Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    });
+    module.export.append(Export {
+        name: "arith",
+        ty: ExportType::Func,
+        index: 0,
+    });
+
+    module
+}
+
+fn bench_arith(c: &mut Criterion, name: &str, verify: bool) {
+    c.bench_function(name, |b| {
+        let module_arena = Bump::new();
+        let module = arith_module(&module_arena);
+
+        b.iter(|| {
+            // A fresh arena each time, so the instances don't pile up in one that keeps growing
+            let arena = Bump::new();
+            let mut inst =
+                Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+                    .unwrap();
+            inst.set_verify(verify);
+            let result = inst.call_export("arith", [Value::I32(10_000)]).unwrap();
+            black_box(result);
+        })
+    });
+}
+
+/// Recurse 10k calls deep, optionally reserving the value stack first.
+/// The bench_allocations target counts the heap allocations this saves.
+fn bench_recursion(c: &mut Criterion, name: &str, value_stack_capacity: Option<usize>) {
+    const DEPTH: i32 = 10_000;
+
+    let module_arena = Bump::new();
+    let module = modules::recursive_module(&module_arena);
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            let arena = Bump::new();
+            let mut inst =
+                Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+                    .unwrap();
+            if let Some(capacity) = value_stack_capacity {
+                inst = inst.with_value_stack_capacity(capacity);
+            }
+            black_box(inst.call_export("sum", [Value::I32(DEPTH)]).unwrap());
+        })
+    });
+}
+
+pub fn interp_benchmark(c: &mut Criterion) {
+    bench_arith(c, "interp arithmetic loop", false);
+    bench_arith(c, "interp arithmetic loop, verified", true);
+    bench_recursion(c, "interp recursion", None);
+    bench_recursion(c, "interp recursion, reserved stack", Some(64 * 1024));
+}
+
+criterion_group!(benches, interp_benchmark);
+criterion_main!(benches);
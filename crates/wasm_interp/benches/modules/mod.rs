@@ -0,0 +1,60 @@
+//! Wasm modules shared by the interpreter benches
+
+use bumpalo::Bump;
+use roc_wasm_module::{
+    opcodes::OpCode, Export, ExportType, SerialBuffer, Signature, ValueType, WasmModule,
+};
+
+/// A single exported function `sum(n: i32) -> i32` that recurses `n` calls deep
+///
+///     if n != 0 {
+///         return n + sum(n - 1)
+///     }
+///     return 0
+pub fn recursive_module(arena: &Bump) -> WasmModule<'_> {
+    let mut module = WasmModule::new(arena);
+
+    let buf = &mut module.code.bytes;
+    let fn_len_index = buf.encode_padded_u32(0);
+
+    // no locals
+    buf.push(0);
+
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(0);
+    buf.push(OpCode::IF as u8);
+    buf.push(ValueType::VOID);
+
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(0);
+    buf.push(OpCode::GETLOCAL as u8);
+    buf.encode_u32(0);
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(1);
+    buf.push(OpCode::I32SUB as u8);
+    buf.push(OpCode::CALL as u8);
+    buf.encode_u32(0);
+    buf.push(OpCode::I32ADD as u8);
+    buf.push(OpCode::RETURN as u8);
+    buf.push(OpCode::END as u8);
+
+    buf.push(OpCode::I32CONST as u8);
+    buf.encode_i32(0);
+    buf.push(OpCode::END as u8);
+
+    buf.overwrite_padded_u32(fn_len_index, (buf.len() - fn_len_index) as u32);
+
+    module.code.function_offsets.push(0);
+    module.code.function_count = 1;
+    module.add_function_signature(Signature {
+        param_types: bumpalo::vec![in arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    });
+    module.export.append(Export {
+        name: "sum",
+        ty: ExportType::Func,
+        index: 0,
+    });
+
+    module
+}
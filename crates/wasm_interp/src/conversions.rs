@@ -0,0 +1,157 @@
+use roc_wasm_module::{Value, ValueType};
+
+/// Arguments for a call to an exported Wasm function.
+/// Implemented for arrays and Vecs of [Value], and for tuples of Rust numbers.
+pub trait IntoValues {
+    type Iter: Iterator<Item = Value>;
+
+    fn into_values(self) -> Self::Iter;
+}
+
+impl<const N: usize> IntoValues for [Value; N] {
+    type Iter = std::array::IntoIter<Value, N>;
+
+    fn into_values(self) -> Self::Iter {
+        self.into_iter()
+    }
+}
+
+impl IntoValues for Vec<Value> {
+    type Iter = std::vec::IntoIter<Value>;
+
+    fn into_values(self) -> Self::Iter {
+        self.into_iter()
+    }
+}
+
+macro_rules! tuple_into_values {
+    ($n: literal; $($name: ident),*) => {
+        impl<$($name: Into<Value>),*> IntoValues for ($($name,)*) {
+            type Iter = std::array::IntoIter<Value, $n>;
+
+            #[allow(non_snake_case)]
+            fn into_values(self) -> Self::Iter {
+                let ($($name,)*) = self;
+                [$($name.into()),*].into_iter()
+            }
+        }
+    };
+}
+
+tuple_into_values!(0;);
+tuple_into_values!(1; A);
+tuple_into_values!(2; A, B);
+tuple_into_values!(3; A, B, C);
+tuple_into_values!(4; A, B, C, D);
+
+/// The return value of a call to an exported Wasm function, converted to a Rust type.
+pub trait FromValues: Sized {
+    fn from_values(value: Option<Value>) -> Result<Self, String>;
+}
+
+impl FromValues for () {
+    fn from_values(value: Option<Value>) -> Result<Self, String> {
+        match value {
+            None => Ok(()),
+            Some(value) => Err(format!("Expected no return value but got {value:?}")),
+        }
+    }
+}
+
+impl FromValues for Option<Value> {
+    fn from_values(value: Option<Value>) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+macro_rules! number_from_values {
+    ($ty: ty, $value_type: expr) => {
+        impl FromValues for $ty {
+            fn from_values(value: Option<Value>) -> Result<Self, String> {
+                match value {
+                    Some(value) => <$ty>::try_from(value).map_err(|(expected, actual)| {
+                        format!("Expected a return value of type {expected:?} but got {actual:?}")
+                    }),
+                    None => Err(format!(
+                        "Expected a return value of type {:?} but got nothing",
+                        $value_type
+                    )),
+                }
+            }
+        }
+    };
+}
+
+number_from_values!(i32, ValueType::I32);
+number_from_values!(u32, ValueType::I32);
+number_from_values!(i64, ValueType::I64);
+number_from_values!(u64, ValueType::I64);
+number_from_values!(f32, ValueType::F32);
+number_from_values!(f64, ValueType::F64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T>(x: T) -> T
+    where
+        T: Into<Value> + TryFrom<Value, Error = (ValueType, ValueType)>,
+    {
+        T::try_from(x.into()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(round_trip(-123i32), -123);
+        assert_eq!(round_trip(u32::MAX), u32::MAX);
+        assert_eq!(round_trip(i64::MIN), i64::MIN);
+        assert_eq!(round_trip(u64::MAX), u64::MAX);
+        assert_eq!(round_trip(1.5f32), 1.5);
+        assert_eq!(round_trip(-2.25f64), -2.25);
+    }
+
+    #[test]
+    fn test_try_from_mismatch() {
+        assert_eq!(
+            i32::try_from(Value::I64(1)),
+            Err((ValueType::I32, ValueType::I64))
+        );
+        assert_eq!(
+            u64::try_from(Value::I32(1)),
+            Err((ValueType::I64, ValueType::I32))
+        );
+        assert_eq!(
+            f32::try_from(Value::F64(1.0)),
+            Err((ValueType::F32, ValueType::F64))
+        );
+        assert_eq!(
+            f64::try_from(Value::I64(1)),
+            Err((ValueType::F64, ValueType::I64))
+        );
+    }
+
+    #[test]
+    fn test_into_values() {
+        let values: Vec<Value> = (1i32, 2u64, 3.0f32, 4.0f64).into_values().collect();
+        assert_eq!(
+            values,
+            [
+                Value::I32(1),
+                Value::I64(2),
+                Value::F32(3.0),
+                Value::F64(4.0)
+            ]
+        );
+        assert_eq!(().into_values().count(), 0);
+    }
+
+    #[test]
+    fn test_from_values() {
+        assert_eq!(i32::from_values(Some(Value::I32(-1))), Ok(-1));
+        assert_eq!(u32::from_values(Some(Value::I32(-1))), Ok(u32::MAX));
+        assert_eq!(<()>::from_values(None), Ok(()));
+        assert!(i64::from_values(Some(Value::I32(1))).is_err());
+        assert!(f64::from_values(None).is_err());
+        assert!(<()>::from_values(Some(Value::I32(1))).is_err());
+    }
+}
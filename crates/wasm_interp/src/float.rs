@@ -0,0 +1,122 @@
+//! Float operations whose Wasm semantics differ from Rust's standard library
+
+/// The parts of `f32` and `f64` needed to implement Wasm float operations generically
+pub(crate) trait Float: Copy + PartialOrd {
+    const NAN: Self;
+    fn is_nan(self) -> bool;
+    fn is_sign_negative(self) -> bool;
+}
+
+macro_rules! impl_float {
+    ($ty: ty) => {
+        impl Float for $ty {
+            const NAN: Self = <$ty>::NAN;
+
+            fn is_nan(self) -> bool {
+                <$ty>::is_nan(self)
+            }
+
+            fn is_sign_negative(self) -> bool {
+                <$ty>::is_sign_negative(self)
+            }
+        }
+    };
+}
+
+impl_float!(f32);
+impl_float!(f64);
+
+/// Wasm `fN.min`. Unlike Rust's `min`, NaN in either argument gives NaN, and -0.0 is less than +0.0.
+/// https://webassembly.github.io/spec/core/exec/numerics.html#op-fmin
+pub(crate) fn wasm_fmin<F: Float>(a: F, b: F) -> F {
+    if a.is_nan() || b.is_nan() {
+        F::NAN
+    } else if a == b {
+        // Only differ if they're zeros of opposite sign
+        if a.is_sign_negative() {
+            a
+        } else {
+            b
+        }
+    } else if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Wasm `fN.max`. Unlike Rust's `max`, NaN in either argument gives NaN, and +0.0 is greater than -0.0.
+/// https://webassembly.github.io/spec/core/exec/numerics.html#op-fmax
+pub(crate) fn wasm_fmax<F: Float>(a: F, b: F) -> F {
+    if a.is_nan() || b.is_nan() {
+        F::NAN
+    } else if a == b {
+        // Only differ if they're zeros of opposite sign
+        if a.is_sign_negative() {
+            b
+        } else {
+            a
+        }
+    } else if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAN: f64 = f64::NAN;
+    const INF: f64 = f64::INFINITY;
+
+    /// (a, b, min, max)
+    const CASES: &[(f64, f64, f64, f64)] = &[
+        // signed zeros, in both argument orders
+        (-0.0, 0.0, -0.0, 0.0),
+        (0.0, -0.0, -0.0, 0.0),
+        (-0.0, -0.0, -0.0, -0.0),
+        (0.0, 0.0, 0.0, 0.0),
+        // NaN in either or both arguments
+        (NAN, 1.0, NAN, NAN),
+        (1.0, NAN, NAN, NAN),
+        (NAN, NAN, NAN, NAN),
+        (NAN, -0.0, NAN, NAN),
+        (INF, NAN, NAN, NAN),
+        // ordinary ordering
+        (1.0, 2.0, 1.0, 2.0),
+        (2.0, 1.0, 1.0, 2.0),
+        (-1.5, 1.5, -1.5, 1.5),
+        (-0.0, 1.0, -0.0, 1.0),
+        (0.0, -1.0, -1.0, 0.0),
+        (-INF, INF, -INF, INF),
+        (3.0, 3.0, 3.0, 3.0),
+    ];
+
+    /// Compare by Debug format, which distinguishes -0.0 from 0.0, and shows all NaNs the same
+    fn assert_same<F: std::fmt::Debug>(op: &str, a: F, b: F, actual: F, expected: F) {
+        assert_eq!(
+            format!("{actual:?}"),
+            format!("{expected:?}"),
+            "{op}({a:?}, {b:?})"
+        );
+    }
+
+    #[test]
+    fn test_min_max_f64() {
+        for &(a, b, min, max) in CASES {
+            assert_same("min", a, b, wasm_fmin(a, b), min);
+            assert_same("max", a, b, wasm_fmax(a, b), max);
+        }
+    }
+
+    #[test]
+    fn test_min_max_f32() {
+        for &(a, b, min, max) in CASES {
+            let (a, b, min, max) = (a as f32, b as f32, min as f32, max as f32);
+            assert_same("min", a, b, wasm_fmin(a, b), min);
+            assert_same("max", a, b, wasm_fmax(a, b), max);
+        }
+    }
+}
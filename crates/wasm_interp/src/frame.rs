@@ -2,10 +2,13 @@ use roc_wasm_module::{parse::Parse, Value, ValueType};
 use std::iter::repeat;
 
 use crate::value_store::ValueStore;
+use crate::Error;
 
 #[derive(Debug)]
 pub struct Frame {
-    /// The function this frame belongs to
+    /// The function this frame belongs to, including the import count.
+    /// Stack traces read this directly rather than looking up the program counter
+    /// in the code section's function offsets.
     pub fn_index: usize,
     /// Address in the code section where this frame returns to
     pub return_addr: usize,
@@ -19,6 +22,18 @@ pub struct Frame {
     pub return_type: Option<ValueType>,
 }
 
+/// A function call on the stack, as passed to a [TrapHandler](crate::TrapHandler)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo<'a> {
+    /// The function's index, including the import count
+    pub fn_index: usize,
+    /// The function's name from the name section, or an empty string if it has none
+    pub fn_name: &'a str,
+    /// File offset of the instruction this frame is executing.
+    /// That's the call to the next frame, or for the innermost frame, the program counter.
+    pub file_offset: usize,
+}
+
 impl Frame {
     pub fn new() -> Self {
         Frame {
@@ -70,13 +85,27 @@ impl Frame {
         }
     }
 
-    pub fn get_local(&self, values: &ValueStore<'_>, index: u32) -> Value {
-        debug_assert!((index as usize) < self.locals_count);
-        *values.get(self.locals_start + index as usize).unwrap()
+    pub fn get_local(&self, values: &ValueStore<'_>, index: u32) -> Result<Value, Error> {
+        if index as usize >= self.locals_count {
+            return Err(Error::InvalidCode(format!(
+                "Local {index} doesn't exist. The current function has {} args & locals",
+                self.locals_count
+            )));
+        }
+        // Invalid code can pop a local off the stack
+        values
+            .get(self.locals_start + index as usize)
+            .copied()
+            .ok_or(Error::StackEmpty)
     }
 
     pub fn set_local(&self, values: &mut ValueStore<'_>, index: u32, value: Value) {
         debug_assert!((index as usize) < self.locals_count);
         values.set(self.locals_start + index as usize, value)
     }
+
+    /// All the args & locals, in index order
+    pub fn locals<'v>(&self, values: &'v ValueStore<'_>) -> &'v [Value] {
+        values.get_range(self.locals_start..self.locals_start + self.locals_count)
+    }
 }
@@ -2,20 +2,54 @@ use bumpalo::{collections::Vec, Bump};
 use std::fmt::{self, Write};
 use std::iter::{self, once, Iterator};
 
-use roc_wasm_module::opcodes::{MemoryInstruction, OpCode};
+use roc_wasm_module::opcodes::{
+    AtomicInstruction, MemoryInstruction, OpCode, LOOKUP_TABLE, MEMARG_MEMORY_INDEX_FLAG,
+};
 use roc_wasm_module::parse::{Parse, SkipBytes};
-use roc_wasm_module::sections::{ImportDesc, MemorySection, SignatureParamsIter};
-use roc_wasm_module::{ExportType, WasmModule};
+use roc_wasm_module::sections::{
+    DataMode, GlobalType, ImportDesc, MemorySection, RefType, SignatureParamsIter,
+};
+use roc_wasm_module::{Export, ExportType, WasmModule};
 use roc_wasm_module::{Value, ValueType};
 
-use crate::frame::Frame;
+use crate::conversions::{FromValues, IntoValues};
+use crate::float::{wasm_fmax, wasm_fmin};
+use crate::frame::{Frame, FrameInfo};
 use crate::value_store::ValueStore;
-use crate::{Error, ImportDispatcher};
+use crate::{Error, ImportDispatcher, TrapKind};
 
 #[derive(Debug)]
 pub enum Action {
     Continue,
     Break,
+    /// An import asked the program to stop, like WASI `proc_exit`
+    Exit,
+}
+
+/// A callback for errors that stop the program, installed with [Instance::set_trap_handler].
+/// It gets the call stack, outermost function first.
+pub type TrapHandler<'a> = Box<dyn FnMut(&Error, &[FrameInfo<'a>]) + 'a>;
+
+/// Holds the [TrapHandler], since closures aren't `Debug`
+struct TrapHandlerSlot<'a>(Option<TrapHandler<'a>>);
+
+impl fmt::Debug for TrapHandlerSlot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Some(TrapHandler)"),
+            None => write!(f, "None"),
+        }
+    }
+}
+
+/// The state of an exported function call after [Instance::run_n]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunProgress {
+    /// The function returned, with this return value.
+    /// Also the result when an import asks the program to exit. See [crate::ImportDispatcher::exit_code].
+    Completed(Option<Value>),
+    /// The instruction budget ran out. Call `run_n` again to continue from where it stopped.
+    Yielded,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -30,8 +64,12 @@ enum BlockType {
 struct Block {
     ty: BlockType,
     vstack: usize,
+    /// Number of values a branch to this block carries: the results of a block, or the params of a loop
+    arity: usize,
 }
 
+type OpHandler<'a, I> = fn(&mut Instance<'a, I>, &WasmModule<'a>) -> Result<Action, Error>;
+
 #[derive(Debug, Clone)]
 struct BranchCacheEntry {
     addr: u32,
@@ -44,6 +82,21 @@ pub struct Instance<'a, I: ImportDispatcher> {
     pub(crate) module: &'a WasmModule<'a>,
     /// Contents of the WebAssembly instance's memory
     pub memory: Vec<'a, u8>,
+    /// Memories 1 and up, for modules using the multi-memory proposal.
+    /// Memory 0 is `memory`, since that's the one WASI and the host work with.
+    pub other_memories: Vec<'a, Vec<'a, u8>>,
+    /// Host-imposed limit on memory size in pages, applied on top of the module's own maximum
+    max_memory_pages: Option<u32>,
+    /// Host-imposed limit on the total bytes memory can grow by, over all runs of the instance
+    total_memory_budget: Option<u64>,
+    /// Total bytes memory has grown by since instantiation. Not reset by [Instance::reset].
+    total_memory_grown: u64,
+    /// Check the types of values written to locals, and of return values.
+    /// Most instructions check their operand types anyway, but these would otherwise
+    /// silently accept a wrongly-typed value and let it cause an error somewhere else.
+    verify: bool,
+    /// Trap on signed overflow in integer add, sub and mul, instead of wrapping as Wasm specifies
+    trap_on_overflow: bool,
     /// The current call frame
     pub(crate) current_frame: Frame,
     /// Previous call frames
@@ -52,6 +105,10 @@ pub struct Instance<'a, I: ImportDispatcher> {
     pub(crate) value_store: ValueStore<'a>,
     /// Values of any global variables
     pub(crate) globals: Vec<'a, Value>,
+    /// Type and mutability of each global variable
+    pub(crate) global_types: Vec<'a, GlobalType>,
+    /// Contents of each data segment available to `memory.init`. Empty if dropped.
+    data_segments: Vec<'a, &'a [u8]>,
     /// Index in the code section of the current instruction
     pub(crate) program_counter: usize,
     /// One entry per nested block. For loops, stores the address of the first instruction.
@@ -66,6 +123,24 @@ pub struct Instance<'a, I: ImportDispatcher> {
     import_arguments: Vec<'a, Value>,
     /// temporary storage for output using the --debug option
     debug_string: Option<String>,
+    /// Number of instructions executed since instantiation or the last reset
+    instructions_executed: u64,
+    /// Called instead of appending a stack trace to the error message
+    trap_handler: TrapHandlerSlot<'a>,
+}
+
+impl<'a> Instance<'a, Box<dyn ImportDispatcher>> {
+    /// Like [Instance::for_module], but the instance owns its dispatcher, and the dispatcher
+    /// doesn't borrow anything. This keeps the dispatcher's type out of the instance's type,
+    /// which is handy for storing an instance in a struct.
+    pub fn with_imports(
+        arena: &'a Bump,
+        module: &'a WasmModule<'a>,
+        import_dispatcher: Box<dyn ImportDispatcher>,
+        is_debug_mode: bool,
+    ) -> Result<Self, std::string::String> {
+        Self::for_module(arena, module, import_dispatcher, is_debug_mode)
+    }
 }
 
 impl<'a, I: ImportDispatcher> Instance<'a, I> {
@@ -81,13 +156,29 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         G: IntoIterator<Item = Value>,
     {
         let mem_bytes = memory_pages * MemorySection::PAGE_SIZE;
+        let globals = Vec::from_iter_in(globals, arena);
+        let global_types = Vec::from_iter_in(
+            globals.iter().map(|value| GlobalType {
+                value_type: ValueType::from(*value),
+                is_mutable: true,
+            }),
+            arena,
+        );
         Instance {
             module: arena.alloc(WasmModule::new(arena)),
             memory: Vec::from_iter_in(iter::repeat(0).take(mem_bytes as usize), arena),
+            other_memories: Vec::new_in(arena),
+            max_memory_pages: None,
+            total_memory_budget: None,
+            total_memory_grown: 0,
+            verify: cfg!(debug_assertions),
+            trap_on_overflow: false,
             current_frame: Frame::new(),
             previous_frames: Vec::new_in(arena),
             value_store: ValueStore::new(arena),
-            globals: Vec::from_iter_in(globals, arena),
+            globals,
+            global_types,
+            data_segments: Vec::new_in(arena),
             program_counter,
             blocks: Vec::new_in(arena),
             branch_cache: bumpalo::vec![in arena; bumpalo::vec![in arena]],
@@ -95,6 +186,8 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             import_dispatcher,
             import_arguments: Vec::new_in(arena),
             debug_string: Some(String::new()),
+            instructions_executed: 0,
+            trap_handler: TrapHandlerSlot(None),
         }
     }
 
@@ -115,24 +208,45 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         import_dispatcher: I,
         is_debug_mode: bool,
     ) -> Result<Self, std::string::String> {
-        let mem_bytes = module.memory.min_bytes().map_err(|e| {
-            format!(
-                "Error parsing Memory section at offset {:#x}:\n{}",
-                e.offset, e.message
+        // We don't handle imported memories or tables
+        let all_imports_supported = module.import.imports.iter().all(|imp| {
+            matches!(
+                imp.description,
+                ImportDesc::Func { .. } | ImportDesc::Global { .. }
             )
-        })?;
-        let mut memory = Vec::from_iter_in(iter::repeat(0).take(mem_bytes as usize), arena);
-        module.data.load_into(&mut memory)?;
-
-        let globals = module.global.initial_values(arena);
-
-        // We don't handle non-function import types (memories, tables, and globals),
-        // and it's nice for lookups to assume they're all functions, so let's assert that.
-        let all_imports_are_functions = module.import.imports.iter().all(|imp| imp.is_function());
+        });
         assert!(
-            all_imports_are_functions,
-            "This Wasm interpreter doesn't support non-function imports"
+            all_imports_supported,
+            "This Wasm interpreter doesn't support imported memories or tables"
+        );
+
+        // Imported globals come first in the index space
+        let mut global_types = Vec::from_iter_in(
+            module
+                .import
+                .imports
+                .iter()
+                .filter_map(|imp| match imp.description {
+                    ImportDesc::Global { ty } => Some(ty),
+                    _ => None,
+                }),
+            arena,
         );
+        global_types.extend(module.global.global_types(arena));
+        let mut globals = Vec::with_capacity_in(global_types.len(), arena);
+        Self::load_globals(module, &import_dispatcher, &mut globals)?;
+
+        let mut memory = Vec::new_in(arena);
+        Self::load_memory(module, 0, &mut memory, &globals)?;
+        let mut other_memories = Vec::new_in(arena);
+        for memory_index in 1..module.memory.count {
+            let mut other = Vec::new_in(arena);
+            Self::load_memory(module, memory_index, &mut other, &globals)?;
+            other_memories.push(other);
+        }
+
+        let mut data_segments = Vec::new_in(arena);
+        Self::load_data_segments(module, &mut data_segments)?;
 
         let value_store = ValueStore::new(arena);
 
@@ -142,7 +256,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             None
         };
 
-        let import_count = module.import.imports.len();
+        let import_count = module.import.function_count();
         let branch_cache = {
             let num_functions = import_count + module.code.function_count as usize;
             let empty_caches_iter = iter::repeat(Vec::new_in(arena)).take(num_functions);
@@ -152,10 +266,18 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         Ok(Instance {
             module,
             memory,
+            other_memories,
+            max_memory_pages: None,
+            total_memory_budget: None,
+            total_memory_grown: 0,
+            verify: cfg!(debug_assertions),
+            trap_on_overflow: false,
             current_frame: Frame::new(),
             previous_frames: Vec::new_in(arena),
             value_store,
             globals,
+            global_types,
+            data_segments,
             program_counter: usize::MAX,
             blocks: Vec::new_in(arena),
             branch_cache,
@@ -163,28 +285,353 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             import_dispatcher,
             import_arguments: Vec::new_in(arena),
             debug_string,
+            instructions_executed: 0,
+            trap_handler: TrapHandlerSlot(None),
+        })
+    }
+
+    /// Restore memory, globals, and the stacks to their state just after instantiation,
+    /// so that the module can be run again without being re-parsed.
+    ///
+    /// Imported globals are read from the dispatcher again, so this can fail like
+    /// [Instance::for_module] does: if a global is missing or has the wrong type, or a data
+    /// segment at an address from one doesn't fit in memory. Then memory and globals are only
+    /// partly restored, and the instance shouldn't be run until a reset succeeds.
+    pub fn reset(&mut self) -> Result<(), std::string::String> {
+        let module = self.module;
+
+        self.current_frame = Frame::new();
+        self.previous_frames.clear();
+        self.value_store.truncate(0);
+        self.program_counter = usize::MAX;
+        self.blocks.clear();
+        self.instructions_executed = 0;
+
+        Self::load_globals(module, &self.import_dispatcher, &mut self.globals)?;
+
+        Self::load_memory(module, 0, &mut self.memory, &self.globals)?;
+        for (i, other) in self.other_memories.iter_mut().enumerate() {
+            Self::load_memory(module, 1 + i as u32, other, &self.globals)?;
+        }
+
+        Self::load_data_segments(module, &mut self.data_segments)
+    }
+
+    /// Set the globals to their initial values. Imported globals get theirs from the dispatcher.
+    fn load_globals(
+        module: &'a WasmModule<'a>,
+        import_dispatcher: &I,
+        globals: &mut Vec<'a, Value>,
+    ) -> Result<(), std::string::String> {
+        globals.clear();
+        for import in module.import.imports.iter() {
+            if let ImportDesc::Global { ty } = import.description {
+                let value = import_dispatcher
+                    .global(import.module, import.name)
+                    .ok_or_else(|| {
+                        format!(
+                            "The import dispatcher has no value for global {}.{}",
+                            import.module, import.name
+                        )
+                    })?;
+                if ValueType::from(value) != ty.value_type {
+                    return Err(format!(
+                        "Imported global {}.{} should be {:?}, but the import dispatcher gave {value:?}",
+                        import.module, import.name, ty.value_type
+                    ));
+                }
+                globals.push(value);
+            }
+        }
+        module.global.append_initial_values(globals).map_err(|e| {
+            format!(
+                "Error parsing Global section at offset {:#x}:\n{}",
+                e.offset, e.message
+            )
         })
     }
 
+    /// Set a memory to its initial size, zeroed except for the active data segments that target it
+    fn load_memory(
+        module: &'a WasmModule<'a>,
+        memory_index: u32,
+        memory: &mut Vec<'a, u8>,
+        globals: &[Value],
+    ) -> Result<(), std::string::String> {
+        let mem_bytes = module.memory.min_bytes_at(memory_index).map_err(|e| {
+            format!(
+                "Error parsing Memory section at offset {:#x}:\n{}",
+                e.offset, e.message
+            )
+        })?;
+        memory.clear();
+        memory.resize(mem_bytes as usize, 0);
+        module.data.load_into(memory_index, memory, globals)
+    }
+
+    /// Passive segments stay available until `data.drop`.
+    /// Active segments are copied into memory at instantiation, and then behave as if dropped.
+    fn load_data_segments(
+        module: &'a WasmModule<'a>,
+        data_segments: &mut Vec<'a, &'a [u8]>,
+    ) -> Result<(), std::string::String> {
+        data_segments.clear();
+        for segment in module.data.segments() {
+            let contents = match segment? {
+                (DataMode::Passive, init) => init,
+                (DataMode::Active { .. }, _) => &[],
+            };
+            data_segments.push(contents);
+        }
+        Ok(())
+    }
+
+    /// Limit memory to `max_pages`, even if the module declares a higher maximum (or none).
+    /// `memory.grow` returns -1 for any request that would exceed the limit.
+    /// Without this, memory can still only grow to the spec's limit of [MemorySection::MAX_PAGES].
+    pub fn set_max_memory_pages(&mut self, max_pages: u32) {
+        self.max_memory_pages = Some(max_pages);
+    }
+
+    /// Limit the total number of bytes that memory can grow by, counting every `memory.grow`
+    /// (and [Instance::grow_memory]) since instantiation, including growth undone by a reset.
+    /// Unlike the other limits, this is an [Error] rather than a failed `memory.grow`, since it's
+    /// there to stop an embedding that runs a module many times from using too much memory in total.
+    pub fn set_total_memory_budget(&mut self, bytes: u64) {
+        self.total_memory_budget = Some(bytes);
+    }
+
+    /// A fingerprint of the contents of every memory, for comparing the end state of runs
+    /// without dumping it. It only depends on the bytes (and the sizes of the memories),
+    /// so it stays the same across runs, platforms and versions of the interpreter.
+    pub fn memory_checksum(&self) -> u64 {
+        self.other_memories
+            .iter()
+            .fold(wyhash::wyhash(&self.memory, 0), |hash, memory| {
+                wyhash::wyhash(memory, hash)
+            })
+    }
+
+    /// Grow memory 0 from the host, like `memory.grow` does, for example to make room for a large input.
+    /// Returns the previous size in pages. The new pages are zeroed.
+    pub fn grow_memory(&mut self, pages: u32) -> Result<u32, String> {
+        match self.grow_memory_help(0, pages) {
+            Ok(Some(old_pages)) => Ok(old_pages),
+            Ok(None) => Err(format!(
+                "Can't grow memory by {pages} pages, because it would exceed the maximum size"
+            )),
+            Err(_) => Err(format!(
+                "Can't grow memory by {pages} pages, because it would exceed the total memory budget"
+            )),
+        }
+    }
+
+    /// Enable or disable extra type checks that catch miscompiled code as early as possible.
+    /// Enabled by default in debug builds.
+    ///
+    /// Operand types are checked on every pop regardless. That check is the same `match` that
+    /// extracts the number from the [Value], so skipping it makes no measurable difference,
+    /// and would make a badly-typed module read garbage rather than fail with an error.
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Make signed overflow in integer add, sub and mul raise an error rather than wrapping.
+    /// This is not valid Wasm behaviour, but it helps to find where unexpected overflow happens.
+    pub fn set_trap_on_overflow(&mut self, trap_on_overflow: bool) {
+        self.trap_on_overflow = trap_on_overflow;
+    }
+
+    /// Call `handler` with the error and the call stack when the program stops with an error,
+    /// for example to log it somewhere other than stderr. The error message that the run
+    /// returns then leaves out the stack trace, which is otherwise appended to it.
+    pub fn set_trap_handler(&mut self, handler: TrapHandler<'a>) {
+        self.trap_handler = TrapHandlerSlot(Some(handler));
+    }
+
+    /// Allocate room for `capacity` values on the stack up front.
+    /// Deep recursion can otherwise reallocate the stack several times as it grows,
+    /// and in an arena, every outgrown buffer stays allocated until the arena is dropped.
+    pub fn with_value_stack_capacity(mut self, capacity: usize) -> Self {
+        self.value_store.reserve(capacity);
+        self
+    }
+
+    /// Number of values on the stack, including the args and locals of every call frame
+    pub fn value_stack_depth(&self) -> usize {
+        self.value_store.depth()
+    }
+
+    /// Look at the value `n` places down from the top of the stack, without popping anything.
+    /// The top of the stack is at `n == 0`.
+    pub fn value_stack_peek(&self, n: usize) -> Option<Value> {
+        let index = self.value_store.depth().checked_sub(n + 1)?;
+        self.value_store.get(index).copied()
+    }
+
+    /// The args and locals of the function that's currently running, in index order.
+    /// Empty if there's no function running.
+    pub fn current_locals(&self) -> &[Value] {
+        self.current_frame.locals(&self.value_store)
+    }
+
+    /// Look up an arg or local of the function that's currently running, by the name it has in
+    /// the module's name section. `None` if the name section doesn't name it.
+    pub fn local_by_name(&self, name: &str) -> Option<Value> {
+        let fn_index = self.current_frame.fn_index as u32;
+        let local_index = self.module.names.local_index(fn_index, name)?;
+        self.current_locals().get(local_index as usize).copied()
+    }
+
+    /// Overwrite an arg or local of the function that's currently running, for example from a debugger.
+    /// The new value must have the same type as the old one.
+    pub fn set_current_local(&mut self, index: u32, value: Value) -> Result<(), String> {
+        let old_value = *self.current_locals().get(index as usize).ok_or_else(|| {
+            format!(
+                "Local {index} doesn't exist. The current function has {} args & locals.",
+                self.current_frame.locals_count
+            )
+        })?;
+        value
+            .check_type(ValueType::from(old_value))
+            .map_err(|(expected, _)| {
+                format!("Type mismatch on local {index}. Expected {expected:?} but got {value:?}")
+            })?;
+        self.current_frame
+            .set_local(&mut self.value_store, index, value);
+        Ok(())
+    }
+
+    /// Everything the module exports, with its kind and its index in the relevant index space
+    pub fn exports(&self) -> &[Export<'a>] {
+        &self.module.export.exports
+    }
+
+    /// Number of instructions executed since instantiation or the last [Instance::reset],
+    /// across all calls, including any instruction that trapped.
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// Offset of the next instruction to execute, relative to the start of the code section
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Describe the interpreter state for debugging: the program counter, then the call stack
+    /// with each function's name (from the name section, if any), args, locals and value stack.
+    /// This is the same stack trace that's appended to the error message when a call traps.
+    pub fn dump_state(&self) -> String {
+        let file_offset = self.program_counter + self.module.code.section_offset as usize;
+        let mut buffer = format!("program counter {file_offset:#x} (file offset)\n");
+        self.debug_stack_trace(&mut buffer).unwrap();
+        buffer
+    }
+
+    /// Move execution to another instruction in the current function, for example from a debugger.
+    /// The offset must be the start of an instruction in the function body, otherwise we'd
+    /// start decoding from the middle of an immediate. Blocks are not entered or exited,
+    /// so jumping into or out of a block, or skipping pushes and pops, is up to the caller.
+    pub fn set_program_counter(&mut self, offset: usize) -> Result<(), String> {
+        let module = self.module;
+        let bytes = &module.code.bytes;
+        let internal_fn_index = match self.current_frame.fn_index.checked_sub(self.import_count) {
+            Some(i) if self.program_counter != usize::MAX => i,
+            _ => return Err("There is no function running".to_string()),
+        };
+
+        let mut cursor = module.code.function_offsets[internal_fn_index] as usize;
+        let body_size = u32::parse((), bytes, &mut cursor).unwrap() as usize;
+        let body_end = cursor + body_size;
+        let local_group_count = u32::parse((), bytes, &mut cursor).unwrap();
+        for _ in 0..local_group_count {
+            <(u32, ValueType)>::parse((), bytes, &mut cursor).unwrap();
+        }
+
+        while cursor < body_end && cursor < offset {
+            OpCode::skip_bytes(bytes, &mut cursor).map_err(|e| e.message)?;
+        }
+
+        if cursor == offset && cursor < body_end {
+            self.program_counter = offset;
+            Ok(())
+        } else {
+            Err(format!(
+                "Offset {offset:#x} is not the start of an instruction in func[{}]",
+                self.current_frame.fn_index
+            ))
+        }
+    }
+
+    /// Call an exported function, converting the arguments and return value to and from Rust types.
+    /// For example `let sum: i32 = instance.call_export_typed("add", (1i32, 2i32))?;`
+    pub fn call_export_typed<A, R>(&mut self, fn_name: &str, args: A) -> Result<R, String>
+    where
+        A: IntoValues,
+        R: FromValues,
+    {
+        let value = self.call_export(fn_name, args)?;
+        R::from_values(value).map_err(|e| format!("{e} from {fn_name}"))
+    }
+
     pub fn call_export<A>(&mut self, fn_name: &str, arg_values: A) -> Result<Option<Value>, String>
     where
-        A: IntoIterator<Item = Value>,
+        A: IntoValues,
+    {
+        // Nothing has run yet, so there's no instruction to report an offset for
+        self.start_export(fn_name, arg_values)
+            .map_err(|e| e.to_string_at(0))?;
+        self.run_export(self.module)
+    }
+
+    /// Set up a call to an exported function, without running any of it.
+    /// Then call [Instance::run_n] to run it in chunks, interleaved with other work.
+    pub fn start_export<A>(&mut self, fn_name: &str, arg_values: A) -> Result<(), Error>
+    where
+        A: IntoValues,
     {
         let (fn_index, param_type_iter, ret_type) =
             self.call_export_help_before_arg_load(self.module, fn_name)?;
         let n_args = param_type_iter.len();
 
-        for (i, (value, expected_type)) in arg_values.into_iter().zip(param_type_iter).enumerate() {
-            let actual_type = ValueType::from(value);
-            if actual_type != expected_type {
-                return Err(format!(
-                    "Type mismatch on argument {i} of {fn_name}. Expected {expected_type:?} but got {value:?}"
-                ));
-            }
-            self.value_store.push(value);
+        // On error, leave the stack as we found it, so the instance can still be used
+        let start_depth = self.value_store.depth();
+        let mut values = arg_values.into_values();
+        for (index, expected) in param_type_iter.enumerate() {
+            let error = match values.next() {
+                Some(value) if ValueType::from(value) == expected => {
+                    self.value_store.push(value);
+                    continue;
+                }
+                Some(value) => Error::ArgumentTypeMismatch {
+                    index,
+                    expected,
+                    actual: ValueType::from(value),
+                },
+                None => Error::ArgumentCount {
+                    expected: n_args,
+                    actual: index,
+                },
+            };
+            self.value_store.truncate(start_depth);
+            return Err(error);
+        }
+        let extra_args = values.count();
+        if extra_args > 0 {
+            self.value_store.truncate(start_depth);
+            return Err(Error::ArgumentCount {
+                expected: n_args,
+                actual: n_args + extra_args,
+            });
         }
 
-        self.call_export_help_after_arg_load(self.module, fn_index, n_args, ret_type)
+        self.enter_export(self.module, fn_index, n_args, ret_type);
+        Ok(())
+    }
+
+    /// Run at most `max_instructions` of the function call set up by [Instance::start_export]
+    pub fn run_n(&mut self, max_instructions: u64) -> Result<RunProgress, String> {
+        self.run_n_help(self.module, max_instructions)
     }
 
     pub fn call_export_from_cli(
@@ -206,8 +653,9 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
         // Implement the "basic numbers" CLI
         // Check if the called Wasm function takes numeric arguments, and if so, try to parse them from the CLI.
-        let (fn_index, param_type_iter, ret_type) =
-            self.call_export_help_before_arg_load(module, fn_name)?;
+        let (fn_index, param_type_iter, ret_type) = self
+            .call_export_help_before_arg_load(module, fn_name)
+            .map_err(|e| e.to_string_at(0))?;
         let n_args = param_type_iter.len();
         for (value_bytes, value_type) in arg_strings
             .iter()
@@ -225,14 +673,15 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             self.value_store.push(value);
         }
 
-        self.call_export_help_after_arg_load(module, fn_index, n_args, ret_type)
+        self.enter_export(module, fn_index, n_args, ret_type);
+        self.run_export(module)
     }
 
     fn call_export_help_before_arg_load<'m>(
         &mut self,
         module: &'m WasmModule<'a>,
         fn_name: &str,
-    ) -> Result<(usize, SignatureParamsIter<'m>, Option<ValueType>), String> {
+    ) -> Result<(usize, SignatureParamsIter<'m>, Option<ValueType>), Error> {
         let fn_index = {
             let mut export_iter = module.export.exports.iter();
             export_iter
@@ -259,9 +708,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                         },
                     )
                 })
-                .ok_or_else(|| {
-                    format!("I couldn't find a function '{fn_name}' in this WebAssembly module")
-                })? as usize
+                .ok_or_else(|| Error::ExportNotFound(fn_name.to_string()))? as usize
         };
 
         let internal_fn_index = fn_index - self.import_count;
@@ -289,18 +736,19 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         Ok((fn_index, param_type_iter, return_type))
     }
 
-    fn call_export_help_after_arg_load(
+    fn enter_export(
         &mut self,
         module: &WasmModule<'a>,
         fn_index: usize,
         n_args: usize,
         return_type: Option<ValueType>,
-    ) -> Result<Option<Value>, String> {
+    ) {
         self.previous_frames.clear();
         self.blocks.clear();
         self.blocks.push(Block {
             ty: BlockType::Locals(fn_index),
             vstack: self.value_store.depth(),
+            arity: 0,
         });
         self.current_frame = Frame::enter(
             fn_index,
@@ -315,56 +763,180 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         self.blocks.push(Block {
             ty: BlockType::FunctionBody(fn_index),
             vstack: self.value_store.depth(),
+            arity: return_type.is_some() as usize,
         });
+    }
 
-        loop {
-            match self.execute_next_instruction(module) {
-                Ok(Action::Continue) => {}
-                Ok(Action::Break) => {
-                    break;
+    fn run_export(&mut self, module: &WasmModule<'a>) -> Result<Option<Value>, String> {
+        match self.run_n_help(module, u64::MAX)? {
+            RunProgress::Completed(return_value) => Ok(return_value),
+            RunProgress::Yielded => unreachable!("ran out of instructions without a budget"),
+        }
+    }
+
+    fn run_n_help(
+        &mut self,
+        module: &WasmModule<'a>,
+        max_instructions: u64,
+    ) -> Result<RunProgress, String> {
+        self.execute_n(module, max_instructions).map_err(|e| {
+            let file_offset = self.program_counter + module.code.section_offset as usize;
+            let mut message = e.to_string_at(file_offset);
+            let frames = self.frame_infos();
+            match self.trap_handler.0.as_mut() {
+                Some(handler) => handler(&e, &frames),
+                None => self.debug_stack_trace(&mut message).unwrap(),
+            }
+            message
+        })
+    }
+
+    /// Run the function call set up by [Instance::start_export] to the end, and return any
+    /// [Error] as it is, without a message, stack trace, or call to the [TrapHandler]
+    pub(crate) fn finish_export(&mut self) -> Result<Option<Value>, Error> {
+        match self.execute_n(self.module, u64::MAX)? {
+            RunProgress::Completed(return_value) => Ok(return_value),
+            RunProgress::Yielded => unreachable!("ran out of instructions without a budget"),
+        }
+    }
+
+    fn execute_n(
+        &mut self,
+        module: &WasmModule<'a>,
+        max_instructions: u64,
+    ) -> Result<RunProgress, Error> {
+        for _ in 0..max_instructions {
+            self.instructions_executed += 1;
+            match self.execute_next_instruction(module)? {
+                Action::Continue => {}
+                Action::Break => {
+                    let return_value = self.value_store.pop().ok();
+                    return Ok(RunProgress::Completed(return_value));
                 }
-                Err(e) => {
-                    let file_offset = self.program_counter + module.code.section_offset as usize;
-                    let mut message = e.to_string_at(file_offset);
-                    self.debug_stack_trace(&mut message).unwrap();
-                    return Err(message);
+                Action::Exit => return Ok(RunProgress::Completed(None)),
+            };
+        }
+        Ok(RunProgress::Yielded)
+    }
+
+    fn fetch_immediate_u32(&mut self, module: &WasmModule<'a>) -> Result<u32, Error> {
+        let x = u32::parse((), &module.code.bytes, &mut self.program_counter)?;
+        if let Some(debug_string) = self.debug_string.as_mut() {
+            write!(debug_string, "{x} ").unwrap();
+        }
+        Ok(x)
+    }
+
+    /// Fetch a memory index immediate, and check that the memory exists
+    fn fetch_memory_index(&mut self, module: &WasmModule<'a>) -> Result<u32, Error> {
+        let memory_index = self.fetch_immediate_u32(module)?;
+        if memory_index as usize > self.other_memories.len() {
+            return Err(Error::InvalidCode(format!(
+                "Memory {memory_index} doesn't exist"
+            )));
+        }
+        Ok(memory_index)
+    }
+
+    /// Fetch the block type immediate of `block`, `loop` or `if`, and return its number of params and results.
+    /// It's a signed LEB. Non-negative values are type indices. Negative values are the one-byte
+    /// encodings of value types, sign-extended: -1 (0x7f) is `i32` down to -4 (0x7c) for `f64`,
+    /// and -64 (0x40) means no result.
+    fn fetch_block_type(&mut self, module: &WasmModule<'a>) -> Result<(usize, usize), Error> {
+        let block_type = i64::parse((), &module.code.bytes, &mut self.program_counter)?;
+        self.write_debug(block_type);
+
+        if block_type < 0 {
+            // Only one-byte LEBs are types. Remove the sign extension to get the byte back.
+            let byte = (block_type >= -64).then_some((block_type & 0x7f) as u8);
+            return match byte {
+                Some(ValueType::VOID) => Ok((0, 0)),
+                Some(b) if (ValueType::F64 as u8..=ValueType::I32 as u8).contains(&b) => Ok((0, 1)),
+                Some(ValueType::V128) => Err(Error::InvalidCode(
+                    "Block type v128 isn't supported, because SIMD isn't supported".into(),
+                )),
+                Some(b) if b == RefType::Func as u8 || b == RefType::Extern as u8 => {
+                    Err(Error::InvalidCode(
+                        "Reference block types aren't supported, because there are no reference types in this interpreter".into(),
+                    ))
                 }
+                _ => Err(Error::InvalidCode(format!(
+                    "Invalid block type {block_type}"
+                ))),
             };
         }
 
-        let return_value = if !self.value_store.is_empty() {
-            Some(self.value_store.pop())
-        } else {
-            None
-        };
+        let signature_index = block_type as u64;
+        if signature_index >= module.types.len() as u64 {
+            return Err(Error::InvalidCode(format!(
+                "Block type {signature_index} doesn't exist"
+            )));
+        }
+        let (params, ret_type) = module.types.look_up(signature_index as u32);
+        Ok((params.len(), ret_type.is_some() as usize))
+    }
 
-        Ok(return_value)
+    /// In verify mode, check that a function is about to return the type in its signature
+    fn verify_return(&self) -> Result<(), Error> {
+        if !self.verify {
+            return Ok(());
+        }
+        let Frame {
+            locals_start,
+            locals_count,
+            return_type,
+            ..
+        } = self.current_frame;
+        match return_type {
+            Some(_) if self.value_store.depth() <= locals_start + locals_count => {
+                Err(Error::StackEmpty)
+            }
+            Some(expected) => {
+                let actual = ValueType::from(self.value_store.peek()?);
+                if actual == expected {
+                    Ok(())
+                } else {
+                    Err(Error::Type(expected, actual))
+                }
+            }
+            None => Ok(()),
+        }
     }
 
-    fn fetch_immediate_u32(&mut self, module: &WasmModule<'a>) -> u32 {
-        let x = u32::parse((), &module.code.bytes, &mut self.program_counter).unwrap();
-        if let Some(debug_string) = self.debug_string.as_mut() {
-            write!(debug_string, "{x} ").unwrap();
+    /// In verify mode, check that a value being overwritten has the same type as its replacement
+    fn verify_same_type(&self, old_value: Value, new_value: Value) -> Result<(), Error> {
+        let expected = ValueType::from(old_value);
+        let actual = ValueType::from(new_value);
+        if self.verify && actual != expected {
+            Err(Error::Type(expected, actual))
+        } else {
+            Ok(())
         }
-        x
     }
 
-    fn do_return(&mut self) -> Action {
+    fn do_return(&mut self) -> Result<Action, Error> {
         // self.debug_values_and_blocks("start do_return");
 
         let Frame {
             return_addr,
             body_block_index,
             return_type,
+            locals_start,
+            locals_count,
             ..
         } = self.current_frame;
 
+        // The return value must be above the locals, not one of them
+        if return_type.is_some() && self.value_store.depth() <= locals_start + locals_count {
+            return Err(Error::StackEmpty);
+        }
+
         // Throw away all locals and values except the return value
         let locals_block_index = body_block_index - 1;
         let locals_block = &self.blocks[locals_block_index];
         let new_stack_depth = if return_type.is_some() {
             self.value_store
-                .set(locals_block.vstack, self.value_store.peek());
+                .set(locals_block.vstack, self.value_store.peek()?);
             locals_block.vstack + 1
         } else {
             locals_block.vstack
@@ -380,43 +952,113 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
         if let Some(caller_frame) = self.previous_frames.pop() {
             self.current_frame = caller_frame;
-            Action::Continue
+            Ok(Action::Continue)
         } else {
             // We just popped the stack frame for the entry function. Terminate the program.
-            Action::Break
+            self.current_frame = Frame::new();
+            Ok(Action::Break)
         }
     }
 
-    fn get_load_address(&mut self, module: &WasmModule<'a>) -> Result<u32, Error> {
+    /// The value stack depth where a new block or call starts, below the params it takes from the stack.
+    /// The params must be above the current function's locals, or branching out of the block would clobber them.
+    fn block_start(&self, n_params: usize) -> Result<usize, Error> {
+        let Frame {
+            locals_start,
+            locals_count,
+            ..
+        } = self.current_frame;
+        self.value_store
+            .depth()
+            .checked_sub(n_params)
+            .filter(|start| *start >= locals_start + locals_count)
+            .ok_or(Error::StackEmpty)
+    }
+
+    /// Fetch the memory index and offset immediates of a load or store
+    fn fetch_memarg(&mut self, module: &WasmModule<'a>) -> Result<(u32, u32), Error> {
         // Alignment is not used in the execution steps from the spec! Maybe it's just an optimization hint?
         // https://webassembly.github.io/spec/core/exec/instructions.html#memory-instructions
         // Also note: in the text format we can specify the useless `align=` but not the useful `offset=`!
-        let _alignment = self.fetch_immediate_u32(module);
-        let offset = self.fetch_immediate_u32(module);
-        let base_addr = self.value_store.pop_u32()?;
-        let addr = base_addr + offset;
-        let memory_size = self.memory.len() as u32;
-        if addr >= memory_size {
-            Err(Error::MemoryAccessOutOfBounds(addr, memory_size))
+        let alignment = self.fetch_immediate_u32(module)?;
+        let memory_index = if alignment & MEMARG_MEMORY_INDEX_FLAG != 0 {
+            self.fetch_memory_index(module)?
         } else {
-            Ok(addr)
-        }
+            0
+        };
+        let offset = self.fetch_immediate_u32(module)?;
+        Ok((memory_index, offset))
     }
 
-    fn get_store_addr_value(&mut self, module: &WasmModule<'a>) -> Result<(usize, Value), Error> {
-        // Alignment is not used in the execution steps from the spec! Maybe it's just an optimization hint?
-        // https://webassembly.github.io/spec/core/exec/instructions.html#memory-instructions
-        // Also note: in the text format we can specify the useless `align=` but not the useful `offset=`!
-        let _alignment = self.fetch_immediate_u32(module);
-        let offset = self.fetch_immediate_u32(module);
-        let value = self.value_store.pop();
+    /// Fetch the immediates of a load and pop its address, returning the memory index and address
+    fn get_load_address(
+        &mut self,
+        module: &WasmModule<'a>,
+        size: u32,
+    ) -> Result<(u32, usize), Error> {
+        let (memory_index, offset) = self.fetch_memarg(module)?;
         let base_addr = self.value_store.pop_u32()?;
-        let addr = base_addr + offset;
-        let memory_size = self.memory.len() as u32;
-        if addr >= memory_size {
-            Err(Error::MemoryAccessOutOfBounds(addr, memory_size))
+        let addr = self.check_memory_access(memory_index, base_addr, offset, size)?;
+        Ok((memory_index, addr as usize))
+    }
+
+    /// Fetch the immediates of a store and pop its operands,
+    /// returning the `size` bytes of memory to write to, and the value to write
+    fn get_store_target_value(
+        &mut self,
+        module: &WasmModule<'a>,
+        size: u32,
+    ) -> Result<(&mut [u8], Value), Error> {
+        let (memory_index, offset) = self.fetch_memarg(module)?;
+        let value = self.value_store.pop()?;
+        let base_addr = self.value_store.pop_u32()?;
+        let addr = self.check_memory_access(memory_index, base_addr, offset, size)?;
+        let target = &mut self.memory_at_mut(memory_index)[addr as usize..][..size as usize];
+        Ok((target, value))
+    }
+
+    /// Fetch the immediates of a load, pop its address, and read the `N` bytes at that address
+    fn load_bytes<const N: usize>(&mut self, module: &WasmModule<'a>) -> Result<[u8; N], Error> {
+        let (memory_index, addr) = self.get_load_address(module, N as u32)?;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.memory_at(memory_index)[addr..][..N]);
+        Ok(bytes)
+    }
+
+    fn memory_at(&self, memory_index: u32) -> &Vec<'a, u8> {
+        match memory_index {
+            0 => &self.memory,
+            _ => &self.other_memories[memory_index as usize - 1],
+        }
+    }
+
+    fn memory_at_mut(&mut self, memory_index: u32) -> &mut Vec<'a, u8> {
+        match memory_index {
+            0 => &mut self.memory,
+            _ => &mut self.other_memories[memory_index as usize - 1],
+        }
+    }
+
+    /// Check that all `size` bytes of a memory access are in bounds, and return the start address.
+    /// The effective address is calculated without wrapping, so a large offset can't wrap around to 0.
+    fn check_memory_access(
+        &self,
+        memory_index: u32,
+        base_addr: u32,
+        offset: u32,
+        size: u32,
+    ) -> Result<u32, Error> {
+        let addr = base_addr as u64 + offset as u64;
+        let end = addr + size as u64;
+        let memory_size = self.memory_at(memory_index).len() as u64;
+        if end > memory_size {
+            let last_byte = (end - 1).min(u32::MAX as u64) as u32;
+            Err(Error::Trap(TrapKind::MemoryOutOfBounds(
+                last_byte,
+                memory_size as u32,
+            )))
         } else {
-            Ok((addr as usize, value))
+            Ok(addr as u32)
         }
     }
 
@@ -426,25 +1068,47 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         }
     }
 
-    fn do_break(&mut self, relative_blocks_outward: u32, module: &WasmModule<'a>) {
-        let block_index = self.blocks.len() - 1 - relative_blocks_outward as usize;
-        let Block { ty, vstack } = self.blocks[block_index];
+    fn do_break(
+        &mut self,
+        relative_blocks_outward: u32,
+        module: &WasmModule<'a>,
+    ) -> Result<Action, Error> {
+        // Branch targets are the blocks of the current function, from the innermost out to its body
+        let block_index = (self.blocks.len() - 1)
+            .checked_sub(relative_blocks_outward as usize)
+            .filter(|i| *i >= self.current_frame.body_block_index)
+            .ok_or_else(|| {
+                Error::InvalidCode(format!(
+                    "Branch depth {relative_blocks_outward} is too deep"
+                ))
+            })?;
+        let Block { ty, vstack, arity } = self.blocks[block_index];
         match ty {
             BlockType::Loop(start_addr) => {
+                self.value_store.truncate_keeping_top(vstack, arity)?;
                 self.blocks.truncate(block_index + 1);
-                self.value_store.truncate(vstack);
                 self.program_counter = start_addr;
             }
-            BlockType::FunctionBody(_) | BlockType::Normal => {
-                self.break_forward(relative_blocks_outward, module);
-                self.value_store.truncate(vstack);
+            BlockType::Normal => {
+                self.value_store.truncate_keeping_top(vstack, arity)?;
+                self.break_forward(relative_blocks_outward, module)?;
+            }
+            // Branching out of the function body is the same as returning
+            BlockType::FunctionBody(_) => {
+                self.verify_return()?;
+                return self.do_return();
             }
             BlockType::Locals(_) => unreachable!(),
         }
+        Ok(Action::Continue)
     }
 
     // Break to an outer block, going forward in the program
-    fn break_forward(&mut self, relative_blocks_outward: u32, module: &WasmModule<'a>) {
+    fn break_forward(
+        &mut self,
+        relative_blocks_outward: u32,
+        module: &WasmModule<'a>,
+    ) -> Result<(), Error> {
         use OpCode::*;
 
         let addr = self.program_counter as u32;
@@ -459,8 +1123,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             self.program_counter = entry.target as usize;
         } else {
             loop {
-                let skipped_op = OpCode::from(module.code.bytes[self.program_counter]);
-                OpCode::skip_bytes(&module.code.bytes, &mut self.program_counter).unwrap();
+                let skipped_op = self.skip_instruction(module)?;
                 match skipped_op {
                     BLOCK | LOOP | IF => {
                         depth += 1;
@@ -481,6 +1144,85 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             });
         }
         self.blocks.truncate(target_block_depth);
+        Ok(())
+    }
+
+    /// Move the program counter past an instruction without executing it, and return its opcode
+    fn skip_instruction(&mut self, module: &WasmModule<'a>) -> Result<OpCode, Error> {
+        let start = self.program_counter;
+        OpCode::skip_bytes(&module.code.bytes, &mut self.program_counter)?;
+        Ok(OpCode::from(module.code.bytes[start]))
+    }
+
+    /// Look up the signature of a function, and check it's the one `call_indirect` expected, if any
+    fn check_signature(
+        &self,
+        expected_signature: Option<u32>,
+        fn_index: usize,
+        module: &WasmModule<'a>,
+    ) -> Result<u32, Error> {
+        if fn_index >= self.import_count + module.function.signatures.len() {
+            return Err(Error::InvalidCode(format!(
+                "Function {fn_index} doesn't exist"
+            )));
+        }
+        let signature_index = if fn_index < self.import_count {
+            match module.import.function(fn_index).map(|imp| &imp.description) {
+                Some(ImportDesc::Func { signature_index }) => *signature_index,
+                _ => unreachable!(),
+            }
+        } else {
+            module.function.signatures[fn_index - self.import_count]
+        };
+
+        match expected_signature {
+            Some(expected) if expected != signature_index => Err(Error::Trap(
+                TrapKind::CallIndirectTypeMismatch(expected, signature_index),
+            )),
+            _ => Ok(signature_index),
+        }
+    }
+
+    /// Call a function in place of the current one, for `return_call` and `return_call_indirect`.
+    /// The current frame is dropped before the call, so tail recursion runs in constant stack space.
+    fn do_tail_call(
+        &mut self,
+        expected_signature: Option<u32>,
+        fn_index: usize,
+        module: &WasmModule<'a>,
+    ) -> Result<Action, Error> {
+        if fn_index < self.import_count {
+            // Imports don't get a frame, so there's nothing to replace. Just call and return.
+            if let Action::Exit = self.do_call(expected_signature, fn_index, module)? {
+                return Ok(Action::Exit);
+            }
+            self.verify_return()?;
+            return self.do_return();
+        }
+
+        let signature_index = self.check_signature(expected_signature, fn_index, module)?;
+        let n_args = module.types.look_up(signature_index).0.len();
+
+        // Throw away the current frame's locals and values, except the args, and its blocks
+        let Frame {
+            return_addr,
+            body_block_index,
+            ..
+        } = self.current_frame;
+        let locals_block_index = body_block_index - 1;
+        let locals_start = self.blocks[locals_block_index].vstack;
+        self.value_store
+            .truncate_keeping_top(locals_start, n_args)?;
+        self.blocks.truncate(locals_block_index);
+        // The locals are gone, and the args are in their place
+        self.current_frame.locals_count = 0;
+
+        // Make the call as if from the current function's caller.
+        // `do_call` saves the current frame so it can return to it, but we're done with it.
+        self.program_counter = return_addr;
+        let action = self.do_call(None, fn_index, module)?;
+        self.previous_frames.pop();
+        Ok(action)
     }
 
     fn do_call(
@@ -488,32 +1230,20 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         expected_signature: Option<u32>,
         fn_index: usize,
         module: &WasmModule<'a>,
-    ) -> Result<(), Error> {
+    ) -> Result<Action, Error> {
         // self.debug_values_and_blocks(&format!("start do_call {}", fn_index));
 
-        let (signature_index, opt_import) = if fn_index < self.import_count {
+        let signature_index = self.check_signature(expected_signature, fn_index, module)?;
+        let opt_import = if fn_index < self.import_count {
             // Imported non-Wasm function
-            let import = &module.import.imports[fn_index];
-            let sig = match import.description {
-                ImportDesc::Func { signature_index } => signature_index,
-                _ => unreachable!(),
-            };
-            (sig, Some(import))
+            module.import.function(fn_index)
         } else {
-            // Wasm function
-            let sig = module.function.signatures[fn_index - self.import_count];
-            (sig, None)
+            None
         };
 
-        if let Some(expected) = expected_signature {
-            assert_eq!(
-                expected, signature_index,
-                "Indirect function call failed. Expected signature {expected} but found {signature_index}",
-            );
-        }
-
         let (arg_type_iter, ret_type) = module.types.look_up(signature_index);
         let n_args = arg_type_iter.len();
+        let args_start = self.block_start(n_args)?;
         if self.debug_string.is_some() {
             self.debug_call(n_args, ret_type);
         }
@@ -523,11 +1253,8 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             self.import_arguments
                 .extend(std::iter::repeat(Value::I64(0)).take(n_args));
             for (i, expected) in arg_type_iter.enumerate().rev() {
-                let arg = self.value_store.pop();
-                let actual = ValueType::from(arg);
-                if actual != expected {
-                    return Err(Error::Type(expected, actual));
-                }
+                let arg = self.value_store.pop()?;
+                arg.check_type(expected)?;
                 self.import_arguments[i] = arg;
             }
 
@@ -537,12 +1264,27 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 &self.import_arguments,
                 &mut self.memory,
             );
-            if let Some(return_val) = optional_return_val {
-                self.value_store.push(return_val);
-            }
             if let Some(debug_string) = self.debug_string.as_mut() {
                 write!(debug_string, " {}.{}", import.module, import.name).unwrap();
             }
+            if self.import_dispatcher.exit_code().is_some() {
+                return Ok(Action::Exit);
+            }
+            match (ret_type, optional_return_val) {
+                (Some(expected), Some(return_val)) => {
+                    return_val.check_type(expected)?;
+                    self.value_store.push(return_val);
+                }
+                (None, None) => {}
+                _ => {
+                    return Err(Error::ImportResultArity {
+                        module: import.module.to_string(),
+                        function: import.name.to_string(),
+                        expected: ret_type.is_some() as usize,
+                        actual: optional_return_val.is_some() as usize,
+                    });
+                }
+            }
         } else {
             let return_addr = self.program_counter;
             // set PC to start of function bytes
@@ -553,7 +1295,8 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
             self.blocks.push(Block {
                 ty: BlockType::Locals(fn_index),
-                vstack: self.value_store.depth() - n_args,
+                vstack: args_start,
+                arity: 0,
             });
             let body_block_index = self.blocks.len();
 
@@ -573,11 +1316,12 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
             self.blocks.push(Block {
                 ty: BlockType::FunctionBody(fn_index),
                 vstack: self.value_store.depth(),
+                arity: ret_type.is_some() as usize,
             });
         }
         // self.debug_values_and_blocks("end do_call");
 
-        Ok(())
+        Ok(Action::Continue)
     }
 
     fn debug_call(&mut self, n_args: usize, return_type: Option<ValueType>) {
@@ -603,1174 +1347,2027 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
     pub(crate) fn execute_next_instruction(
         &mut self,
         module: &WasmModule<'a>,
+    ) -> Result<Action, Error> {
+        let op_byte = *module
+            .code
+            .bytes
+            .get(self.program_counter)
+            .ok_or_else(|| Error::InvalidCode("Reached the end of the code".into()))?;
+
+        if self.debug_string.is_some() {
+            return self.execute_next_instruction_debug(module, op_byte);
+        }
+
+        self.program_counter += 1;
+        let handler = Self::DISPATCH_TABLE[op_byte as usize];
+        handler(self, module)
+    }
+
+    fn execute_next_instruction_debug(
+        &mut self,
+        module: &WasmModule<'a>,
+        op_byte: u8,
     ) -> Result<Action, Error> {
         use OpCode::*;
 
         let file_offset = self.program_counter as u32 + module.code.section_offset;
-        let op_code = OpCode::from(module.code.bytes[self.program_counter]);
         self.program_counter += 1;
+        let Some(op_code) = LOOKUP_TABLE[op_byte as usize] else {
+            return self.op_invalid(module);
+        };
 
         if let Some(debug_string) = self.debug_string.as_mut() {
             debug_string.clear();
             self.write_debug(op_code);
         }
 
-        let mut action = Action::Continue;
-        let mut implicit_return = false;
+        let implicit_return =
+            op_code == END && self.blocks.len() == (self.current_frame.body_block_index + 1);
 
-        match op_code {
-            UNREACHABLE => {
-                return Err(Error::UnreachableOp);
-            }
-            NOP => {}
-            BLOCK => {
-                self.fetch_immediate_u32(module); // blocktype (ignored)
-                self.blocks.push(Block {
-                    ty: BlockType::Normal,
-                    vstack: self.value_store.depth(),
-                });
+        let handler = Self::DISPATCH_TABLE[op_byte as usize];
+        let action = handler(self, module)?;
+
+        if let Some(debug_string) = &self.debug_string {
+            if matches!(
+                op_code,
+                CALL | CALLINDIRECT | RETURNCALL | RETURNCALLINDIRECT
+            ) {
+                eprintln!("\n{file_offset:06x} {debug_string}");
+            } else {
+                // For calls, we print special debug stuff in do_call
+                let base = self.current_frame.locals_start + self.current_frame.locals_count;
+                let slice = self.value_store.get_slice(base);
+                eprintln!("{file_offset:06x} {debug_string:17} {slice:x?}");
             }
-            LOOP => {
-                self.fetch_immediate_u32(module); // blocktype (ignored)
-                self.blocks.push(Block {
-                    ty: BlockType::Loop(self.program_counter),
-                    vstack: self.value_store.depth(),
-                });
+            let is_return = op_code == RETURN || implicit_return;
+            let is_program_end = self.program_counter == 0;
+            if is_return && !is_program_end {
+                eprintln!(
+                    "returning to function {} at {:06x}",
+                    self.current_frame.fn_index,
+                    self.program_counter + self.module.code.section_offset as usize,
+                );
             }
-            IF => {
-                self.fetch_immediate_u32(module); // blocktype (ignored)
-                let condition = self.value_store.pop_i32()?;
-                self.blocks.push(Block {
-                    ty: BlockType::Normal,
-                    vstack: self.value_store.depth(),
-                });
-                if condition == 0 {
-                    let addr = self.program_counter as u32;
-                    let cache_result = self.branch_cache[self.current_frame.fn_index]
-                        .iter()
-                        .find(|entry| entry.addr == addr);
-                    if let Some(entry) = cache_result {
-                        self.program_counter = entry.target as usize;
-                    } else {
-                        let target_depth = self.blocks.len();
-                        let mut depth = target_depth;
-                        loop {
-                            let skipped_op = OpCode::from(module.code.bytes[self.program_counter]);
-                            OpCode::skip_bytes(&module.code.bytes, &mut self.program_counter)
-                                .unwrap();
-                            match skipped_op {
-                                BLOCK | LOOP | IF => {
-                                    depth += 1;
-                                }
-                                END => {
-                                    if depth == target_depth {
-                                        // `if` without `else`
-                                        self.blocks.pop();
-                                        break;
-                                    } else {
-                                        depth -= 1;
-                                    }
-                                }
-                                ELSE => {
-                                    if depth == target_depth {
-                                        break;
-                                    }
-                                }
-                                _ => {}
+        }
+
+        Ok(action)
+    }
+
+    /// Handler for each instruction byte, indexed by opcode.
+    /// Dispatching through a table is faster than a `match` on `OpCode`, which the compiler
+    /// doesn't reliably turn into a jump table for an enum this large.
+    const DISPATCH_TABLE: [OpHandler<'a, I>; 256] = {
+        use OpCode::*;
+
+        let mut result: [OpHandler<'a, I>; 256] = [Self::op_invalid; 256];
+
+        result[UNREACHABLE as usize] = Self::op_unreachable;
+        result[NOP as usize] = Self::op_nop;
+        result[BLOCK as usize] = Self::op_block;
+        result[LOOP as usize] = Self::op_loop;
+        result[IF as usize] = Self::op_if;
+        result[ELSE as usize] = Self::op_else;
+        result[END as usize] = Self::op_end;
+        result[BR as usize] = Self::op_br;
+        result[BRIF as usize] = Self::op_brif;
+        result[BRTABLE as usize] = Self::op_brtable;
+        result[RETURN as usize] = Self::op_return;
+        result[CALL as usize] = Self::op_call;
+        result[CALLINDIRECT as usize] = Self::op_callindirect;
+        result[RETURNCALL as usize] = Self::op_returncall;
+        result[RETURNCALLINDIRECT as usize] = Self::op_returncallindirect;
+        result[DROP as usize] = Self::op_drop;
+        result[SELECT as usize] = Self::op_select;
+        result[SELECTT as usize] = Self::op_selectt;
+        result[GETLOCAL as usize] = Self::op_getlocal;
+        result[SETLOCAL as usize] = Self::op_setlocal;
+        result[TEELOCAL as usize] = Self::op_teelocal;
+        result[GETGLOBAL as usize] = Self::op_getglobal;
+        result[SETGLOBAL as usize] = Self::op_setglobal;
+        result[I32LOAD as usize] = Self::op_i32load;
+        result[I64LOAD as usize] = Self::op_i64load;
+        result[F32LOAD as usize] = Self::op_f32load;
+        result[F64LOAD as usize] = Self::op_f64load;
+        result[I32LOAD8S as usize] = Self::op_i32load8s;
+        result[I32LOAD8U as usize] = Self::op_i32load8u;
+        result[I32LOAD16S as usize] = Self::op_i32load16s;
+        result[I32LOAD16U as usize] = Self::op_i32load16u;
+        result[I64LOAD8S as usize] = Self::op_i64load8s;
+        result[I64LOAD8U as usize] = Self::op_i64load8u;
+        result[I64LOAD16S as usize] = Self::op_i64load16s;
+        result[I64LOAD16U as usize] = Self::op_i64load16u;
+        result[I64LOAD32S as usize] = Self::op_i64load32s;
+        result[I64LOAD32U as usize] = Self::op_i64load32u;
+        result[I32STORE as usize] = Self::op_i32store;
+        result[I64STORE as usize] = Self::op_i64store;
+        result[F32STORE as usize] = Self::op_f32store;
+        result[F64STORE as usize] = Self::op_f64store;
+        result[I32STORE8 as usize] = Self::op_i32store8;
+        result[I32STORE16 as usize] = Self::op_i32store16;
+        result[I64STORE8 as usize] = Self::op_i64store8;
+        result[I64STORE16 as usize] = Self::op_i64store16;
+        result[I64STORE32 as usize] = Self::op_i64store32;
+        result[CURRENTMEMORY as usize] = Self::op_currentmemory;
+        result[GROWMEMORY as usize] = Self::op_growmemory;
+        result[MEMORY as usize] = Self::op_memory;
+        result[ATOMIC as usize] = Self::op_atomic;
+        result[I32CONST as usize] = Self::op_i32const;
+        result[I64CONST as usize] = Self::op_i64const;
+        result[F32CONST as usize] = Self::op_f32const;
+        result[F64CONST as usize] = Self::op_f64const;
+        result[I32EQZ as usize] = Self::op_i32eqz;
+        result[I32EQ as usize] = Self::op_i32eq;
+        result[I32NE as usize] = Self::op_i32ne;
+        result[I32LTS as usize] = Self::op_i32lts;
+        result[I32LTU as usize] = Self::op_i32ltu;
+        result[I32GTS as usize] = Self::op_i32gts;
+        result[I32GTU as usize] = Self::op_i32gtu;
+        result[I32LES as usize] = Self::op_i32les;
+        result[I32LEU as usize] = Self::op_i32leu;
+        result[I32GES as usize] = Self::op_i32ges;
+        result[I32GEU as usize] = Self::op_i32geu;
+        result[I64EQZ as usize] = Self::op_i64eqz;
+        result[I64EQ as usize] = Self::op_i64eq;
+        result[I64NE as usize] = Self::op_i64ne;
+        result[I64LTS as usize] = Self::op_i64lts;
+        result[I64LTU as usize] = Self::op_i64ltu;
+        result[I64GTS as usize] = Self::op_i64gts;
+        result[I64GTU as usize] = Self::op_i64gtu;
+        result[I64LES as usize] = Self::op_i64les;
+        result[I64LEU as usize] = Self::op_i64leu;
+        result[I64GES as usize] = Self::op_i64ges;
+        result[I64GEU as usize] = Self::op_i64geu;
+        result[F32EQ as usize] = Self::op_f32eq;
+        result[F32NE as usize] = Self::op_f32ne;
+        result[F32LT as usize] = Self::op_f32lt;
+        result[F32GT as usize] = Self::op_f32gt;
+        result[F32LE as usize] = Self::op_f32le;
+        result[F32GE as usize] = Self::op_f32ge;
+        result[F64EQ as usize] = Self::op_f64eq;
+        result[F64NE as usize] = Self::op_f64ne;
+        result[F64LT as usize] = Self::op_f64lt;
+        result[F64GT as usize] = Self::op_f64gt;
+        result[F64LE as usize] = Self::op_f64le;
+        result[F64GE as usize] = Self::op_f64ge;
+        result[I32CLZ as usize] = Self::op_i32clz;
+        result[I32CTZ as usize] = Self::op_i32ctz;
+        result[I32POPCNT as usize] = Self::op_i32popcnt;
+        result[I32ADD as usize] = Self::op_i32add;
+        result[I32SUB as usize] = Self::op_i32sub;
+        result[I32MUL as usize] = Self::op_i32mul;
+        result[I32DIVS as usize] = Self::op_i32divs;
+        result[I32DIVU as usize] = Self::op_i32divu;
+        result[I32REMS as usize] = Self::op_i32rems;
+        result[I32REMU as usize] = Self::op_i32remu;
+        result[I32AND as usize] = Self::op_i32and;
+        result[I32OR as usize] = Self::op_i32or;
+        result[I32XOR as usize] = Self::op_i32xor;
+        result[I32SHL as usize] = Self::op_i32shl;
+        result[I32SHRS as usize] = Self::op_i32shrs;
+        result[I32SHRU as usize] = Self::op_i32shru;
+        result[I32ROTL as usize] = Self::op_i32rotl;
+        result[I32ROTR as usize] = Self::op_i32rotr;
+        result[I64CLZ as usize] = Self::op_i64clz;
+        result[I64CTZ as usize] = Self::op_i64ctz;
+        result[I64POPCNT as usize] = Self::op_i64popcnt;
+        result[I64ADD as usize] = Self::op_i64add;
+        result[I64SUB as usize] = Self::op_i64sub;
+        result[I64MUL as usize] = Self::op_i64mul;
+        result[I64DIVS as usize] = Self::op_i64divs;
+        result[I64DIVU as usize] = Self::op_i64divu;
+        result[I64REMS as usize] = Self::op_i64rems;
+        result[I64REMU as usize] = Self::op_i64remu;
+        result[I64AND as usize] = Self::op_i64and;
+        result[I64OR as usize] = Self::op_i64or;
+        result[I64XOR as usize] = Self::op_i64xor;
+        result[I64SHL as usize] = Self::op_i64shl;
+        result[I64SHRS as usize] = Self::op_i64shrs;
+        result[I64SHRU as usize] = Self::op_i64shru;
+        result[I64ROTL as usize] = Self::op_i64rotl;
+        result[I64ROTR as usize] = Self::op_i64rotr;
+        result[F32ABS as usize] = Self::op_f32abs;
+        result[F32NEG as usize] = Self::op_f32neg;
+        result[F32CEIL as usize] = Self::op_f32ceil;
+        result[F32FLOOR as usize] = Self::op_f32floor;
+        result[F32TRUNC as usize] = Self::op_f32trunc;
+        result[F32NEAREST as usize] = Self::op_f32nearest;
+        result[F32SQRT as usize] = Self::op_f32sqrt;
+        result[F32ADD as usize] = Self::op_f32add;
+        result[F32SUB as usize] = Self::op_f32sub;
+        result[F32MUL as usize] = Self::op_f32mul;
+        result[F32DIV as usize] = Self::op_f32div;
+        result[F32MIN as usize] = Self::op_f32min;
+        result[F32MAX as usize] = Self::op_f32max;
+        result[F32COPYSIGN as usize] = Self::op_f32copysign;
+        result[F64ABS as usize] = Self::op_f64abs;
+        result[F64NEG as usize] = Self::op_f64neg;
+        result[F64CEIL as usize] = Self::op_f64ceil;
+        result[F64FLOOR as usize] = Self::op_f64floor;
+        result[F64TRUNC as usize] = Self::op_f64trunc;
+        result[F64NEAREST as usize] = Self::op_f64nearest;
+        result[F64SQRT as usize] = Self::op_f64sqrt;
+        result[F64ADD as usize] = Self::op_f64add;
+        result[F64SUB as usize] = Self::op_f64sub;
+        result[F64MUL as usize] = Self::op_f64mul;
+        result[F64DIV as usize] = Self::op_f64div;
+        result[F64MIN as usize] = Self::op_f64min;
+        result[F64MAX as usize] = Self::op_f64max;
+        result[F64COPYSIGN as usize] = Self::op_f64copysign;
+        result[I32WRAPI64 as usize] = Self::op_i32wrapi64;
+        result[I32TRUNCSF32 as usize] = Self::op_i32truncsf32;
+        result[I32TRUNCUF32 as usize] = Self::op_i32truncuf32;
+        result[I32TRUNCSF64 as usize] = Self::op_i32truncsf64;
+        result[I32TRUNCUF64 as usize] = Self::op_i32truncuf64;
+        result[I64EXTENDSI32 as usize] = Self::op_i64extendsi32;
+        result[I64EXTENDUI32 as usize] = Self::op_i64extendui32;
+        result[I64TRUNCSF32 as usize] = Self::op_i64truncsf32;
+        result[I64TRUNCUF32 as usize] = Self::op_i64truncuf32;
+        result[I64TRUNCSF64 as usize] = Self::op_i64truncsf64;
+        result[I64TRUNCUF64 as usize] = Self::op_i64truncuf64;
+        result[F32CONVERTSI32 as usize] = Self::op_f32convertsi32;
+        result[F32CONVERTUI32 as usize] = Self::op_f32convertui32;
+        result[F32CONVERTSI64 as usize] = Self::op_f32convertsi64;
+        result[F32CONVERTUI64 as usize] = Self::op_f32convertui64;
+        result[F32DEMOTEF64 as usize] = Self::op_f32demotef64;
+        result[F64CONVERTSI32 as usize] = Self::op_f64convertsi32;
+        result[F64CONVERTUI32 as usize] = Self::op_f64convertui32;
+        result[F64CONVERTSI64 as usize] = Self::op_f64convertsi64;
+        result[F64CONVERTUI64 as usize] = Self::op_f64convertui64;
+        result[F64PROMOTEF32 as usize] = Self::op_f64promotef32;
+        result[I32REINTERPRETF32 as usize] = Self::op_i32reinterpretf32;
+        result[I64REINTERPRETF64 as usize] = Self::op_i64reinterpretf64;
+        result[F32REINTERPRETI32 as usize] = Self::op_f32reinterpreti32;
+        result[F64REINTERPRETI64 as usize] = Self::op_f64reinterpreti64;
+        result[I32EXTEND8S as usize] = Self::op_i32extend8s;
+        result[I32EXTEND16S as usize] = Self::op_i32extend16s;
+        result[I64EXTEND8S as usize] = Self::op_i64extend8s;
+        result[I64EXTEND16S as usize] = Self::op_i64extend16s;
+        result[I64EXTEND32S as usize] = Self::op_i64extend32s;
+
+        result
+    };
+
+    fn op_invalid(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let byte = module.code.bytes[self.program_counter - 1];
+        Err(Error::InvalidCode(format!(
+            "Unsupported instruction byte {byte:#x?}"
+        )))
+    }
+
+    fn op_unreachable(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        Err(Error::Trap(TrapKind::Unreachable))
+    }
+
+    fn op_nop(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+
+    fn op_block(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (n_params, n_results) = self.fetch_block_type(module)?;
+        self.blocks.push(Block {
+            ty: BlockType::Normal,
+            vstack: self.block_start(n_params)?,
+            arity: n_results,
+        });
+        Ok(Action::Continue)
+    }
+
+    fn op_loop(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (n_params, _) = self.fetch_block_type(module)?;
+        self.blocks.push(Block {
+            ty: BlockType::Loop(self.program_counter),
+            vstack: self.block_start(n_params)?,
+            arity: n_params,
+        });
+        Ok(Action::Continue)
+    }
+
+    fn op_if(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        use OpCode::*;
+
+        let (n_params, n_results) = self.fetch_block_type(module)?;
+        let condition = self.value_store.pop_i32()?;
+        self.blocks.push(Block {
+            ty: BlockType::Normal,
+            vstack: self.block_start(n_params)?,
+            arity: n_results,
+        });
+        if condition == 0 {
+            let addr = self.program_counter as u32;
+            let cache_result = self.branch_cache[self.current_frame.fn_index]
+                .iter()
+                .find(|entry| entry.addr == addr);
+            if let Some(entry) = cache_result {
+                self.program_counter = entry.target as usize;
+                // `if` without `else`: we skipped its `end`, so the block is already finished
+                if module.code.bytes[self.program_counter - 1] == END as u8 {
+                    self.blocks.pop();
+                }
+            } else {
+                let target_depth = self.blocks.len();
+                let mut depth = target_depth;
+                loop {
+                    let skipped_op = self.skip_instruction(module)?;
+                    match skipped_op {
+                        BLOCK | LOOP | IF => {
+                            depth += 1;
+                        }
+                        END => {
+                            if depth == target_depth {
+                                // `if` without `else`
+                                self.blocks.pop();
+                                break;
+                            } else {
+                                depth -= 1;
                             }
                         }
-                        self.branch_cache[self.current_frame.fn_index].push(BranchCacheEntry {
-                            addr,
-                            argument: 0,
-                            target: self.program_counter as u32,
-                        });
+                        ELSE => {
+                            if depth == target_depth {
+                                break;
+                            }
+                        }
+                        _ => {}
                     }
                 }
+                self.branch_cache[self.current_frame.fn_index].push(BranchCacheEntry {
+                    addr,
+                    argument: 0,
+                    target: self.program_counter as u32,
+                });
             }
-            ELSE => {
-                // We only reach this point when we finish executing the "then" block of an IF statement
-                // (For a false condition, we would have skipped past the ELSE when we saw the IF)
-                // We don't want to execute the ELSE block, so we skip it, just like `br 0` would.
-                self.do_break(0, module);
+        }
+        Ok(Action::Continue)
+    }
+
+    fn op_else(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        // We only reach this point when we finish executing the "then" block of an IF statement
+        // (For a false condition, we would have skipped past the ELSE when we saw the IF)
+        // We don't want to execute the ELSE block, so we skip it, just like `br 0` would.
+        self.do_break(0, module)
+    }
+
+    fn op_end(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        if self.blocks.len() == (self.current_frame.body_block_index + 1) {
+            // implicit RETURN at end of function
+            self.verify_return()?;
+            self.do_return()
+        } else {
+            self.blocks.pop().unwrap();
+            Ok(Action::Continue)
+        }
+    }
+
+    fn op_br(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let relative_blocks_outward = self.fetch_immediate_u32(module)?;
+        self.do_break(relative_blocks_outward, module)
+    }
+
+    fn op_brif(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let relative_blocks_outward = self.fetch_immediate_u32(module)?;
+        let condition = self.value_store.pop_i32()?;
+        if condition != 0 {
+            self.do_break(relative_blocks_outward, module)
+        } else {
+            Ok(Action::Continue)
+        }
+    }
+
+    fn op_brtable(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let selector = self.value_store.pop_u32()?;
+        let nondefault_condition_count = self.fetch_immediate_u32(module)?;
+        let mut selected = None;
+        for i in 0..nondefault_condition_count {
+            let rel_blocks = self.fetch_immediate_u32(module)?;
+            if i == selector {
+                selected = Some(rel_blocks);
             }
-            END => {
-                if self.blocks.len() == (self.current_frame.body_block_index + 1) {
-                    // implicit RETURN at end of function
-                    action = self.do_return();
-                    implicit_return = true;
-                } else {
-                    self.blocks.pop().unwrap();
-                }
+        }
+        let fallback = self.fetch_immediate_u32(module)?;
+        let relative_blocks_outward = selected.unwrap_or(fallback);
+        self.do_break(relative_blocks_outward, module)
+    }
+
+    fn op_return(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        self.verify_return()?;
+        self.do_return()
+    }
+
+    fn op_call(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let fn_index = self.fetch_immediate_u32(module)? as usize;
+        self.do_call(None, fn_index, module)
+    }
+
+    fn op_callindirect(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (expected_signature, fn_index) = self.fetch_indirect_call_target(module)?;
+        self.do_call(Some(expected_signature), fn_index, module)
+    }
+
+    fn op_returncall(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let fn_index = self.fetch_immediate_u32(module)? as usize;
+        self.do_tail_call(None, fn_index, module)
+    }
+
+    fn op_returncallindirect(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (expected_signature, fn_index) = self.fetch_indirect_call_target(module)?;
+        self.do_tail_call(Some(expected_signature), fn_index, module)
+    }
+
+    /// Fetch the immediates of `call_indirect` or `return_call_indirect`, and pop the element index.
+    /// Returns the expected signature index and the function index.
+    fn fetch_indirect_call_target(
+        &mut self,
+        module: &WasmModule<'a>,
+    ) -> Result<(u32, usize), Error> {
+        let expected_signature = self.fetch_immediate_u32(module)?;
+        let table_index = self.fetch_immediate_u32(module)?;
+        let element_index = self.value_store.pop_u32()?;
+
+        // So far, all compilers seem to be emitting MVP-compatible code. (Rust, Zig, Roc...)
+        if table_index != 0 {
+            return Err(Error::InvalidCode(format!(
+                "Table index {table_index} is not supported. This interpreter only supports Wasm MVP"
+            )));
+        }
+
+        // Dereference the function pointer (look up the element index in the function table)
+        let fn_index = module
+            .element
+            .lookup(element_index)
+            .ok_or(Error::Trap(TrapKind::UndefinedElement(element_index)))?;
+
+        Ok((expected_signature, fn_index as usize))
+    }
+
+    fn op_drop(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        self.value_store.pop()?;
+        Ok(Action::Continue)
+    }
+
+    /// The untyped `select` only works on numbers, and both operands must have the same type.
+    /// Selecting references needs `select t`.
+    fn op_select(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let c = self.value_store.pop_i32()?;
+        let val2 = self.value_store.pop()?;
+        let val1 = self.value_store.pop()?;
+        val2.check_type(ValueType::from(val1))?;
+        let result = if c != 0 { val1 } else { val2 };
+        self.value_store.push(result);
+        Ok(Action::Continue)
+    }
+
+    /// `select t`, which names the type of its operands. It's the only way to select references,
+    /// but there are no reference types in this interpreter, so only numeric types are accepted.
+    fn op_selectt(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let type_count = self.fetch_immediate_u32(module)?;
+        if type_count != 1 {
+            return Err(Error::InvalidCode(format!(
+                "select must have exactly one result type, not {type_count}"
+            )));
+        }
+        let byte = module.code.bytes.get(self.program_counter).copied();
+        let ty = match byte {
+            Some(b) if (ValueType::F64 as u8..=ValueType::I32 as u8).contains(&b) => {
+                ValueType::from(b)
+            }
+            Some(b) if b == RefType::Func as u8 || b == RefType::Extern as u8 => {
+                return Err(Error::InvalidCode(
+                    "select on references isn't supported, because there are no reference types in this interpreter".into(),
+                ));
             }
-            BR => {
-                let relative_blocks_outward = self.fetch_immediate_u32(module);
-                self.do_break(relative_blocks_outward, module);
+            _ => {
+                return Err(Error::InvalidCode(format!(
+                    "Invalid select result type {byte:#04x?}"
+                )));
             }
-            BRIF => {
-                let relative_blocks_outward = self.fetch_immediate_u32(module);
-                let condition = self.value_store.pop_i32()?;
-                if condition != 0 {
-                    self.do_break(relative_blocks_outward, module);
-                }
+        };
+        self.program_counter += 1;
+        self.write_debug(ty);
+
+        let c = self.value_store.pop_i32()?;
+        let val2 = self.value_store.pop()?;
+        let val1 = self.value_store.pop()?;
+        val1.check_type(ty)?;
+        val2.check_type(ty)?;
+        let result = if c != 0 { val1 } else { val2 };
+        self.value_store.push(result);
+        Ok(Action::Continue)
+    }
+
+    fn op_getlocal(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let index = self.fetch_immediate_u32(module)?;
+        let value = self.current_frame.get_local(&self.value_store, index)?;
+        self.value_store.push(value);
+        Ok(Action::Continue)
+    }
+
+    fn op_setlocal(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let index = self.fetch_immediate_u32(module)?;
+        let value = self.value_store.pop()?;
+        let old_value = self.current_frame.get_local(&self.value_store, index)?;
+        self.verify_same_type(old_value, value)?;
+        self.current_frame
+            .set_local(&mut self.value_store, index, value);
+        Ok(Action::Continue)
+    }
+
+    fn op_teelocal(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let index = self.fetch_immediate_u32(module)?;
+        let value = self.value_store.peek()?;
+        let old_value = self.current_frame.get_local(&self.value_store, index)?;
+        self.verify_same_type(old_value, value)?;
+        self.current_frame
+            .set_local(&mut self.value_store, index, value);
+        Ok(Action::Continue)
+    }
+
+    /// Fetch a global index immediate, and check that the global exists
+    fn fetch_global_index(&mut self, module: &WasmModule<'a>) -> Result<u32, Error> {
+        let index = self.fetch_immediate_u32(module)?;
+        if index as usize >= self.globals.len() {
+            return Err(Error::InvalidCode(format!("Global {index} doesn't exist")));
+        }
+        Ok(index)
+    }
+
+    fn op_getglobal(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let index = self.fetch_global_index(module)?;
+        self.value_store.push(self.globals[index as usize]);
+        Ok(Action::Continue)
+    }
+
+    fn op_setglobal(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let index = self.fetch_global_index(module)?;
+        let value = self.value_store.pop()?;
+        let GlobalType {
+            value_type,
+            is_mutable,
+        } = self.global_types[index as usize];
+        if !is_mutable {
+            return Err(Error::ImmutableGlobal(index));
+        }
+        value.check_type(value_type)?;
+        self.globals[index as usize] = value;
+        Ok(Action::Continue)
+    }
+
+    fn op_i32load(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<4>(module)?;
+        let value = i32::from_le_bytes(bytes);
+        self.value_store.push(Value::I32(value));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<8>(module)?;
+        let value = i64::from_le_bytes(bytes);
+        self.value_store.push(Value::I64(value));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32load(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<4>(module)?;
+        let value = f32::from_le_bytes(bytes);
+        self.value_store.push(Value::F32(value));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64load(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<8>(module)?;
+        let value = f64::from_le_bytes(bytes);
+        self.value_store.push(Value::F64(value));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32load8s(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<1>(module)?;
+        let value = i8::from_le_bytes(bytes);
+        self.value_store.push(Value::I32(value as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32load8u(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let [value] = self.load_bytes::<1>(module)?;
+        self.value_store.push(Value::I32(value as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32load16s(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<2>(module)?;
+        let value = i16::from_le_bytes(bytes);
+        self.value_store.push(Value::I32(value as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32load16u(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<2>(module)?;
+        let value = u16::from_le_bytes(bytes);
+        self.value_store.push(Value::I32(value as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load8s(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<1>(module)?;
+        let value = i8::from_le_bytes(bytes);
+        self.value_store.push(Value::I64(value as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load8u(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let [value] = self.load_bytes::<1>(module)?;
+        self.value_store.push(Value::I64(value as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load16s(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<2>(module)?;
+        let value = i16::from_le_bytes(bytes);
+        self.value_store.push(Value::I64(value as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load16u(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<2>(module)?;
+        let value = u16::from_le_bytes(bytes);
+        self.value_store.push(Value::I64(value as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load32s(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<4>(module)?;
+        let value = i32::from_le_bytes(bytes);
+        self.value_store.push(Value::I64(value as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64load32u(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = self.load_bytes::<4>(module)?;
+        let value = u32::from_le_bytes(bytes);
+        self.value_store.push(Value::I64(value as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32store(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 4)?;
+        let unwrapped = value.expect_i32().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes());
+        Ok(Action::Continue)
+    }
+
+    fn op_i64store(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 8)?;
+        let unwrapped = value.expect_i64().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes());
+        Ok(Action::Continue)
+    }
+
+    fn op_f32store(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 4)?;
+        let unwrapped = value.expect_f32().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes());
+        Ok(Action::Continue)
+    }
+
+    fn op_f64store(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 8)?;
+        let unwrapped = value.expect_f64().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes());
+        Ok(Action::Continue)
+    }
+
+    fn op_i32store8(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 1)?;
+        let unwrapped = value.expect_i32().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes()[..1]);
+        Ok(Action::Continue)
+    }
+
+    fn op_i32store16(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 2)?;
+        let unwrapped = value.expect_i32().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes()[..2]);
+        Ok(Action::Continue)
+    }
+
+    fn op_i64store8(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 1)?;
+        let unwrapped = value.expect_i64().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes()[..1]);
+        Ok(Action::Continue)
+    }
+
+    fn op_i64store16(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 2)?;
+        let unwrapped = value.expect_i64().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes()[..2]);
+        Ok(Action::Continue)
+    }
+
+    fn op_i64store32(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let (target, value) = self.get_store_target_value(module, 4)?;
+        let unwrapped = value.expect_i64().map_err(Error::from)?;
+        target.copy_from_slice(&unwrapped.to_le_bytes()[..4]);
+        Ok(Action::Continue)
+    }
+
+    fn op_currentmemory(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let memory_index = self.fetch_memory_index(module)?;
+        let size = self.memory_at(memory_index).len() as i32 / MemorySection::PAGE_SIZE as i32;
+        self.value_store.push(Value::I32(size));
+        Ok(Action::Continue)
+    }
+
+    fn op_growmemory(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let memory_index = self.fetch_memory_index(module)?;
+        let grow_pages = self.value_store.pop_u32()?;
+        let result = match self.grow_memory_help(memory_index, grow_pages)? {
+            Some(old_pages) => old_pages as i32,
+            None => -1,
+        };
+        self.value_store.push(Value::I32(result));
+        Ok(Action::Continue)
+    }
+
+    /// Grow a memory, within the spec's, the module's and the host's limits, and return its previous size in pages.
+    /// The new pages are zeroed. Returns `None` if the growth would exceed the maximum size,
+    /// or an error if it would exceed the total memory budget.
+    fn grow_memory_help(
+        &mut self,
+        memory_index: u32,
+        grow_pages: u32,
+    ) -> Result<Option<u32>, Error> {
+        // At the 4 GiB limit, the size in bytes doesn't fit in a u32, but the size in pages does
+        let old_pages =
+            (self.memory_at(memory_index).len() / MemorySection::PAGE_SIZE as usize) as u32;
+        let new_pages = old_pages as u64 + grow_pages as u64;
+
+        let module_max_pages = self
+            .module
+            .memory
+            .max_bytes_at(memory_index)
+            .unwrap()
+            .map(|max_bytes| max_bytes / MemorySection::PAGE_SIZE);
+        let success = [
+            Some(MemorySection::MAX_PAGES),
+            module_max_pages,
+            self.max_memory_pages,
+        ]
+        .into_iter()
+        .flatten()
+        .all(|max_pages| new_pages <= max_pages as u64);
+        if !success {
+            return Ok(None);
+        }
+
+        let grow_bytes = grow_pages as u64 * MemorySection::PAGE_SIZE as u64;
+        let total_memory_grown = self.total_memory_grown + grow_bytes;
+        if let Some(budget) = self.total_memory_budget {
+            if total_memory_grown > budget {
+                return Err(Error::MemoryBudgetExhausted(budget));
             }
-            BRTABLE => {
-                let selector = self.value_store.pop_u32()?;
-                let nondefault_condition_count = self.fetch_immediate_u32(module);
-                let mut selected = None;
-                for i in 0..nondefault_condition_count {
-                    let rel_blocks = self.fetch_immediate_u32(module);
-                    if i == selector {
-                        selected = Some(rel_blocks);
+        }
+        self.total_memory_grown = total_memory_grown;
+
+        self.memory_at_mut(memory_index)
+            .extend(iter::repeat(0).take(grow_bytes as usize));
+        Ok(Some(old_pages))
+    }
+
+    /// Fetch a data segment index immediate, and check that the segment exists
+    fn fetch_data_segment_index(&mut self, module: &WasmModule<'a>) -> Result<u32, Error> {
+        let index = self.fetch_immediate_u32(module)?;
+        if index as usize >= self.data_segments.len() {
+            return Err(Error::InvalidCode(format!(
+                "Data segment {index} doesn't exist"
+            )));
+        }
+        Ok(index)
+    }
+
+    fn op_memory(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        // the first argument determines exactly which memory operation we have
+        let op_byte = module.code.bytes.get(self.program_counter).copied();
+        match MemoryInstruction::try_from(op_byte.unwrap_or(u8::MAX)) {
+            Ok(op) => match op {
+                MemoryInstruction::MemoryInit => {
+                    // skip the op byte
+                    self.program_counter += 1;
+                    let segment_index = self.fetch_data_segment_index(module)?;
+                    let memory_index = self.fetch_memory_index(module)?;
+
+                    let size = self.value_store.pop_u32()?;
+                    let source = self.value_store.pop_u32()?;
+                    let destination = self.value_store.pop_u32()?;
+
+                    let segment = self.data_segments[segment_index as usize];
+                    let source_end = source as u64 + size as u64;
+                    if source_end > segment.len() as u64 {
+                        return Err(Error::Trap(TrapKind::DataSegmentOutOfBounds(
+                            segment_index,
+                            source_end.min(u32::MAX as u64) as u32,
+                            segment.len() as u32,
+                        )));
+                    }
+                    let memory = self.memory_at_mut(memory_index);
+                    let destination_end = destination as u64 + size as u64;
+                    if destination_end > memory.len() as u64 {
+                        return Err(Error::Trap(TrapKind::MemoryOutOfBounds(
+                            (destination_end - 1).min(u32::MAX as u64) as u32,
+                            memory.len() as u32,
+                        )));
+                    }
+
+                    memory[destination as usize..][..size as usize]
+                        .copy_from_slice(&segment[source as usize..][..size as usize]);
+                }
+                MemoryInstruction::DataDrop => {
+                    // skip the op byte
+                    self.program_counter += 1;
+                    let segment_index = self.fetch_data_segment_index(module)?;
+                    self.data_segments[segment_index as usize] = &[];
+                }
+                MemoryInstruction::MemoryCopy => {
+                    let size = self.value_store.pop_u32()? as usize;
+                    let source = self.value_store.pop_u32()? as usize;
+                    let destination = self.value_store.pop_u32()? as usize;
+
+                    // skip the op byte
+                    self.program_counter += 1;
+                    let destination_index = self.fetch_memory_index(module)?;
+                    let source_index = self.fetch_memory_index(module)?;
+                    self.check_memory_access(source_index, source as u32, 0, size as u32)?;
+                    self.check_memory_access(
+                        destination_index,
+                        destination as u32,
+                        0,
+                        size as u32,
+                    )?;
+
+                    if destination_index == source_index {
+                        self.memory_at_mut(destination_index)
+                            .copy_within(source..source + size, destination)
+                    } else {
+                        // Can't borrow two memories at once, so go through a temporary buffer
+                        let bytes = self.memory_at(source_index)[source..][..size].to_vec();
+                        self.memory_at_mut(destination_index)[destination..][..size]
+                            .copy_from_slice(&bytes);
                     }
                 }
-                let fallback = self.fetch_immediate_u32(module);
-                let relative_blocks_outward = selected.unwrap_or(fallback);
-                self.do_break(relative_blocks_outward, module);
+                MemoryInstruction::MemoryFill => {
+                    let size = self.value_store.pop_u32()? as usize;
+                    let byte_value = self.value_store.pop_u32()? as u8;
+                    let destination = self.value_store.pop_u32()? as usize;
+
+                    // skip the op byte
+                    self.program_counter += 1;
+                    let memory_index = self.fetch_memory_index(module)?;
+                    self.check_memory_access(memory_index, destination as u32, 0, size as u32)?;
+
+                    self.memory_at_mut(memory_index)[destination..][..size].fill(byte_value);
+                }
+            },
+            Err(other) => {
+                return Err(Error::InvalidCode(format!(
+                    "Invalid memory instruction {other:?}"
+                )));
             }
-            RETURN => {
-                action = self.do_return();
+        };
+        Ok(Action::Continue)
+    }
+
+    /// Atomic instructions from the threads proposal.
+    /// We only have one thread, so `wait` can never be woken up and `notify` never wakes anyone.
+    fn op_atomic(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let op_byte = module.code.bytes.get(self.program_counter).copied();
+        self.program_counter += 1;
+        match AtomicInstruction::try_from(op_byte.unwrap_or(u8::MAX)) {
+            Ok(AtomicInstruction::MemoryAtomicNotify) => {
+                let _count = self.value_store.pop_u32()?;
+                self.get_atomic_address(module, 4)?;
+                let woken_count = 0;
+                self.value_store.push(Value::I32(woken_count));
+            }
+            Ok(AtomicInstruction::MemoryAtomicWait32) => {
+                let timeout = self.value_store.pop_i64()?;
+                let expected = self.value_store.pop_i32()?;
+                let (memory_index, addr) = self.get_atomic_address(module, 4)?;
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&self.memory_at(memory_index)[addr..][..4]);
+                let result =
+                    Self::atomic_wait_result(i32::from_le_bytes(bytes) == expected, timeout)?;
+                self.value_store.push(Value::I32(result));
+            }
+            Ok(AtomicInstruction::MemoryAtomicWait64) => {
+                let timeout = self.value_store.pop_i64()?;
+                let expected = self.value_store.pop_i64()?;
+                let (memory_index, addr) = self.get_atomic_address(module, 8)?;
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&self.memory_at(memory_index)[addr..][..8]);
+                let result =
+                    Self::atomic_wait_result(i64::from_le_bytes(bytes) == expected, timeout)?;
+                self.value_store.push(Value::I32(result));
             }
-            CALL => {
-                let fn_index = self.fetch_immediate_u32(module) as usize;
-                self.do_call(None, fn_index, module)?;
+            Err(other) => {
+                return Err(Error::InvalidCode(format!(
+                    "Invalid atomic instruction {other:?}"
+                )));
             }
-            CALLINDIRECT => {
-                let expected_signature = self.fetch_immediate_u32(module);
-                let table_index = self.fetch_immediate_u32(module);
-                let element_index = self.value_store.pop_u32()?;
-
-                // So far, all compilers seem to be emitting MVP-compatible code. (Rust, Zig, Roc...)
-                assert_eq!(
-                    table_index, 0,
-                    "Table index {table_index} not supported at file offset {file_offset:#x}. This interpreter only supports Wasm MVP."
-                );
+        }
+        Ok(Action::Continue)
+    }
+
+    /// Atomic accesses are like loads, except that the address must be aligned to the access size
+    fn get_atomic_address(
+        &mut self,
+        module: &WasmModule<'a>,
+        size: u32,
+    ) -> Result<(u32, usize), Error> {
+        let (memory_index, addr) = self.get_load_address(module, size)?;
+        if addr % size as usize != 0 {
+            return Err(Error::Trap(TrapKind::UnalignedAtomicAccess(
+                addr as u32,
+                size,
+            )));
+        }
+        Ok((memory_index, addr))
+    }
+
+    /// The result of `memory.atomic.wait` when no other thread exists to wake us up.
+    /// The spec's return codes are 0 for "ok" (woken up), 1 for "not-equal" and 2 for "timed-out".
+    /// A negative timeout means "wait forever", which would hang, so we trap instead.
+    fn atomic_wait_result(is_equal: bool, timeout: i64) -> Result<i32, Error> {
+        if !is_equal {
+            Ok(1)
+        } else if timeout >= 0 {
+            Ok(2)
+        } else {
+            Err(Error::AtomicWaitForever)
+        }
+    }
+
+    fn op_i32const(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let value = i32::parse((), &module.code.bytes, &mut self.program_counter)?;
+        self.write_debug(value);
+        self.value_store.push(Value::I32(value));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64const(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let value = i64::parse((), &module.code.bytes, &mut self.program_counter)?;
+        self.write_debug(value);
+        self.value_store.push(Value::I64(value));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32const(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = module
+            .code
+            .bytes
+            .get(self.program_counter..self.program_counter + 4)
+            .ok_or_else(|| Error::InvalidCode("Reached the end of the code".into()))?;
+        let value = f32::from_le_bytes(bytes.try_into().unwrap());
+        self.write_debug(value);
+        self.value_store.push(Value::F32(value));
+        self.program_counter += 4;
+        Ok(Action::Continue)
+    }
+
+    fn op_f64const(&mut self, module: &WasmModule<'a>) -> Result<Action, Error> {
+        let bytes = module
+            .code
+            .bytes
+            .get(self.program_counter..self.program_counter + 8)
+            .ok_or_else(|| Error::InvalidCode("Reached the end of the code".into()))?;
+        let value = f64::from_le_bytes(bytes.try_into().unwrap());
+        self.write_debug(value);
+        self.value_store.push(Value::F64(value));
+        self.program_counter += 8;
+        Ok(Action::Continue)
+    }
+
+    fn op_i32eqz(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i32()?;
+        let result: bool = arg == 0;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32eq(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result: bool = arg1 == arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32ne(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result: bool = arg1 != arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32lts(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result: bool = arg1 < arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32ltu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let result: bool = arg1 < arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32gts(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result: bool = arg1 > arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32gtu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let result: bool = arg1 > arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32les(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result: bool = arg1 <= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32leu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let result: bool = arg1 <= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32ges(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result: bool = arg1 >= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32geu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let result: bool = arg1 >= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64eqz(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i64()?;
+        let result: bool = arg == 0;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64eq(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result: bool = arg1 == arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64ne(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result: bool = arg1 != arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64lts(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result: bool = arg1 < arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64ltu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let result: bool = arg1 < arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64gts(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result: bool = arg1 > arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64gtu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let result: bool = arg1 > arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64les(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result: bool = arg1 <= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64leu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let result: bool = arg1 <= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64ges(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result: bool = arg1 >= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64geu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let result: bool = arg1 >= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32eq(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        let result: bool = arg1 == arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32ne(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        let result: bool = arg1 != arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32lt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        let result: bool = arg1 < arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32gt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        let result: bool = arg1 > arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32le(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        let result: bool = arg1 <= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32ge(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        let result: bool = arg1 >= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64eq(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        let result: bool = arg1 == arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64ne(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        let result: bool = arg1 != arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64lt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        let result: bool = arg1 < arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64gt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        let result: bool = arg1 > arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64le(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        let result: bool = arg1 <= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64ge(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        let result: bool = arg1 >= arg2;
+        self.value_store.push(Value::I32(result as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32clz(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg.leading_zeros()));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32ctz(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg.trailing_zeros()));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32popcnt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg.count_ones()));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32add(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result = if self.trap_on_overflow {
+            arg1.checked_add(arg2)
+                .ok_or(Error::Trap(TrapKind::IntegerOverflow))?
+        } else {
+            arg1.wrapping_add(arg2)
+        };
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32sub(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result = if self.trap_on_overflow {
+            arg1.checked_sub(arg2)
+                .ok_or(Error::Trap(TrapKind::IntegerOverflow))?
+        } else {
+            arg1.wrapping_sub(arg2)
+        };
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32mul(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let result = if self.trap_on_overflow {
+            arg1.checked_mul(arg2)
+                .ok_or(Error::Trap(TrapKind::IntegerOverflow))?
+        } else {
+            arg1.wrapping_mul(arg2)
+        };
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32divs(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        let result = arg1
+            .checked_div(arg2)
+            .ok_or(Error::Trap(TrapKind::IntegerOverflow))?;
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32divu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        self.value_store.push(Value::from(arg1.wrapping_div(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32rems(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32remu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32and(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg1 & arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32or(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg1 | arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32xor(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg1 ^ arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32shl(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        // Take modulo N as per the spec https://webassembly.github.io/spec/core/exec/numerics.html#op-ishl
+        let k = arg2 % 32;
+        self.value_store.push(Value::from(arg1 << k));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32shrs(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        // The shift amount is unsigned, even for an arithmetic shift
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_i32()?;
+        let k = arg2 % 32;
+        self.value_store.push(Value::from(arg1 >> k));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32shru(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let k = arg2 % 32;
+        self.value_store.push(Value::from(arg1 >> k));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32rotl(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let k = arg2 % 32;
+        self.value_store.push(Value::from(arg1.rotate_left(k)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32rotr(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u32()?;
+        let arg1 = self.value_store.pop_u32()?;
+        let k = arg2 % 32;
+        self.value_store.push(Value::from(arg1.rotate_right(k)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64clz(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u64()?;
+        self.value_store
+            .push(Value::from(arg.leading_zeros() as u64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64ctz(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u64()?;
+        self.value_store
+            .push(Value::from(arg.trailing_zeros() as u64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64popcnt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u64()?;
+        self.value_store.push(Value::from(arg.count_ones() as u64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64add(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result = if self.trap_on_overflow {
+            arg1.checked_add(arg2)
+                .ok_or(Error::Trap(TrapKind::IntegerOverflow))?
+        } else {
+            arg1.wrapping_add(arg2)
+        };
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64sub(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result = if self.trap_on_overflow {
+            arg1.checked_sub(arg2)
+                .ok_or(Error::Trap(TrapKind::IntegerOverflow))?
+        } else {
+            arg1.wrapping_sub(arg2)
+        };
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64mul(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let result = if self.trap_on_overflow {
+            arg1.checked_mul(arg2)
+                .ok_or(Error::Trap(TrapKind::IntegerOverflow))?
+        } else {
+            arg1.wrapping_mul(arg2)
+        };
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64divs(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        let result = arg1
+            .checked_div(arg2)
+            .ok_or(Error::Trap(TrapKind::IntegerOverflow))?;
+        self.value_store.push(Value::from(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64divu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        self.value_store.push(Value::from(arg1.wrapping_div(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64rems(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_i64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64remu(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        if arg2 == 0 {
+            return Err(Error::Trap(TrapKind::IntegerDivideByZero));
+        }
+        self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64and(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        self.value_store.push(Value::from(arg1 & arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64or(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        self.value_store.push(Value::from(arg1 | arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64xor(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        self.value_store.push(Value::from(arg1 ^ arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64shl(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        // Take modulo N as per the spec https://webassembly.github.io/spec/core/exec/numerics.html#op-ishl
+        let k = arg2 % 64;
+        self.value_store.push(Value::from(arg1 << k));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64shrs(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        // The shift amount is unsigned, even for an arithmetic shift
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_i64()?;
+        let k = arg2 % 64;
+        self.value_store.push(Value::from(arg1 >> k));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64shru(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let k = arg2 % 64;
+        self.value_store.push(Value::from(arg1 >> k));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64rotl(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let k = (arg2 % 64) as u32;
+        self.value_store.push(Value::from(arg1.rotate_left(k)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64rotr(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_u64()?;
+        let arg1 = self.value_store.pop_u64()?;
+        let k = (arg2 % 64) as u32;
+        self.value_store.push(Value::from(arg1.rotate_right(k)));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32abs(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg.abs()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32neg(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(-arg));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32ceil(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg.ceil()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32floor(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg.floor()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32trunc(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg.trunc()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32nearest(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        // https://webassembly.github.io/spec/core/exec/numerics.html#op-fnearest
+        let arg = self.value_store.pop_f32()?;
+        let rounded = arg.round(); // "Rounds half-way cases away from 0.0"
+        let frac = arg - rounded;
+        let result = if frac == 0.5 || frac == -0.5 {
+            let rounded_half = rounded / 2.0;
+            let is_rounded_even = rounded_half.trunc() == rounded_half;
+            if is_rounded_even {
+                rounded
+            } else if rounded < arg {
+                rounded + 1.0
+            } else {
+                rounded - 1.0
+            }
+        } else {
+            rounded
+        };
+        // Zero results keep the sign of the input, e.g. -0.5 rounds to -0.0
+        let result = result.copysign(arg);
+        self.value_store.push(Value::F32(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32sqrt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg.sqrt()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32add(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg1 + arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32sub(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg1 - arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32mul(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg1 * arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32div(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg1 / arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32min(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(wasm_fmin(arg1, arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32max(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(wasm_fmax(arg1, arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_f32copysign(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f32()?;
+        let arg1 = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F32(arg1.copysign(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64abs(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg.abs()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64neg(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(-arg));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64ceil(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg.ceil()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64floor(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg.floor()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64trunc(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg.trunc()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64nearest(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        // https://webassembly.github.io/spec/core/exec/numerics.html#op-fnearest
+        let arg = self.value_store.pop_f64()?;
+        let rounded = arg.round(); // "Rounds half-way cases away from 0.0"
+        let frac = arg - rounded;
+        let result = if frac == 0.5 || frac == -0.5 {
+            let rounded_half = rounded / 2.0;
+            let is_rounded_even = rounded_half.trunc() == rounded_half;
+            if is_rounded_even {
+                rounded
+            } else if rounded < arg {
+                rounded + 1.0
+            } else {
+                rounded - 1.0
+            }
+        } else {
+            rounded
+        };
+        // Zero results keep the sign of the input, e.g. -0.5 rounds to -0.0
+        let result = result.copysign(arg);
+        self.value_store.push(Value::F64(result));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64sqrt(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg.sqrt()));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64add(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg1 + arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64sub(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg1 - arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64mul(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg1 * arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64div(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg1 / arg2));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64min(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(wasm_fmin(arg1, arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64max(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(wasm_fmax(arg1, arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_f64copysign(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg2 = self.value_store.pop_f64()?;
+        let arg1 = self.value_store.pop_f64()?;
+        self.value_store.push(Value::F64(arg1.copysign(arg2)));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32wrapi64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u64()?;
+        let wrapped: u32 = (arg & 0xffff_ffff) as u32;
+        self.value_store.push(Value::from(wrapped));
+        Ok(Action::Continue)
+    }
+
+    /// Truncate a float toward zero, trapping if it's NaN or the result doesn't fit in the integer type.
+    /// Every f32 converts exactly to f64, and so do the limits of every integer type.
+    fn trunc_to_int(arg: f64, signed: bool, bits: i32) -> Result<f64, Error> {
+        let (min, max_exclusive) = if signed {
+            (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1))
+        } else {
+            (0.0, 2f64.powi(bits))
+        };
+        let truncated = arg.trunc();
+        if truncated >= min && truncated < max_exclusive {
+            Ok(truncated)
+        } else {
+            Err(Error::Trap(TrapKind::InvalidConversionToInteger))
+        }
+    }
+
+    fn op_i32truncsf32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()? as f64;
+        let truncated = Self::trunc_to_int(arg, true, 32)?;
+        self.value_store.push(Value::I32(truncated as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32truncuf32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()? as f64;
+        let truncated = Self::trunc_to_int(arg, false, 32)?;
+        self.value_store.push(Value::from(truncated as u32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32truncsf64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        let truncated = Self::trunc_to_int(arg, true, 32)?;
+        self.value_store.push(Value::I32(truncated as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i32truncuf64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        let truncated = Self::trunc_to_int(arg, false, 32)?;
+        self.value_store.push(Value::from(truncated as u32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64extendsi32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i32()?;
+        self.value_store.push(Value::I64(arg as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64extendui32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u32()?;
+        self.value_store.push(Value::from(arg as u64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64truncsf32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()? as f64;
+        let truncated = Self::trunc_to_int(arg, true, 64)?;
+        self.value_store.push(Value::I64(truncated as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64truncuf32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()? as f64;
+        let truncated = Self::trunc_to_int(arg, false, 64)?;
+        self.value_store.push(Value::from(truncated as u64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64truncsf64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        let truncated = Self::trunc_to_int(arg, true, 64)?;
+        self.value_store.push(Value::I64(truncated as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64truncuf64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        let truncated = Self::trunc_to_int(arg, false, 64)?;
+        self.value_store.push(Value::from(truncated as u64));
+        Ok(Action::Continue)
+    }
 
-                // Dereference the function pointer (look up the element index in the function table)
-                let fn_index = module.element.lookup(element_index).unwrap_or_else(|| {
-                    panic!(
-                        "Indirect function call failed. There is no function with element index {element_index}"
-                    )
-                });
+    fn op_f32convertsi32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i32()?;
+        self.value_store.push(Value::F32(arg as f32));
+        Ok(Action::Continue)
+    }
 
-                self.do_call(Some(expected_signature), fn_index as usize, module)?;
-            }
-            DROP => {
-                self.value_store.pop();
-            }
-            SELECT => {
-                let c = self.value_store.pop_i32()?;
-                let val2 = self.value_store.pop();
-                let val1 = self.value_store.pop();
-                let actual = ValueType::from(val2);
-                let expected = ValueType::from(val1);
-                if actual != expected {
-                    return Err(Error::Type(expected, actual));
-                }
-                let result = if c != 0 { val1 } else { val2 };
-                self.value_store.push(result);
-            }
-            GETLOCAL => {
-                let index = self.fetch_immediate_u32(module);
-                let value = self.current_frame.get_local(&self.value_store, index);
-                self.value_store.push(value);
-            }
-            SETLOCAL => {
-                let index = self.fetch_immediate_u32(module);
-                let value = self.value_store.pop();
-                self.current_frame
-                    .set_local(&mut self.value_store, index, value);
-            }
-            TEELOCAL => {
-                let index = self.fetch_immediate_u32(module);
-                let value = self.value_store.peek();
-                self.current_frame
-                    .set_local(&mut self.value_store, index, value);
-            }
-            GETGLOBAL => {
-                let index = self.fetch_immediate_u32(module);
-                self.value_store.push(self.globals[index as usize]);
-            }
-            SETGLOBAL => {
-                let index = self.fetch_immediate_u32(module);
-                self.globals[index as usize] = self.value_store.pop();
-            }
-            I32LOAD => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 4];
-                bytes.copy_from_slice(&self.memory[addr..][..4]);
-                let value = i32::from_le_bytes(bytes);
-                self.value_store.push(Value::I32(value));
-            }
-            I64LOAD => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 8];
-                bytes.copy_from_slice(&self.memory[addr..][..8]);
-                let value = i64::from_le_bytes(bytes);
-                self.value_store.push(Value::I64(value));
-            }
-            F32LOAD => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 4];
-                bytes.copy_from_slice(&self.memory[addr..][..4]);
-                let value = f32::from_le_bytes(bytes);
-                self.value_store.push(Value::F32(value));
-            }
-            F64LOAD => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 8];
-                bytes.copy_from_slice(&self.memory[addr..][..8]);
-                let value = f64::from_le_bytes(bytes);
-                self.value_store.push(Value::F64(value));
-            }
-            I32LOAD8S => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 1];
-                bytes.copy_from_slice(&self.memory[addr..][..1]);
-                let value = i8::from_le_bytes(bytes);
-                self.value_store.push(Value::I32(value as i32));
-            }
-            I32LOAD8U => {
-                let addr = self.get_load_address(module)? as usize;
-                let value = self.memory[addr];
-                self.value_store.push(Value::I32(value as i32));
-            }
-            I32LOAD16S => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 2];
-                bytes.copy_from_slice(&self.memory[addr..][..2]);
-                let value = i16::from_le_bytes(bytes);
-                self.value_store.push(Value::I32(value as i32));
-            }
-            I32LOAD16U => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 2];
-                bytes.copy_from_slice(&self.memory[addr..][..2]);
-                let value = u16::from_le_bytes(bytes);
-                self.value_store.push(Value::I32(value as i32));
-            }
-            I64LOAD8S => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 1];
-                bytes.copy_from_slice(&self.memory[addr..][..1]);
-                let value = i8::from_le_bytes(bytes);
-                self.value_store.push(Value::I64(value as i64));
-            }
-            I64LOAD8U => {
-                let addr = self.get_load_address(module)? as usize;
-                let value = self.memory[addr];
-                self.value_store.push(Value::I64(value as i64));
-            }
-            I64LOAD16S => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 2];
-                bytes.copy_from_slice(&self.memory[addr..][..2]);
-                let value = i16::from_le_bytes(bytes);
-                self.value_store.push(Value::I64(value as i64));
-            }
-            I64LOAD16U => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 2];
-                bytes.copy_from_slice(&self.memory[addr..][..2]);
-                let value = u16::from_le_bytes(bytes);
-                self.value_store.push(Value::I64(value as i64));
-            }
-            I64LOAD32S => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 4];
-                bytes.copy_from_slice(&self.memory[addr..][..4]);
-                let value = i32::from_le_bytes(bytes);
-                self.value_store.push(Value::I64(value as i64));
-            }
-            I64LOAD32U => {
-                let addr = self.get_load_address(module)? as usize;
-                let mut bytes = [0; 4];
-                bytes.copy_from_slice(&self.memory[addr..][..4]);
-                let value = u32::from_le_bytes(bytes);
-                self.value_store.push(Value::I64(value as i64));
-            }
-            I32STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i32().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..4];
-                target.copy_from_slice(&unwrapped.to_le_bytes());
-            }
-            I64STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i64().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..8];
-                target.copy_from_slice(&unwrapped.to_le_bytes());
-            }
-            F32STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_f32().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..4];
-                target.copy_from_slice(&unwrapped.to_le_bytes());
-            }
-            F64STORE => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_f64().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..8];
-                target.copy_from_slice(&unwrapped.to_le_bytes());
-            }
-            I32STORE8 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i32().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..1];
-                target.copy_from_slice(&unwrapped.to_le_bytes()[..1]);
-            }
-            I32STORE16 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i32().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..2];
-                target.copy_from_slice(&unwrapped.to_le_bytes()[..2]);
-            }
-            I64STORE8 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i64().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..1];
-                target.copy_from_slice(&unwrapped.to_le_bytes()[..1]);
-            }
-            I64STORE16 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i64().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..2];
-                target.copy_from_slice(&unwrapped.to_le_bytes()[..2]);
-            }
-            I64STORE32 => {
-                let (addr, value) = self.get_store_addr_value(module)?;
-                let unwrapped = value.expect_i64().map_err(Error::from)?;
-                let target = &mut self.memory[addr..][..4];
-                target.copy_from_slice(&unwrapped.to_le_bytes()[..4]);
-            }
-            CURRENTMEMORY => {
-                let memory_index = self.fetch_immediate_u32(module);
-                assert_eq!(memory_index, 0);
-                let size = self.memory.len() as i32 / MemorySection::PAGE_SIZE as i32;
-                self.value_store.push(Value::I32(size));
-            }
-            GROWMEMORY => {
-                let memory_index = self.fetch_immediate_u32(module);
-                assert_eq!(memory_index, 0);
-                let old_bytes = self.memory.len() as u32;
-                let old_pages = old_bytes / MemorySection::PAGE_SIZE;
-                let grow_pages = self.value_store.pop_u32()?;
-                let grow_bytes = grow_pages * MemorySection::PAGE_SIZE;
-                let new_bytes = old_bytes + grow_bytes;
-
-                let success = match module.memory.max_bytes().unwrap() {
-                    Some(max_bytes) => new_bytes <= max_bytes,
-                    None => true,
-                };
-                if success {
-                    self.memory
-                        .extend(iter::repeat(0).take(grow_bytes as usize));
-                    self.value_store.push(Value::I32(old_pages as i32));
-                } else {
-                    self.value_store.push(Value::I32(-1));
-                }
-            }
-            MEMORY => {
-                // the first argument determines exactly which memory operation we have
-                match MemoryInstruction::try_from(module.code.bytes[self.program_counter]) {
-                    Ok(op) => match op {
-                        MemoryInstruction::MemoryInit => todo!("WASM instruction: memory.init"),
-                        MemoryInstruction::DataDrop => todo!("WASM instruction: data.drop"),
-                        MemoryInstruction::MemoryCopy => {
-                            let size = self.value_store.pop_u32()? as usize;
-                            let source = self.value_store.pop_u32()? as usize;
-                            let destination = self.value_store.pop_u32()? as usize;
-
-                            // skip the op byte and an extra two zero bytes.
-                            // in future versions of WebAssembly this byte may be used to index additional memories
-                            self.program_counter += 1 + 2;
-
-                            self.memory.copy_within(source..source + size, destination)
-                        }
-                        MemoryInstruction::MemoryFill => {
-                            let size = self.value_store.pop_u32()? as usize;
-                            let byte_value = self.value_store.pop_u32()? as u8;
-                            let destination = self.value_store.pop_u32()? as usize;
+    fn op_f32convertui32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u32()?;
+        self.value_store.push(Value::F32(arg as f32));
+        Ok(Action::Continue)
+    }
 
-                            // skip the op byte and an extra zero byte.
-                            // in future versions of WebAssembly this byte may be used to index additional memories
-                            self.program_counter += 1 + 1;
+    fn op_f32convertsi64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i64()?;
+        self.value_store.push(Value::F32(arg as f32));
+        Ok(Action::Continue)
+    }
 
-                            self.memory[destination..][..size].fill(byte_value);
-                        }
-                    },
-                    Err(other) => unreachable!("invalid memory instruction {other:?}"),
-                };
-            }
-            I32CONST => {
-                let value = i32::parse((), &module.code.bytes, &mut self.program_counter).unwrap();
-                self.write_debug(value);
-                self.value_store.push(Value::I32(value));
-            }
-            I64CONST => {
-                let value = i64::parse((), &module.code.bytes, &mut self.program_counter).unwrap();
-                self.write_debug(value);
-                self.value_store.push(Value::I64(value));
-            }
-            F32CONST => {
-                let mut bytes = [0; 4];
-                bytes.copy_from_slice(&module.code.bytes[self.program_counter..][..4]);
-                let value = f32::from_le_bytes(bytes);
-                self.write_debug(value);
-                self.value_store.push(Value::F32(value));
-                self.program_counter += 4;
-            }
-            F64CONST => {
-                let mut bytes = [0; 8];
-                bytes.copy_from_slice(&module.code.bytes[self.program_counter..][..8]);
-                let value = f64::from_le_bytes(bytes);
-                self.write_debug(value);
-                self.value_store.push(Value::F64(value));
-                self.program_counter += 8;
-            }
+    fn op_f32convertui64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u64()?;
+        self.value_store.push(Value::F32(arg as f32));
+        Ok(Action::Continue)
+    }
 
-            I32EQZ => {
-                let arg = self.value_store.pop_i32()?;
-                let result: bool = arg == 0;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32EQ => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let result: bool = arg1 == arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32NE => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let result: bool = arg1 != arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32LTS => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let result: bool = arg1 < arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32LTU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let result: bool = arg1 < arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32GTS => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let result: bool = arg1 > arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32GTU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let result: bool = arg1 > arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32LES => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let result: bool = arg1 <= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32LEU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let result: bool = arg1 <= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32GES => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let result: bool = arg1 >= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I32GEU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let result: bool = arg1 >= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
+    fn op_f32demotef64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f64()?;
+        // Rust's `as` rounds to nearest-even and overflows to infinity, as Wasm requires
+        self.value_store.push(Value::F32(arg as f32));
+        Ok(Action::Continue)
+    }
 
-            I64EQZ => {
-                let arg = self.value_store.pop_i64()?;
-                let result: bool = arg == 0;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64EQ => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let result: bool = arg1 == arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64NE => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let result: bool = arg1 != arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64LTS => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let result: bool = arg1 < arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64LTU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let result: bool = arg1 < arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64GTS => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let result: bool = arg1 > arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64GTU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let result: bool = arg1 > arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64LES => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let result: bool = arg1 <= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64LEU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let result: bool = arg1 <= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64GES => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let result: bool = arg1 >= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            I64GEU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let result: bool = arg1 >= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
+    fn op_f64convertsi32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i32()?;
+        self.value_store.push(Value::F64(arg as f64));
+        Ok(Action::Continue)
+    }
 
-            F32EQ => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result: bool = arg1 == arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F32NE => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result: bool = arg1 != arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F32LT => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result: bool = arg1 < arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F32GT => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result: bool = arg1 > arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F32LE => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result: bool = arg1 <= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F32GE => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result: bool = arg1 >= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
+    fn op_f64convertui32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u32()?;
+        self.value_store.push(Value::F64(arg as f64));
+        Ok(Action::Continue)
+    }
 
-            F64EQ => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result: bool = arg1 == arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F64NE => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result: bool = arg1 != arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F64LT => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result: bool = arg1 < arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F64GT => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result: bool = arg1 > arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F64LE => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result: bool = arg1 <= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
-            F64GE => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result: bool = arg1 >= arg2;
-                self.value_store.push(Value::I32(result as i32));
-            }
+    fn op_f64convertsi64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_i64()?;
+        self.value_store.push(Value::F64(arg as f64));
+        Ok(Action::Continue)
+    }
 
-            I32CLZ => {
-                let arg = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg.leading_zeros()));
-            }
-            I32CTZ => {
-                let arg = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg.trailing_zeros()));
-            }
-            I32POPCNT => {
-                let arg = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg.count_ones()));
-            }
-            I32ADD => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                self.value_store.push(Value::from(arg1.wrapping_add(arg2)));
-            }
-            I32SUB => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                self.value_store.push(Value::from(arg1.wrapping_sub(arg2)));
-            }
-            I32MUL => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                self.value_store.push(Value::from(arg1.wrapping_mul(arg2)));
-            }
-            I32DIVS => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                self.value_store.push(Value::from(arg1.wrapping_div(arg2)));
-            }
-            I32DIVU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg1.wrapping_div(arg2)));
-            }
-            I32REMS => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
-            }
-            I32REMU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
-            }
-            I32AND => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg1 & arg2));
-            }
-            I32OR => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg1 | arg2));
-            }
-            I32XOR => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg1 ^ arg2));
-            }
-            I32SHL => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                // Take modulo N as per the spec https://webassembly.github.io/spec/core/exec/numerics.html#op-ishl
-                let k = arg2 % 32;
-                self.value_store.push(Value::from(arg1 << k));
-            }
-            I32SHRS => {
-                let arg2 = self.value_store.pop_i32()?;
-                let arg1 = self.value_store.pop_i32()?;
-                let k = arg2 % 32;
-                self.value_store.push(Value::from(arg1 >> k));
-            }
-            I32SHRU => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let k = arg2 % 32;
-                self.value_store.push(Value::from(arg1 >> k));
-            }
-            I32ROTL => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let k = arg2 % 32;
-                self.value_store.push(Value::from(arg1.rotate_left(k)));
-            }
-            I32ROTR => {
-                let arg2 = self.value_store.pop_u32()?;
-                let arg1 = self.value_store.pop_u32()?;
-                let k = arg2 % 32;
-                self.value_store.push(Value::from(arg1.rotate_right(k)));
-            }
+    fn op_f64convertui64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_u64()?;
+        self.value_store.push(Value::F64(arg as f64));
+        Ok(Action::Continue)
+    }
 
-            I64CLZ => {
-                let arg = self.value_store.pop_u64()?;
-                self.value_store
-                    .push(Value::from(arg.leading_zeros() as u64));
-            }
-            I64CTZ => {
-                let arg = self.value_store.pop_u64()?;
-                self.value_store
-                    .push(Value::from(arg.trailing_zeros() as u64));
-            }
-            I64POPCNT => {
-                let arg = self.value_store.pop_u64()?;
-                self.value_store.push(Value::from(arg.count_ones() as u64));
-            }
-            I64ADD => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                self.value_store.push(Value::from(arg1.wrapping_add(arg2)));
-            }
-            I64SUB => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                self.value_store.push(Value::from(arg1.wrapping_sub(arg2)));
-            }
-            I64MUL => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                self.value_store.push(Value::from(arg1.wrapping_mul(arg2)));
-            }
-            I64DIVS => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                self.value_store.push(Value::from(arg1.wrapping_div(arg2)));
-            }
-            I64DIVU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                self.value_store.push(Value::from(arg1.wrapping_div(arg2)));
-            }
-            I64REMS => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
-            }
-            I64REMU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                self.value_store.push(Value::from(arg1.wrapping_rem(arg2)));
-            }
-            I64AND => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                self.value_store.push(Value::from(arg1 & arg2));
-            }
-            I64OR => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                self.value_store.push(Value::from(arg1 | arg2));
-            }
-            I64XOR => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                self.value_store.push(Value::from(arg1 ^ arg2));
-            }
-            I64SHL => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                // Take modulo N as per the spec https://webassembly.github.io/spec/core/exec/numerics.html#op-ishl
-                let k = arg2 % 64;
-                self.value_store.push(Value::from(arg1 << k));
-            }
-            I64SHRS => {
-                let arg2 = self.value_store.pop_i64()?;
-                let arg1 = self.value_store.pop_i64()?;
-                let k = arg2 % 64;
-                self.value_store.push(Value::from(arg1 >> k));
-            }
-            I64SHRU => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let k = arg2 % 64;
-                self.value_store.push(Value::from(arg1 >> k));
-            }
-            I64ROTL => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let k = (arg2 % 64) as u32;
-                self.value_store.push(Value::from(arg1.rotate_left(k)));
-            }
-            I64ROTR => {
-                let arg2 = self.value_store.pop_u64()?;
-                let arg1 = self.value_store.pop_u64()?;
-                let k = (arg2 % 64) as u32;
-                self.value_store.push(Value::from(arg1.rotate_right(k)));
-            }
+    fn op_f64promotef32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let arg = self.value_store.pop_f32()?;
+        self.value_store.push(Value::F64(arg as f64));
+        Ok(Action::Continue)
+    }
 
-            F32ABS => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg.abs()));
-            }
-            F32NEG => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(-arg));
-            }
-            F32CEIL => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg.ceil()));
-            }
-            F32FLOOR => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg.floor()));
-            }
-            F32TRUNC => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg.trunc()));
-            }
-            F32NEAREST => {
-                // https://webassembly.github.io/spec/core/exec/numerics.html#op-fnearest
-                let arg = self.value_store.pop_f32()?;
-                let rounded = arg.round(); // "Rounds half-way cases away from 0.0"
-                let frac = arg - rounded;
-                let result = if frac == 0.5 || frac == -0.5 {
-                    let rounded_half = rounded / 2.0;
-                    let is_rounded_even = rounded_half.trunc() == rounded_half;
-                    if is_rounded_even {
-                        rounded
-                    } else if rounded < arg {
-                        rounded + 1.0
-                    } else {
-                        rounded - 1.0
-                    }
-                } else {
-                    rounded
-                };
-                self.value_store.push(Value::F32(result));
-            }
-            F32SQRT => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg.sqrt()));
-            }
-            F32ADD => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg1 + arg2));
-            }
-            F32SUB => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg1 - arg2));
-            }
-            F32MUL => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg1 * arg2));
-            }
-            F32DIV => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F32(arg1 / arg2));
-            }
-            F32MIN => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result = if arg1 < arg2 { arg1 } else { arg2 };
-                self.value_store.push(Value::F32(result));
-            }
-            F32MAX => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result = if arg1 > arg2 { arg1 } else { arg2 };
-                self.value_store.push(Value::F32(result));
-            }
-            F32COPYSIGN => {
-                let arg2 = self.value_store.pop_f32()?;
-                let arg1 = self.value_store.pop_f32()?;
-                let result = if arg1.is_sign_negative() == arg2.is_sign_negative() {
-                    arg1
-                } else {
-                    arg2
-                };
-                self.value_store.push(Value::F32(result));
-            }
+    fn op_i32reinterpretf32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_f32()?;
+        self.value_store
+            .push(Value::I32(i32::from_ne_bytes(x.to_ne_bytes())));
+        Ok(Action::Continue)
+    }
 
-            F64ABS => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg.abs()));
-            }
-            F64NEG => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(-arg));
-            }
-            F64CEIL => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg.ceil()));
-            }
-            F64FLOOR => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg.floor()));
-            }
-            F64TRUNC => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg.trunc()));
-            }
-            F64NEAREST => {
-                // https://webassembly.github.io/spec/core/exec/numerics.html#op-fnearest
-                let arg = self.value_store.pop_f64()?;
-                let rounded = arg.round(); // "Rounds half-way cases away from 0.0"
-                let frac = arg - rounded;
-                let result = if frac == 0.5 || frac == -0.5 {
-                    let rounded_half = rounded / 2.0;
-                    let is_rounded_even = rounded_half.trunc() == rounded_half;
-                    if is_rounded_even {
-                        rounded
-                    } else if rounded < arg {
-                        rounded + 1.0
-                    } else {
-                        rounded - 1.0
-                    }
-                } else {
-                    rounded
-                };
-                self.value_store.push(Value::F64(result));
-            }
-            F64SQRT => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg.sqrt()));
-            }
-            F64ADD => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg1 + arg2));
-            }
-            F64SUB => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg1 - arg2));
-            }
-            F64MUL => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg1 * arg2));
-            }
-            F64DIV => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F64(arg1 / arg2));
-            }
-            F64MIN => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result = if arg1 < arg2 { arg1 } else { arg2 };
-                self.value_store.push(Value::F64(result));
-            }
-            F64MAX => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result = if arg1 > arg2 { arg1 } else { arg2 };
-                self.value_store.push(Value::F64(result));
-            }
-            F64COPYSIGN => {
-                let arg2 = self.value_store.pop_f64()?;
-                let arg1 = self.value_store.pop_f64()?;
-                let result = if arg1.is_sign_negative() == arg2.is_sign_negative() {
-                    arg1
-                } else {
-                    arg2
-                };
-                self.value_store.push(Value::F64(result));
-            }
+    fn op_i64reinterpretf64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_f64()?;
+        self.value_store
+            .push(Value::I64(i64::from_ne_bytes(x.to_ne_bytes())));
+        Ok(Action::Continue)
+    }
 
-            I32WRAPI64 => {
-                let arg = self.value_store.pop_u64()?;
-                let wrapped: u32 = (arg & 0xffff_ffff) as u32;
-                self.value_store.push(Value::from(wrapped));
-            }
-            I32TRUNCSF32 => {
-                let arg = self.value_store.pop_f32()?;
-                if arg < i32::MIN as f32 || arg > i32::MAX as f32 {
-                    panic!("Cannot truncate {arg} from F32 to I32");
-                }
-                self.value_store.push(Value::I32(arg as i32));
-            }
-            I32TRUNCUF32 => {
-                let arg = self.value_store.pop_f32()?;
-                if arg < u32::MIN as f32 || arg > u32::MAX as f32 {
-                    panic!("Cannot truncate {arg} from F32 to unsigned I32");
-                }
-                self.value_store.push(Value::from(arg as u32));
-            }
-            I32TRUNCSF64 => {
-                let arg = self.value_store.pop_f64()?;
-                if arg < i32::MIN as f64 || arg > i32::MAX as f64 {
-                    panic!("Cannot truncate {arg} from F64 to I32");
-                }
-                self.value_store.push(Value::I32(arg as i32));
-            }
-            I32TRUNCUF64 => {
-                let arg = self.value_store.pop_f64()?;
-                if arg < u32::MIN as f64 || arg > u32::MAX as f64 {
-                    panic!("Cannot truncate {arg} from F64 to unsigned I32");
-                }
-                self.value_store.push(Value::from(arg as u32));
-            }
-            I64EXTENDSI32 => {
-                let arg = self.value_store.pop_i32()?;
-                self.value_store.push(Value::I64(arg as i64));
-            }
-            I64EXTENDUI32 => {
-                let arg = self.value_store.pop_u32()?;
-                self.value_store.push(Value::from(arg as u64));
-            }
-            I64TRUNCSF32 => {
-                let arg = self.value_store.pop_f32()?;
-                if arg < i64::MIN as f32 || arg > i64::MAX as f32 {
-                    panic!("Cannot truncate {arg} from F32 to I64");
-                }
-                self.value_store.push(Value::I64(arg as i64));
-            }
-            I64TRUNCUF32 => {
-                let arg = self.value_store.pop_f32()?;
-                if arg < u64::MIN as f32 || arg > u64::MAX as f32 {
-                    panic!("Cannot truncate {arg} from F32 to unsigned I64");
-                }
-                self.value_store.push(Value::from(arg as u64));
-            }
-            I64TRUNCSF64 => {
-                let arg = self.value_store.pop_f64()?;
-                if arg < i64::MIN as f64 || arg > i64::MAX as f64 {
-                    panic!("Cannot truncate {arg} from F64 to I64");
-                }
-                self.value_store.push(Value::I64(arg as i64));
-            }
-            I64TRUNCUF64 => {
-                let arg = self.value_store.pop_f64()?;
-                if arg < u64::MIN as f64 || arg > u64::MAX as f64 {
-                    panic!("Cannot truncate {arg} from F64 to unsigned I64");
-                }
-                self.value_store.push(Value::from(arg as u64));
-            }
-            F32CONVERTSI32 => {
-                let arg = self.value_store.pop_i32()?;
-                self.value_store.push(Value::F32(arg as f32));
-            }
-            F32CONVERTUI32 => {
-                let arg = self.value_store.pop_u32()?;
-                self.value_store.push(Value::F32(arg as f32));
-            }
-            F32CONVERTSI64 => {
-                let arg = self.value_store.pop_i64()?;
-                self.value_store.push(Value::F32(arg as f32));
-            }
-            F32CONVERTUI64 => {
-                let arg = self.value_store.pop_u64()?;
-                self.value_store.push(Value::F32(arg as f32));
-            }
-            F32DEMOTEF64 => {
-                let arg = self.value_store.pop_f64()?;
-                self.value_store.push(Value::F32(arg as f32));
-            }
-            F64CONVERTSI32 => {
-                let arg = self.value_store.pop_i32()?;
-                self.value_store.push(Value::F64(arg as f64));
-            }
-            F64CONVERTUI32 => {
-                let arg = self.value_store.pop_u32()?;
-                self.value_store.push(Value::F64(arg as f64));
-            }
-            F64CONVERTSI64 => {
-                let arg = self.value_store.pop_i64()?;
-                self.value_store.push(Value::F64(arg as f64));
-            }
-            F64CONVERTUI64 => {
-                let arg = self.value_store.pop_u64()?;
-                self.value_store.push(Value::F64(arg as f64));
-            }
-            F64PROMOTEF32 => {
-                let arg = self.value_store.pop_f32()?;
-                self.value_store.push(Value::F64(arg as f64));
-            }
+    fn op_f32reinterpreti32(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i32()?;
+        self.value_store
+            .push(Value::F32(f32::from_ne_bytes(x.to_ne_bytes())));
+        Ok(Action::Continue)
+    }
 
-            I32REINTERPRETF32 => {
-                let x = self.value_store.pop_f32()?;
-                self.value_store
-                    .push(Value::I32(i32::from_ne_bytes(x.to_ne_bytes())));
-            }
-            I64REINTERPRETF64 => {
-                let x = self.value_store.pop_f64()?;
-                self.value_store
-                    .push(Value::I64(i64::from_ne_bytes(x.to_ne_bytes())));
-            }
-            F32REINTERPRETI32 => {
-                let x = self.value_store.pop_i32()?;
-                self.value_store
-                    .push(Value::F32(f32::from_ne_bytes(x.to_ne_bytes())));
-            }
-            F64REINTERPRETI64 => {
-                let x = self.value_store.pop_i64()?;
-                self.value_store
-                    .push(Value::F64(f64::from_ne_bytes(x.to_ne_bytes())));
-            }
-            I32EXTEND8S => {
-                let x = self.value_store.pop_i32()?;
-                self.value_store.push(Value::I32(x as i8 as i32));
-            }
-            I32EXTEND16S => {
-                let x = self.value_store.pop_i32()?;
-                self.value_store.push(Value::I32(x as i16 as i32));
-            }
-            I64EXTEND8S => {
-                let x = self.value_store.pop_i64()?;
-                self.value_store.push(Value::I64(x as i8 as i64));
-            }
-            I64EXTEND16S => {
-                let x = self.value_store.pop_i64()?;
-                self.value_store.push(Value::I64(x as i16 as i64));
-            }
-            I64EXTEND32S => {
-                let x = self.value_store.pop_i64()?;
-                self.value_store.push(Value::I64(x as i32 as i64));
-            }
-        }
+    fn op_f64reinterpreti64(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i64()?;
+        self.value_store
+            .push(Value::F64(f64::from_ne_bytes(x.to_ne_bytes())));
+        Ok(Action::Continue)
+    }
 
-        if let Some(debug_string) = &self.debug_string {
-            if matches!(op_code, CALL | CALLINDIRECT) {
-                eprintln!("\n{file_offset:06x} {debug_string}");
-            } else {
-                // For calls, we print special debug stuff in do_call
-                let base = self.current_frame.locals_start + self.current_frame.locals_count;
-                let slice = self.value_store.get_slice(base);
-                eprintln!("{file_offset:06x} {debug_string:17} {slice:x?}");
-            }
-            let is_return = op_code == RETURN || (op_code == END && implicit_return);
-            let is_program_end = self.program_counter == 0;
-            if is_return && !is_program_end {
-                eprintln!(
-                    "returning to function {} at {:06x}",
-                    self.current_frame.fn_index,
-                    self.program_counter + self.module.code.section_offset as usize,
-                );
-            }
-        }
+    fn op_i32extend8s(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i32()?;
+        self.value_store.push(Value::I32(x as i8 as i32));
+        Ok(Action::Continue)
+    }
 
-        Ok(action)
+    fn op_i32extend16s(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i32()?;
+        self.value_store.push(Value::I32(x as i16 as i32));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64extend8s(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i64()?;
+        self.value_store.push(Value::I64(x as i8 as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64extend16s(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i64()?;
+        self.value_store.push(Value::I64(x as i16 as i64));
+        Ok(Action::Continue)
+    }
+
+    fn op_i64extend32s(&mut self, _module: &WasmModule<'a>) -> Result<Action, Error> {
+        let x = self.value_store.pop_i64()?;
+        self.value_store.push(Value::I64(x as i32 as i64));
+        Ok(Action::Continue)
     }
 
     #[allow(dead_code)]
@@ -1783,7 +3380,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
         let mut print_blocks = |i| {
             block_str.clear();
-            while let Some((b, Block { vstack, ty })) = block {
+            while let Some((b, Block { vstack, ty, .. })) = block {
                 if *vstack > i {
                     break;
                 }
@@ -1818,18 +3415,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
         let frames = self.previous_frames.iter().chain(once(&self.current_frame));
         let next_frames = frames.clone().skip(1);
-
-        // Find the code address to display for each frame
-        // For previous frames, show the address of the CALL instruction
-        // For the current frame, show the program counter value
-        let mut execution_addrs = {
-            // for each previous_frame, find return address of the *next* frame
-            let return_addrs = next_frames.clone().map(|f| f.return_addr);
-            // roll back to the CALL instruction before that return address, it's more meaningful.
-            let call_addrs = return_addrs.map(|ra| self.debug_return_addr_to_call_addr(ra));
-            // For the current frame, show the program_counter
-            call_addrs.chain(once(self.program_counter))
-        };
+        let mut execution_addrs = self.debug_execution_addrs().into_iter();
 
         let mut frame_ends = next_frames.map(|f| f.locals_start);
 
@@ -1843,8 +3429,13 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
             let arg_count = {
                 let signature_index = if *fn_index < self.import_count {
-                    match self.module.import.imports[*fn_index].description {
-                        ImportDesc::Func { signature_index } => signature_index,
+                    match self
+                        .module
+                        .import
+                        .function(*fn_index)
+                        .map(|imp| &imp.description)
+                    {
+                        Some(ImportDesc::Func { signature_index }) => *signature_index,
                         _ => unreachable!(),
                     }
                 } else {
@@ -1853,14 +3444,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 self.module.types.look_up(signature_index).0.len()
             };
 
-            let fn_name = self
-                .module
-                .names
-                .function_names
-                .iter()
-                .find(|(idx, _)| *idx == *fn_index as u32)
-                .map(|(_, name)| *name)
-                .unwrap_or("");
+            let fn_name = self.debug_fn_name(*fn_index);
 
             // Function and address match wasm-objdump formatting, for easy copy & find
             writeln!(buffer, "func[{fn_index}]  {fn_name}")?;
@@ -1868,13 +3452,16 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
 
             write!(buffer, "  args     ")?;
             for local_index in 0..*locals_count {
-                let value = self.value_store.get(locals_start + local_index).unwrap();
                 if local_index == arg_count {
                     write!(buffer, "\n  locals   ")?;
                 } else if local_index != 0 {
                     write!(buffer, ", ")?;
                 }
-                write!(buffer, "{local_index}: {value:?}")?;
+                // Invalid code can pop values off the stack that belong to a local
+                match self.value_store.get(locals_start + local_index) {
+                    Some(value) => write!(buffer, "{local_index}: {value:?}")?,
+                    None => write!(buffer, "{local_index}: (popped)")?,
+                }
             }
 
             write!(buffer, "\n  stack    [")?;
@@ -1882,7 +3469,7 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
                 .next()
                 .unwrap_or_else(|| self.value_store.depth());
             let stack_start = locals_start + locals_count;
-            for i in stack_start..frame_end {
+            for i in stack_start..frame_end.min(self.value_store.depth()) {
                 let value = self.value_store.get(i).unwrap();
                 if i != stack_start {
                     write!(buffer, ", ")?;
@@ -1896,6 +3483,45 @@ impl<'a, I: ImportDispatcher> Instance<'a, I> {
         Ok(())
     }
 
+    /// The functions on the call stack, outermost first, for a [TrapHandler]
+    fn frame_infos(&self) -> std::vec::Vec<FrameInfo<'a>> {
+        let frames = self.previous_frames.iter().chain(once(&self.current_frame));
+        let section_offset = self.module.code.section_offset as usize;
+        frames
+            .zip(self.debug_execution_addrs())
+            .map(|(frame, addr)| FrameInfo {
+                fn_index: frame.fn_index,
+                fn_name: self.debug_fn_name(frame.fn_index),
+                file_offset: addr + section_offset,
+            })
+            .collect()
+    }
+
+    // Find the code address to display for each frame
+    // For previous frames, show the address of the CALL instruction
+    // For the current frame, show the program counter value
+    fn debug_execution_addrs(&self) -> std::vec::Vec<usize> {
+        // for each previous_frame, find return address of the *next* frame
+        let frames = self.previous_frames.iter().chain(once(&self.current_frame));
+        let next_frames = frames.skip(1);
+        let return_addrs = next_frames.map(|f| f.return_addr);
+        // roll back to the CALL instruction before that return address, it's more meaningful.
+        let call_addrs = return_addrs.map(|ra| self.debug_return_addr_to_call_addr(ra));
+        // For the current frame, show the program_counter
+        call_addrs.chain(once(self.program_counter)).collect()
+    }
+
+    /// A function's name from the name section, or an empty string if it has none
+    fn debug_fn_name(&self, fn_index: usize) -> &'a str {
+        self.module
+            .names
+            .function_names
+            .iter()
+            .find(|(idx, _)| *idx == fn_index as u32)
+            .map(|(_, name)| *name)
+            .unwrap_or("")
+    }
+
     // Call address is more intuitive than the return address in the stack trace. Search backward for it.
     fn debug_return_addr_to_call_addr(&self, return_addr: usize) -> usize {
         // return_addr is pointing at the next instruction after the CALL/CALLINDIRECT.
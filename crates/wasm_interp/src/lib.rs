@@ -1,17 +1,24 @@
+mod conversions;
+mod float;
 mod frame;
 mod instance;
 #[cfg(test)]
 mod tests;
 
+mod strings;
 mod value_store;
 pub mod wasi;
 
 // Main external interface
-pub use instance::Instance;
-pub use wasi::{WasiDispatcher, WasiFile};
+pub use conversions::{FromValues, IntoValues};
+pub use frame::FrameInfo;
+pub use instance::{Instance, RunProgress, TrapHandler};
+pub use strings::{read_cstr, read_utf8, StringError};
+pub use wasi::{WasiDispatcher, WasiFile, WasiResult};
 
+use bumpalo::{collections::Vec, Bump};
 pub use roc_wasm_module::Value;
-use roc_wasm_module::ValueType;
+use roc_wasm_module::{parse::ParseError, sections::ImportDesc, ValueType, WasmModule};
 
 pub trait ImportDispatcher {
     /// Dispatch a call from WebAssembly to your own code, based on module and function name.
@@ -22,24 +29,96 @@ pub trait ImportDispatcher {
         arguments: &[Value],
         memory: &mut [u8],
     ) -> Option<Value>;
+
+    /// Whether `dispatch` implements this import. [ChainedDispatcher] asks each of its dispatchers
+    /// in turn, and calls the first one that does. A dispatcher that handles everything can keep the default.
+    fn handles(&self, _module_name: &str, _function_name: &str) -> bool {
+        true
+    }
+
+    /// The value of an imported global, like the `__memory_base` that position-independent code
+    /// adds to its data addresses. It's read once at instantiation, and again on [Instance::reset].
+    fn global(&self, _module_name: &str, _global_name: &str) -> Option<Value> {
+        None
+    }
+
+    /// The exit code, once a call has asked the program to stop, like WASI `proc_exit` does.
+    /// The interpreter checks this after every call to an import, and stops if it's `Some`.
+    fn exit_code(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Run a WASI program's `_start` function, and return its exit code.
+/// That's the code it passed to `proc_exit`, or 0 if it returned normally.
+/// The program's stdout and stderr are the real ones. WASI programs expect `args[0]` to be the program name.
+pub fn run_wasi<'a>(module: &'a WasmModule<'a>, args: &[&str]) -> Result<i32, Error> {
+    let arena = Bump::new();
+    let args = Vec::from_iter_in(args.iter().map(|arg| arg.as_bytes()), &arena);
+    let dispatcher = DefaultImportDispatcher::new(&args);
+
+    // Unsupported imports would panic, either in DefaultImportDispatcher or while instantiating
+    for import in module.import.imports.iter() {
+        let is_supported = match import.description {
+            ImportDesc::Func { .. } => dispatcher.handles(import.module, import.name),
+            ImportDesc::Global { .. } => true,
+            ImportDesc::Mem { .. } | ImportDesc::Table { .. } => false,
+        };
+        if !is_supported {
+            return Err(Error::UnsupportedImport {
+                module: import.module.to_string(),
+                name: import.name.to_string(),
+            });
+        }
+    }
+
+    let mut inst =
+        Instance::for_module(&arena, module, dispatcher, false).map_err(Error::Instantiation)?;
+    inst.start_export("_start", [])?;
+    inst.finish_export()?;
+    Ok(inst.import_dispatcher.exit_code.unwrap_or(0))
 }
 
 impl Default for DefaultImportDispatcher<'_> {
     fn default() -> Self {
         DefaultImportDispatcher {
             wasi: WasiDispatcher::new(&[]),
+            exit_code: None,
         }
     }
 }
 
 pub struct DefaultImportDispatcher<'a> {
     pub wasi: WasiDispatcher<'a>,
+    /// Set when the program calls `proc_exit`, which stops the interpreter rather than the host process
+    exit_code: Option<i32>,
 }
 
 impl<'a> DefaultImportDispatcher<'a> {
     pub fn new(args: &'a [&'a [u8]]) -> Self {
         DefaultImportDispatcher {
             wasi: WasiDispatcher::new(args),
+            exit_code: None,
+        }
+    }
+
+    /// Configure everything the program can see of its environment.
+    /// Pass [WasiFile::HostSystemFile] to use the real stdout or stderr, or [WasiFile::WriteOnly]
+    /// to capture the output, then read it back from `wasi.files[1]` or `wasi.files[2]`.
+    /// Environment variables are in `KEY=value` form.
+    pub fn with_config(
+        args: &'a [&'a [u8]],
+        stdout: WasiFile,
+        stderr: WasiFile,
+        env: &'a [&'a [u8]],
+    ) -> Self {
+        let mut wasi = WasiDispatcher::new(args);
+        wasi.files[1] = stdout;
+        wasi.files[2] = stderr;
+        wasi.env = env;
+        DefaultImportDispatcher {
+            wasi,
+            exit_code: None,
         }
     }
 }
@@ -53,26 +132,173 @@ impl<'a> ImportDispatcher for DefaultImportDispatcher<'a> {
         memory: &mut [u8],
     ) -> Option<Value> {
         if module_name == wasi::MODULE_NAME {
-            self.wasi.dispatch(function_name, arguments, memory)
+            match self.wasi.dispatch(function_name, arguments, memory) {
+                WasiResult::Return(value) => value,
+                WasiResult::Exit(exit_code) => {
+                    self.exit_code = Some(exit_code);
+                    None
+                }
+            }
         } else {
             panic!("DefaultImportDispatcher does not implement {module_name}.{function_name}");
         }
     }
+
+    fn handles(&self, module_name: &str, _function_name: &str) -> bool {
+        module_name == wasi::MODULE_NAME
+    }
+
+    fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+}
+
+/// Combine several dispatchers, for example one for each host module.
+/// Each import goes to the first dispatcher that [handles](ImportDispatcher::handles) it.
+#[derive(Default)]
+pub struct ChainedDispatcher<'a> {
+    dispatchers: std::vec::Vec<Box<dyn ImportDispatcher + 'a>>,
+}
+
+impl<'a> ChainedDispatcher<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a dispatcher, to be tried after all the ones added before it
+    pub fn with<D: ImportDispatcher + 'a>(mut self, dispatcher: D) -> Self {
+        self.dispatchers.push(Box::new(dispatcher));
+        self
+    }
+}
+
+impl<'a> ImportDispatcher for ChainedDispatcher<'a> {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut [u8],
+    ) -> Option<Value> {
+        match self
+            .dispatchers
+            .iter_mut()
+            .find(|d| d.handles(module_name, function_name))
+        {
+            Some(dispatcher) => dispatcher.dispatch(module_name, function_name, arguments, memory),
+            None => panic!("ChainedDispatcher has no dispatcher for {module_name}.{function_name}"),
+        }
+    }
+
+    fn handles(&self, module_name: &str, function_name: &str) -> bool {
+        self.dispatchers
+            .iter()
+            .any(|d| d.handles(module_name, function_name))
+    }
+
+    fn global(&self, module_name: &str, global_name: &str) -> Option<Value> {
+        self.dispatchers
+            .iter()
+            .find_map(|d| d.global(module_name, global_name))
+    }
+
+    fn exit_code(&self) -> Option<i32> {
+        self.dispatchers.iter().find_map(|d| d.exit_code())
+    }
+}
+
+/// Lets an [Instance] own a dispatcher whose type is only known at runtime. See [Instance::with_imports].
+impl<D: ImportDispatcher + ?Sized> ImportDispatcher for Box<D> {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut [u8],
+    ) -> Option<Value> {
+        (**self).dispatch(module_name, function_name, arguments, memory)
+    }
+
+    fn handles(&self, module_name: &str, function_name: &str) -> bool {
+        (**self).handles(module_name, function_name)
+    }
+
+    fn global(&self, module_name: &str, global_name: &str) -> Option<Value> {
+        (**self).global(module_name, global_name)
+    }
+
+    fn exit_code(&self) -> Option<i32> {
+        (**self).exit_code()
+    }
 }
 
 /// Errors that can happen while interpreting the program
-/// All of these cause a WebAssembly stack trace to be dumped
+/// All of these cause a WebAssembly stack trace to be dumped, unless there's a [TrapHandler]
 #[derive(Debug, PartialEq)]
-pub(crate) enum Error {
+pub enum Error {
+    /// The Wasm program did something the spec says must stop execution
+    Trap(TrapKind),
+    // The rest are invalid Wasm that a validator would have rejected, or interpreter limitations
     Type(ValueType, ValueType),
     StackEmpty,
-    MemoryAccessOutOfBounds(u32, u32),
-    UnreachableOp,
+    ImmutableGlobal(u32),
+    AtomicWaitForever,
+    /// `memory.grow` would take memory beyond the host's [total budget](Instance::set_total_memory_budget), in bytes
+    MemoryBudgetExhausted(u64),
+    /// Code that doesn't decode as a supported instruction, or refers to something that doesn't exist
+    InvalidCode(String),
+    /// [Instance::start_export] was asked for a function the module doesn't export
+    ExportNotFound(String),
+    /// An argument to [Instance::start_export] doesn't have the type of the parameter at `index`
+    ArgumentTypeMismatch {
+        index: usize,
+        expected: ValueType,
+        actual: ValueType,
+    },
+    /// [Instance::start_export] got a different number of arguments than the function has parameters
+    ArgumentCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// The module couldn't be instantiated, for example because an imported global is missing
+    Instantiation(String),
+    /// The module imports a function that the [ImportDispatcher] doesn't [handle](ImportDispatcher::handles),
+    /// or a memory or table, which this interpreter doesn't support
+    UnsupportedImport {
+        module: String,
+        name: String,
+    },
+    /// An [ImportDispatcher] returned a different number of values than the import's signature has results
+    ImportResultArity {
+        module: String,
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// The kinds of trap defined by the Wasm spec
+#[derive(Debug, PartialEq)]
+pub enum TrapKind {
+    Unreachable,
+    IntegerDivideByZero,
+    /// Signed division overflow, or any overflow in `trap_on_overflow` mode
+    IntegerOverflow,
+    /// Float to integer truncation of NaN, infinity, or a number outside the integer's range
+    InvalidConversionToInteger,
+    MemoryOutOfBounds(u32, u32),
+    DataSegmentOutOfBounds(u32, u32, u32),
+    UnalignedAtomicAccess(u32, u32),
+    /// `call_indirect` to an element index that isn't in the table
+    UndefinedElement(u32),
+    /// `call_indirect` to a function whose signature index isn't the expected one
+    CallIndirectTypeMismatch(u32, u32),
 }
 
 impl Error {
     pub fn to_string_at(&self, file_offset: usize) -> String {
         match self {
+            Error::Trap(kind) => kind.to_string_at(file_offset),
             Error::Type(expected, actual) => {
                 format!(
                     "ERROR: I found a type mismatch at file offset {file_offset:#x}. Expected {expected:?}, but found {actual:?}.\n"
@@ -83,19 +309,112 @@ impl Error {
                     "ERROR: I tried to pop a value from the stack at file offset {file_offset:#x}, but it was empty.\n"
                 )
             }
-            Error::MemoryAccessOutOfBounds(addr, memory_size) => {
+            Error::ImmutableGlobal(index) => {
+                format!(
+                    "ERROR: A Wasm instruction at file offset {file_offset:#x} tried to set immutable global {index}.\n"
+                )
+            }
+            Error::AtomicWaitForever => {
+                format!(
+                    "ERROR: A Wasm instruction at file offset {file_offset:#x} tried to wait forever, but there are no other threads to wake it up.\n"
+                )
+            }
+            Error::MemoryBudgetExhausted(budget) => {
                 format!(
-                    "ERROR: A Wasm instruction at file offset {:#x} tried to access memory at {:#x} but the maximum address is {:#x}\n",
-                    file_offset, addr, memory_size-1
+                    "ERROR: A Wasm instruction at file offset {file_offset:#x} tried to grow memory, but that would exceed the total memory budget of {budget:#x} bytes.\n"
                 )
             }
-            Error::UnreachableOp => {
+            Error::InvalidCode(message) => {
+                format!(
+                    "ERROR: I couldn't execute the Wasm code at file offset {file_offset:#x}. {message}.\n"
+                )
+            }
+            Error::ExportNotFound(fn_name) => {
+                format!("I couldn't find a function '{fn_name}' in this WebAssembly module")
+            }
+            Error::ArgumentTypeMismatch {
+                index,
+                expected,
+                actual,
+            } => {
+                format!(
+                    "Type mismatch on argument {index}. Expected {expected:?} but got {actual:?}"
+                )
+            }
+            Error::ArgumentCount { expected, actual } => {
+                format!("Expected {expected} arguments but got {actual}")
+            }
+            Error::Instantiation(message) => message.clone(),
+            Error::UnsupportedImport { module, name } => {
+                format!("ERROR: The module imports {module}.{name}, which isn't supported here.\n")
+            }
+            Error::ImportResultArity {
+                module,
+                function,
+                expected,
+                actual,
+            } => {
+                format!(
+                    "ERROR: The import {module}.{function}, called at file offset {file_offset:#x}, returned {actual} values but its signature has {expected} results.\n"
+                )
+            }
+        }
+    }
+}
+
+impl TrapKind {
+    pub fn to_string_at(&self, file_offset: usize) -> String {
+        match self {
+            TrapKind::Unreachable => {
                 format!("WebAssembly `unreachable` instruction at file offset {file_offset:#x}.\n")
             }
+            TrapKind::IntegerDivideByZero => {
+                format!("WebAssembly integer divide by zero at file offset {file_offset:#x}.\n")
+            }
+            TrapKind::IntegerOverflow => {
+                format!("WebAssembly integer overflow at file offset {file_offset:#x}.\n")
+            }
+            TrapKind::InvalidConversionToInteger => {
+                format!(
+                    "WebAssembly invalid conversion to integer at file offset {file_offset:#x}.\n"
+                )
+            }
+            TrapKind::MemoryOutOfBounds(addr, memory_size) => {
+                format!(
+                    "ERROR: A Wasm instruction at file offset {file_offset:#x} tried to access memory at {addr:#x} but memory is {memory_size:#x} bytes\n"
+                )
+            }
+            TrapKind::DataSegmentOutOfBounds(segment_index, end, segment_size) => {
+                format!(
+                    "ERROR: A Wasm instruction at file offset {:#x} tried to read data segment {} up to offset {:#x} but its size is {:#x} (dropped segments have size zero)\n",
+                    file_offset, segment_index, end, segment_size
+                )
+            }
+            TrapKind::UnalignedAtomicAccess(addr, size) => {
+                format!(
+                    "ERROR: An atomic Wasm instruction at file offset {file_offset:#x} tried to access memory at {addr:#x}, which is not aligned to {size} bytes.\n"
+                )
+            }
+            TrapKind::UndefinedElement(element_index) => {
+                format!(
+                    "ERROR: Indirect function call at file offset {file_offset:#x} failed. There is no function with element index {element_index}.\n"
+                )
+            }
+            TrapKind::CallIndirectTypeMismatch(expected, actual) => {
+                format!(
+                    "ERROR: Indirect function call at file offset {file_offset:#x} failed. Expected signature {expected} but found {actual}.\n"
+                )
+            }
         }
     }
 }
 
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::InvalidCode(e.message)
+    }
+}
+
 impl From<(ValueType, ValueType)> for Error {
     fn from((expected, actual): (ValueType, ValueType)) -> Self {
         Error::Type(expected, actual)
@@ -6,7 +6,7 @@ use std::io;
 use std::iter::once;
 use std::process;
 
-use roc_wasm_interp::{DefaultImportDispatcher, Instance};
+use roc_wasm_interp::{DefaultImportDispatcher, ImportDispatcher, Instance};
 use roc_wasm_module::WasmModule;
 
 pub const FLAG_FUNCTION: &str = "function";
@@ -98,6 +98,11 @@ fn main() -> io::Result<()> {
 
     let result = inst.call_export_from_cli(&module, start_fn_name, &wasi_argv);
 
+    // The program called WASI `proc_exit`
+    if let Some(exit_code) = inst.import_dispatcher.exit_code() {
+        process::exit(exit_code);
+    }
+
     // Print out return value, if any
 
     match result {
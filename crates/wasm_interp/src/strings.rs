@@ -0,0 +1,49 @@
+use std::fmt;
+use std::str::Utf8Error;
+
+/// Why a string couldn't be read from Wasm memory
+#[derive(Debug, PartialEq, Eq)]
+pub enum StringError {
+    /// The string, or its zero terminator, is not inside the memory
+    OutOfBounds {
+        addr: u32,
+        len: u32,
+    },
+    InvalidUtf8(Utf8Error),
+}
+
+impl fmt::Display for StringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StringError::OutOfBounds { addr, len } => write!(
+                f,
+                "String at {addr:#x} with length {len:#x} is outside of Wasm memory"
+            ),
+            StringError::InvalidUtf8(e) => write!(f, "String in Wasm memory is not UTF-8: {e}"),
+        }
+    }
+}
+
+/// Read a string of `len` bytes at `addr`, like the pointer and length a Wasm program passes to an import.
+pub fn read_utf8(memory: &[u8], addr: u32, len: u32) -> Result<&str, StringError> {
+    let bytes = (addr as usize)
+        .checked_add(len as usize)
+        .and_then(|end| memory.get(addr as usize..end))
+        .ok_or(StringError::OutOfBounds { addr, len })?;
+    std::str::from_utf8(bytes).map_err(StringError::InvalidUtf8)
+}
+
+/// Read a zero-terminated C string at `addr`. The terminator is not included in the result.
+pub fn read_cstr(memory: &[u8], addr: u32) -> Result<&str, StringError> {
+    let rest = memory
+        .get(addr as usize..)
+        .ok_or(StringError::OutOfBounds { addr, len: 0 })?;
+    let len = rest
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(StringError::OutOfBounds {
+            addr,
+            len: rest.len() as u32,
+        })?;
+    std::str::from_utf8(&rest[..len]).map_err(StringError::InvalidUtf8)
+}
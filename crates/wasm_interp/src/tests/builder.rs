@@ -0,0 +1,357 @@
+//! A small builder for assembling Wasm modules in tests, without writing raw bytes by hand.
+//! It's not an assembler, just enough to cover the instructions the interpreter supports.
+
+use bumpalo::{collections::Vec, Bump};
+use roc_wasm_module::{
+    opcodes::{AtomicInstruction, OpCode, MEMARG_MEMORY_INDEX_FLAG},
+    sections::{DataMode, DataSegment, Global, GlobalType, Import, ImportDesc, Limits},
+    ConstExpr, Export, ExportType, SerialBuffer, Serialize, Signature, Value, ValueType,
+    WasmModule,
+};
+
+use super::const_value;
+
+/// One instruction in a function body
+#[derive(Debug, Clone, Copy)]
+pub enum Instr {
+    /// An instruction with no immediates, like `i32.add` or `end`
+    Op(OpCode),
+    /// A constant of any type
+    Const(Value),
+    /// An instruction with a single u32 immediate, like `local.get`, `global.set`, `call` or `br`.
+    /// Also `memory.size` and `memory.grow`, whose immediate is the memory index.
+    Index(OpCode, u32),
+    /// `call_indirect` or `return_call_indirect` with the given signature index, in table 0
+    CallIndirect(OpCode, u32),
+    /// A load or store with the given memory offset. Alignment is always 0.
+    Mem(OpCode, u32),
+    /// A load or store in the given memory index, with the given offset
+    MemAt(OpCode, u32, u32),
+    /// `block`, `loop` or `if`, with an optional result type
+    Block(OpCode, Option<ValueType>),
+    /// `block`, `loop` or `if`, whose params and results are the signature at this type index
+    BlockSig(OpCode, u32),
+    /// An atomic memory instruction with the given memory offset. Alignment is always 0.
+    Atomic(AtomicInstruction, u32),
+}
+
+impl Instr {
+    fn encode(&self, buf: &mut Vec<'_, u8>) {
+        match *self {
+            Instr::Op(op) => buf.push(op as u8),
+            Instr::Const(value) => const_value(buf, value),
+            Instr::Index(op, index) => {
+                buf.push(op as u8);
+                buf.encode_u32(index);
+            }
+            Instr::CallIndirect(op, signature_index) => {
+                buf.push(op as u8);
+                buf.encode_u32(signature_index);
+                buf.encode_u32(0);
+            }
+            Instr::Mem(op, offset) => {
+                buf.push(op as u8);
+                buf.encode_u32(0);
+                buf.encode_u32(offset);
+            }
+            Instr::MemAt(op, memory_index, offset) => {
+                buf.push(op as u8);
+                buf.encode_u32(MEMARG_MEMORY_INDEX_FLAG);
+                buf.encode_u32(memory_index);
+                buf.encode_u32(offset);
+            }
+            Instr::Block(op, result) => {
+                buf.push(op as u8);
+                buf.push(result.map_or(ValueType::VOID, |ty| ty as u8));
+            }
+            Instr::BlockSig(op, signature_index) => {
+                buf.push(op as u8);
+                buf.encode_i64(signature_index as i64);
+            }
+            Instr::Atomic(op, offset) => {
+                buf.push(OpCode::ATOMIC as u8);
+                buf.push(op as u8);
+                buf.encode_u32(0);
+                buf.encode_u32(offset);
+            }
+        }
+    }
+}
+
+pub struct ModuleBuilder<'a> {
+    arena: &'a Bump,
+    module: WasmModule<'a>,
+}
+
+impl<'a> ModuleBuilder<'a> {
+    pub fn new(arena: &'a Bump) -> Self {
+        ModuleBuilder {
+            arena,
+            module: WasmModule::new(arena),
+        }
+    }
+
+    /// Add a memory and return its index
+    pub fn memory(&mut self, pages: u32) -> u32 {
+        self.module.memory.append(Limits::Min(pages))
+    }
+
+    /// Add a signature to the type section and return its index, for [Instr::BlockSig]
+    pub fn signature(&mut self, param_types: &[ValueType], ret_type: Option<ValueType>) -> u32 {
+        self.module.types.insert(Signature {
+            param_types: Vec::from_iter_in(param_types.iter().copied(), self.arena),
+            ret_type,
+        })
+    }
+
+    /// Add a global and return its index
+    pub fn global(&mut self, initial_value: Value, is_mutable: bool) -> u32 {
+        let init = match initial_value {
+            Value::I32(x) => ConstExpr::I32(x),
+            Value::I64(x) => ConstExpr::I64(x),
+            Value::F32(x) => ConstExpr::F32(x),
+            Value::F64(x) => ConstExpr::F64(x),
+        };
+        self.module.global.append(Global {
+            ty: GlobalType {
+                value_type: ValueType::from(initial_value),
+                is_mutable,
+            },
+            init,
+        });
+        self.global_import_count() + self.module.global.count - 1
+    }
+
+    /// Add an imported global and return its index.
+    /// Imports come first in the global index space, so add them before any other globals.
+    pub fn import_global(
+        &mut self,
+        module_name: &'a str,
+        name: &'a str,
+        value_type: ValueType,
+        is_mutable: bool,
+    ) -> u32 {
+        assert_eq!(
+            self.module.global.count, 0,
+            "Imports must be added before other globals"
+        );
+        self.module.import.imports.push(Import {
+            module: module_name,
+            name,
+            description: ImportDesc::Global {
+                ty: GlobalType {
+                    value_type,
+                    is_mutable,
+                },
+            },
+        });
+        self.global_import_count() - 1
+    }
+
+    fn global_import_count(&self) -> u32 {
+        let imports = self.module.import.imports.iter();
+        imports
+            .filter(|imp| matches!(imp.description, ImportDesc::Global { .. }))
+            .count() as u32
+    }
+
+    /// Add an active data segment at a fixed memory address, and return its index
+    pub fn data(&mut self, address: u32, bytes: &[u8]) -> u32 {
+        self.data_in(0, address, bytes)
+    }
+
+    /// Add an active data segment at a fixed address in the given memory, and return its index
+    pub fn data_in(&mut self, memory_index: u32, address: u32, bytes: &[u8]) -> u32 {
+        self.module.data.append_segment(DataSegment {
+            mode: DataMode::active_in(memory_index, address),
+            init: Vec::from_iter_in(bytes.iter().copied(), self.arena),
+        })
+    }
+
+    /// Add an active data segment whose address is the value of a global, and return its index
+    pub fn data_at_global(&mut self, global_index: u32, bytes: &[u8]) -> u32 {
+        self.module.data.append_segment(DataSegment {
+            mode: DataMode::Active {
+                memory_index: 0,
+                offset: ConstExpr::GlobalGet(global_index),
+            },
+            init: Vec::from_iter_in(bytes.iter().copied(), self.arena),
+        })
+    }
+
+    /// Add an imported function and return its index.
+    /// Imports come first in the function index space, so add them before any other functions.
+    pub fn import_function(
+        &mut self,
+        module_name: &'a str,
+        name: &'a str,
+        param_types: &[ValueType],
+        ret_type: Option<ValueType>,
+    ) -> u32 {
+        let module = &mut self.module;
+        assert_eq!(
+            module.code.function_count, 0,
+            "Imports must be added before other functions"
+        );
+        let signature_index = module.types.insert(Signature {
+            param_types: Vec::from_iter_in(param_types.iter().copied(), self.arena),
+            ret_type,
+        });
+        module.import.imports.push(Import {
+            module: module_name,
+            name,
+            description: ImportDesc::Func { signature_index },
+        });
+        module.import.function_count() as u32 - 1
+    }
+
+    /// Add an exported function and return its index.
+    /// The body must include the final `end` instruction.
+    pub fn function(
+        &mut self,
+        name: &'a str,
+        param_types: &[ValueType],
+        ret_type: Option<ValueType>,
+        local_types: &[ValueType],
+        body: &[Instr],
+    ) -> u32 {
+        let module = &mut self.module;
+        let fn_index = (module.import.function_count() + module.code.function_offsets.len()) as u32;
+
+        module.export.append(Export {
+            name,
+            ty: ExportType::Func,
+            index: fn_index,
+        });
+        module.add_function_signature(Signature {
+            param_types: Vec::from_iter_in(param_types.iter().copied(), self.arena),
+            ret_type,
+        });
+
+        let buf = &mut module.code.bytes;
+        let offset = buf.encode_padded_u32(0);
+        let start = buf.len();
+        let locals: std::vec::Vec<(u32, ValueType)> =
+            local_types.iter().map(|ty| (1, *ty)).collect();
+        locals.as_slice().serialize(buf);
+        for instr in body {
+            instr.encode(buf);
+        }
+        buf.overwrite_padded_u32(offset, (buf.len() - start) as u32);
+
+        module.code.function_count += 1;
+        module.code.function_offsets.push(offset as u32);
+
+        fn_index
+    }
+
+    pub fn build(self) -> WasmModule<'a> {
+        self.module
+    }
+}
+
+#[test]
+fn test_builder_two_functions() {
+    use crate::{DefaultImportDispatcher, Instance};
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+
+    builder.memory(1);
+    builder.data(0x100, &[10, 0, 0, 0]);
+    let counter = builder.global(Value::I32(0), true);
+
+    let double = builder.function(
+        "double",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[Index(GETLOCAL, 0), Index(GETLOCAL, 0), Op(I32ADD), Op(END)],
+    );
+
+    // Load from memory, double it, add a constant, and count how many times we've run
+    builder.function(
+        "main",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[
+            Const(Value::I32(0x100)),
+            Mem(I32LOAD, 0),
+            Index(CALL, double),
+            Index(GETLOCAL, 0),
+            Op(I32ADD),
+            Index(SETLOCAL, 1),
+            Index(GETGLOBAL, counter),
+            Const(Value::I32(1)),
+            Op(I32ADD),
+            Index(SETGLOBAL, counter),
+            Index(GETLOCAL, 1),
+            Op(END),
+        ],
+    );
+
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.call_export("double", [Value::I32(21)]),
+        Ok(Some(Value::I32(42)))
+    );
+    assert_eq!(
+        inst.call_export("main", [Value::I32(1)]),
+        Ok(Some(Value::I32(21)))
+    );
+    assert_eq!(
+        inst.call_export("main", [Value::I32(2)]),
+        Ok(Some(Value::I32(22)))
+    );
+    assert_eq!(inst.globals[counter as usize], Value::I32(2));
+}
+
+#[test]
+fn test_builder_blocks() {
+    use crate::{DefaultImportDispatcher, Instance};
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+
+    // Absolute value, using an `if` to negate the argument in place
+    builder.function(
+        "abs",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Index(GETLOCAL, 0),
+            Const(Value::I32(0)),
+            Op(I32LTS),
+            Block(IF, None),
+            Const(Value::I32(0)),
+            Index(GETLOCAL, 0),
+            Op(I32SUB),
+            Index(SETLOCAL, 0),
+            Op(END),
+            Index(GETLOCAL, 0),
+            Op(END),
+        ],
+    );
+
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.call_export("abs", [Value::I32(-5)]),
+        Ok(Some(Value::I32(5)))
+    );
+    assert_eq!(
+        inst.call_export("abs", [Value::I32(7)]),
+        Ok(Some(Value::I32(7)))
+    );
+}
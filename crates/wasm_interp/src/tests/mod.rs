@@ -1,12 +1,15 @@
 #![cfg(test)]
 
+mod builder;
 mod test_basics;
 mod test_convert;
 mod test_f32;
 mod test_f64;
+mod test_fuzz;
 mod test_i32;
 mod test_i64;
 mod test_mem;
+mod test_wasi;
 
 use crate::{DefaultImportDispatcher, Instance};
 use bumpalo::{collections::Vec, Bump};
@@ -51,6 +54,25 @@ pub fn const_value(buf: &mut Vec<'_, u8>, value: Value) {
 }
 
 pub fn test_op_example<A>(op: OpCode, args: A, expected: Value)
+where
+    A: IntoIterator<Item = Value>,
+{
+    let return_val = run_op_example(op, args, ValueType::from(expected))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(return_val, expected);
+}
+
+/// Run an instruction that is expected to fail, and return the error message
+pub fn test_op_error<A>(op: OpCode, args: A, ret_type: ValueType) -> String
+where
+    A: IntoIterator<Item = Value>,
+{
+    run_op_example(op, args, ret_type).unwrap_err()
+}
+
+fn run_op_example<A>(op: OpCode, args: A, ret_type: ValueType) -> Result<Option<Value>, String>
 where
     A: IntoIterator<Item = Value>,
 {
@@ -74,7 +96,7 @@ where
         module.code.function_offsets.push(0);
         module.add_function_signature(Signature {
             param_types: Vec::new_in(&arena),
-            ret_type: Some(ValueType::from(expected)),
+            ret_type: Some(ret_type),
         });
         module.export.append(Export {
             name: "test",
@@ -95,9 +117,7 @@ where
     let mut inst =
         Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), true).unwrap();
 
-    let return_val = inst.call_export("test", []).unwrap().unwrap();
-
-    assert_eq!(return_val, expected);
+    inst.call_export("test", [])
 }
 
 pub fn create_exported_function_no_locals<'a, F>(
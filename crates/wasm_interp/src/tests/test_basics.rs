@@ -2,15 +2,20 @@
 
 use crate::frame::Frame;
 use crate::tests::{
+    builder::{Instr, ModuleBuilder},
     const_value, create_exported_function_no_locals, create_exported_function_with_locals,
     default_state,
 };
-use crate::{DefaultImportDispatcher, ImportDispatcher, Instance};
+use crate::{
+    DefaultImportDispatcher, Error, ImportDispatcher, Instance, IntoValues, RunProgress, TrapKind,
+};
 use bumpalo::{collections::Vec, Bump};
-use roc_wasm_module::sections::{Import, ImportDesc};
+use roc_wasm_module::sections::{
+    DataMode, DataSegment, Global, GlobalType, Import, ImportDesc, MemorySection, RefType,
+};
 use roc_wasm_module::{
-    opcodes::OpCode, sections::ElementSegment, Export, ExportType, SerialBuffer, Serialize,
-    Signature, Value, ValueType, WasmModule,
+    opcodes::OpCode, sections::ElementSegment, ConstExpr, Export, ExportType, SerialBuffer,
+    Serialize, Signature, Value, ValueType, WasmModule,
 };
 
 #[test]
@@ -487,6 +492,501 @@ fn test_br_table_help(condition: i32, expected: i32) {
     assert_eq!(result, Value::I32(expected))
 }
 
+#[test]
+fn test_block_results() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function(
+        "if_else",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Index(GETLOCAL, 0),
+            Block(IF, Some(ValueType::I32)),
+            Const(Value::I32(111)),
+            Op(ELSE),
+            Const(Value::I32(222)),
+            Op(END),
+            Op(END),
+        ],
+    );
+    // `br` carries the block's result, and drops any other values above the block
+    builder.function(
+        "br",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(1)),
+            Block(BLOCK, Some(ValueType::I32)),
+            Const(Value::I32(2)),
+            Const(Value::I32(3)),
+            Index(BR, 0),
+            Op(END),
+            Op(I32ADD),
+            Op(END),
+        ],
+    );
+    // `br` to the function body is a return
+    builder.function(
+        "br_function",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(4)),
+            Const(Value::I32(5)),
+            Index(BR, 0),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let if_else = |inst: &mut Instance<_>, c| inst.call_export("if_else", [Value::I32(c)]);
+    assert_eq!(if_else(&mut inst, 1), Ok(Some(Value::I32(111))));
+    assert_eq!(if_else(&mut inst, 0), Ok(Some(Value::I32(222))));
+    assert_eq!(inst.call_export("br", []), Ok(Some(Value::I32(4))));
+    assert_eq!(inst.call_export("br_function", []), Ok(Some(Value::I32(5))));
+}
+
+#[test]
+fn test_block_type_index() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let i32_i32_to_i32 = builder.signature(&[ValueType::I32, ValueType::I32], Some(ValueType::I32));
+    let i32_to_i32 = builder.signature(&[ValueType::I32], Some(ValueType::I32));
+
+    // A block that takes two operands from the enclosing stack and produces one
+    builder.function(
+        "subtract",
+        &[ValueType::I32, ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Index(GETLOCAL, 0),
+            Index(GETLOCAL, 1),
+            BlockSig(BLOCK, i32_i32_to_i32),
+            Op(I32SUB),
+            Op(END),
+            Op(END),
+        ],
+    );
+
+    // A loop whose param is the counter, so branching back to the start carries it along
+    builder.function(
+        "count_down",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[
+            Index(GETLOCAL, 0),
+            BlockSig(LOOP, i32_to_i32),
+            // count the iterations in local 1
+            Index(GETLOCAL, 1),
+            Const(Value::I32(1)),
+            Op(I32ADD),
+            Index(SETLOCAL, 1),
+            // decrement the counter, and loop until it's zero
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(TEELOCAL, 0),
+            Index(GETLOCAL, 0),
+            Index(BRIF, 0),
+            Op(END),
+            Index(GETLOCAL, 1),
+            Op(I32ADD),
+            Op(END),
+        ],
+    );
+
+    // Type indices from 64 up take two bytes. Skip over one in an `if` whose condition is false.
+    for n_params in 0..62 {
+        builder.signature(&vec![ValueType::I64; n_params], None);
+    }
+    let f32_to_i32 = builder.signature(&[ValueType::F32], Some(ValueType::I32));
+    assert_eq!(f32_to_i32, 64);
+    builder.function(
+        "skip",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::F32(1.5)),
+            Index(GETLOCAL, 0),
+            BlockSig(IF, f32_to_i32),
+            BlockSig(BLOCK, f32_to_i32),
+            Op(I32TRUNCSF32),
+            Op(END),
+            Op(ELSE),
+            Op(DROP),
+            Const(Value::I32(-1)),
+            Op(END),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(
+        inst.call_export("subtract", [Value::I32(50), Value::I32(8)]),
+        Ok(Some(Value::I32(42)))
+    );
+    assert_eq!(
+        inst.call_export("count_down", [Value::I32(5)]),
+        Ok(Some(Value::I32(5)))
+    );
+    assert_eq!(
+        inst.call_export("skip", [Value::I32(1)]),
+        Ok(Some(Value::I32(1)))
+    );
+    assert_eq!(
+        inst.call_export("skip", [Value::I32(0)]),
+        Ok(Some(Value::I32(-1)))
+    );
+}
+
+#[test]
+fn test_block_value_types() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let values = [
+        Value::I32(-1),
+        Value::I64(-2),
+        Value::F32(3.5),
+        Value::F64(-4.5),
+    ];
+    for (i, value) in values.iter().enumerate() {
+        let ty = ValueType::from(*value);
+        let name = arena.alloc_str(&format!("block{i}"));
+        builder.function(
+            name,
+            &[],
+            Some(ty),
+            &[],
+            &[Block(BLOCK, Some(ty)), Const(*value), Op(END), Op(END)],
+        );
+    }
+    builder.function(
+        "void",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Block(BLOCK, None), Op(END), Const(Value::I32(7)), Op(END)],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(inst.call_export(&format!("block{i}"), []), Ok(Some(*value)));
+    }
+    assert_eq!(inst.call_export("void", []), Ok(Some(Value::I32(7))));
+}
+
+/// Run a `block` instruction with the given block type immediate, and return the program counter after it.
+/// The module has one signature if `has_signature` is set.
+fn run_block_type(immediate: &[u8], has_signature: bool) -> Result<usize, Error> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    if has_signature {
+        module.types.insert(Signature {
+            param_types: bumpalo::vec![in &arena],
+            ret_type: Some(ValueType::I64),
+        });
+    }
+    module.code.bytes.push(OpCode::BLOCK as u8);
+    module.code.bytes.extend_from_slice(immediate);
+
+    let mut state = Instance::new(&arena, 0, 0, [], DefaultImportDispatcher::default());
+    state.execute_next_instruction(&module)?;
+    Ok(state.program_counter)
+}
+
+#[test]
+fn test_block_type_signed_leb() {
+    // One-byte value types, read as negative numbers
+    for byte in [0x40, 0x7f, 0x7e, 0x7d, 0x7c] {
+        assert_eq!(run_block_type(&[byte], false), Ok(2));
+    }
+    // -1 (i32) in two bytes. Read as unsigned, it would be a huge type index.
+    assert_eq!(run_block_type(&[0xff, 0x7f], false), Ok(3));
+    // Type index 0, in one byte and in two
+    assert_eq!(run_block_type(&[0x00], true), Ok(2));
+    assert_eq!(run_block_type(&[0x80, 0x00], true), Ok(3));
+
+    let is_invalid =
+        |immediate: &[u8]| matches!(run_block_type(immediate, false), Err(Error::InvalidCode(_)));
+    assert!(is_invalid(&[0x00]), "type index that doesn't exist");
+    assert!(is_invalid(&[ValueType::V128]), "v128");
+    assert!(is_invalid(&[RefType::Func as u8]), "funcref");
+    assert!(is_invalid(&[RefType::Extern as u8]), "externref");
+    assert!(is_invalid(&[0x7a]), "not a type");
+    assert!(
+        is_invalid(&[0xff, 0x7e]),
+        "-129, which has 0x7f in its low bits"
+    );
+}
+
+#[test]
+fn test_conditions_are_nonzero() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let params = [ValueType::I32];
+    let ret = Some(ValueType::I32);
+    builder.function(
+        "br_if",
+        &params,
+        ret,
+        &[],
+        &[
+            Block(BLOCK, ret),
+            Const(Value::I32(1)),
+            Index(GETLOCAL, 0),
+            Index(BRIF, 0),
+            Op(DROP),
+            Const(Value::I32(0)),
+            Op(END),
+            Op(END),
+        ],
+    );
+    builder.function(
+        "if",
+        &params,
+        ret,
+        &[],
+        &[
+            Index(GETLOCAL, 0),
+            Block(IF, ret),
+            Const(Value::I32(1)),
+            Op(ELSE),
+            Const(Value::I32(0)),
+            Op(END),
+            Op(END),
+        ],
+    );
+    builder.function(
+        "select",
+        &params,
+        ret,
+        &[],
+        &[
+            Const(Value::I32(1)),
+            Const(Value::I32(0)),
+            Index(GETLOCAL, 0),
+            Op(SELECT),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    for name in ["br_if", "if", "select"] {
+        for (condition, expected) in [(0, 0), (1, 1), (2, 1), (-1, 1), (i32::MIN, 1)] {
+            let result = inst.call_export(name, [Value::I32(condition)]);
+            assert_eq!(result, Ok(Some(Value::I32(expected))), "{name} {condition}");
+        }
+    }
+}
+
+#[test]
+fn test_branch_stack_underflow() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let i32_param = builder.signature(&[ValueType::I32], None);
+    // The only value is outside the block, so `br` has no result to carry
+    builder.function(
+        "br_block",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(1)),
+            Block(BLOCK, Some(ValueType::I32)),
+            Index(BR, 0),
+            Op(END),
+            Op(END),
+        ],
+    );
+    // Branching back to a loop needs values for its params
+    builder.function(
+        "br_loop",
+        &[],
+        None,
+        &[],
+        &[
+            Const(Value::I32(1)),
+            BlockSig(LOOP, i32_param),
+            Op(DROP),
+            Index(BR, 0),
+            Op(END),
+            Op(END),
+        ],
+    );
+    // A local is not a return value
+    builder.function(
+        "return_local",
+        &[],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[Op(RETURN), Op(END)],
+    );
+    builder.function(
+        "br_function",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Index(BR, 0), Op(END)],
+    );
+    let module = builder.build();
+
+    for name in ["br_block", "br_loop", "return_local", "br_function"] {
+        let mut inst =
+            Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+                .unwrap();
+        // Without verification, the branch or return itself has to notice
+        inst.set_verify(false);
+        let err = inst.call_export(name, []).unwrap_err();
+        assert!(err.contains("but it was empty"), "{name}: {err}");
+    }
+}
+
+#[test]
+fn test_invalid_code() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function("local", &[], None, &[], &[Index(GETLOCAL, 5), Op(END)]);
+    builder.function("global", &[], None, &[], &[Index(GETGLOBAL, 3), Op(END)]);
+    builder.function("branch", &[], None, &[], &[Index(BR, 2), Op(END)]);
+    builder.function("call", &[], None, &[], &[Index(CALL, 99), Op(END)]);
+    builder.function("opcode", &[], None, &[], &[Op(NOP), Op(END)]);
+    let mut module = builder.build();
+    // Replace the `nop` in the last function with a byte that isn't an instruction
+    let nop_offset = module.code.bytes.len() - 2;
+    module.code.bytes[nop_offset] = 0xff;
+
+    let cases = [
+        ("local", "Local 5 doesn't exist"),
+        ("global", "Global 3 doesn't exist"),
+        ("branch", "Branch depth 2 is too deep"),
+        ("call", "Function 99 doesn't exist"),
+        ("opcode", "Unsupported instruction byte 0xff"),
+    ];
+    for is_debug_mode in [false, true] {
+        for (name, expected) in cases {
+            let mut inst = Instance::for_module(
+                &arena,
+                &module,
+                DefaultImportDispatcher::default(),
+                is_debug_mode,
+            )
+            .unwrap();
+            let err = inst.call_export(name, []).unwrap_err();
+            assert!(err.contains(expected), "{name}: {err}");
+        }
+    }
+}
+
+#[test]
+fn test_branch_unwinding() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let i32_to_i32 = builder.signature(&[ValueType::I32], Some(ValueType::I32));
+    builder.function(
+        "out_of_block",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(1)),
+            Block(BLOCK, Some(ValueType::I32)),
+            Const(Value::I32(2)),
+            Const(Value::I32(3)),
+            Const(Value::I32(4)),
+            Const(Value::I32(1)),
+            Index(BRIF, 0),
+            Op(UNREACHABLE),
+            Op(END),
+            Op(I32ADD),
+            Op(END),
+        ],
+    );
+    builder.function(
+        "back_to_loop",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(100)),
+            Index(GETLOCAL, 0),
+            BlockSig(LOOP, i32_to_i32),
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(TEELOCAL, 0),
+            Index(GETLOCAL, 0),
+            Index(GETLOCAL, 0),
+            Index(BRIF, 0),
+            Op(DROP),
+            Op(END),
+            Op(I32ADD),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // After the `br_if`, the result is on top of the value from before the block
+    inst.start_export("out_of_block", []).unwrap();
+    assert_eq!(inst.run_n(7), Ok(RunProgress::Yielded));
+    assert_eq!(inst.value_stack_depth(), 2);
+    assert_eq!(inst.value_stack_peek(0), Some(Value::I32(4)));
+    assert_eq!(inst.value_stack_peek(1), Some(Value::I32(1)));
+    assert_eq!(
+        inst.run_n(100),
+        Ok(RunProgress::Completed(Some(Value::I32(5))))
+    );
+
+    // After the `br_if`, the loop param is on top of the value from before the loop, and the local
+    inst.start_export("back_to_loop", [Value::I32(3)]).unwrap();
+    assert_eq!(inst.run_n(9), Ok(RunProgress::Yielded));
+    assert_eq!(inst.value_stack_depth(), 3);
+    assert_eq!(inst.value_stack_peek(0), Some(Value::I32(2)));
+    assert_eq!(inst.value_stack_peek(1), Some(Value::I32(100)));
+    assert_eq!(inst.run_n(6), Ok(RunProgress::Yielded));
+    assert_eq!(inst.value_stack_depth(), 3);
+    assert_eq!(inst.value_stack_peek(0), Some(Value::I32(1)));
+    assert_eq!(
+        inst.run_n(100),
+        Ok(RunProgress::Completed(Some(Value::I32(100))))
+    );
+}
+
 struct TestDispatcher {
     internal_state: i32,
 }
@@ -724,6 +1224,388 @@ fn test_call_indirect_unsupported_table() {
     test_call_indirect_help(1, 0);
 }
 
+#[test]
+fn test_tail_calls() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let params = [ValueType::I32, ValueType::I32];
+    let signature = builder.signature(&params, Some(ValueType::I32));
+
+    // count(n, acc) = if n == 0 then acc else count(n - 1, acc + 1)
+    let count_body = |call: &[Instr]| {
+        let mut body = vec![
+            Index(GETLOCAL, 0),
+            Op(I32EQZ),
+            Block(IF, None),
+            Index(GETLOCAL, 1),
+            Op(RETURN),
+            Op(END),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(GETLOCAL, 1),
+            Const(Value::I32(1)),
+            Op(I32ADD),
+        ];
+        body.extend_from_slice(call);
+        body.push(Op(END));
+        body
+    };
+    let count_tail = builder.function(
+        "count_tail",
+        &params,
+        Some(ValueType::I32),
+        &[],
+        &count_body(&[Index(RETURNCALL, 0)]),
+    );
+    let count_call = builder.function(
+        "count_call",
+        &params,
+        Some(ValueType::I32),
+        &[],
+        &count_body(&[Index(CALL, 1)]),
+    );
+    // Element 0 in the table
+    let count_indirect = builder.function(
+        "count_indirect",
+        &params,
+        Some(ValueType::I32),
+        &[],
+        &count_body(&[
+            Const(Value::I32(0)),
+            CallIndirect(RETURNCALLINDIRECT, signature),
+        ]),
+    );
+    let other_signature = builder.signature(&[], None);
+    builder.function(
+        "wrong_signature",
+        &params,
+        Some(ValueType::I32),
+        &[],
+        &count_body(&[
+            Const(Value::I32(0)),
+            CallIndirect(RETURNCALLINDIRECT, other_signature),
+        ]),
+    );
+    let mut module = builder.build();
+    module.element.segments.push(ElementSegment::new(&arena));
+    assert_eq!(module.element.get_or_insert_fn(count_indirect), 0);
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let mut count = |name, n| inst.call_export(name, [Value::I32(n), Value::I32(0)]);
+    assert_eq!(count("count_tail", 100_000), Ok(Some(Value::I32(100_000))));
+    assert_eq!(
+        count("count_indirect", 100_000),
+        Ok(Some(Value::I32(100_000)))
+    );
+    assert_eq!(count("count_call", 10), Ok(Some(Value::I32(10))));
+    let err = count("wrong_signature", 1).unwrap_err();
+    assert!(err.contains("Expected signature"), "{err}");
+
+    // Tail calls replace the frame, so the stack doesn't grow. Ordinary calls keep every frame.
+    for (fn_index, name) in [count_tail, count_call, count_indirect].into_iter().zip([
+        "count_tail",
+        "count_call",
+        "count_indirect",
+    ]) {
+        let base = inst.value_stack_depth();
+        inst.start_export(name, [Value::I32(1_000_000), Value::I32(0)])
+            .unwrap();
+        assert_eq!(inst.run_n(10_000), Ok(RunProgress::Yielded));
+        let depth = inst.value_stack_depth() - base;
+        let is_tail = fn_index != count_call;
+        assert_eq!(depth < 10, is_tail, "{name} has stack depth {depth}");
+    }
+}
+
+#[test]
+fn test_current_locals() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function(
+        "scale",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[
+            Index(GETLOCAL, 0),
+            Const(Value::I32(10)),
+            Op(I32MUL),
+            Index(SETLOCAL, 1),
+            Index(GETLOCAL, 1),
+            Const(Value::I32(1)),
+            Op(I32ADD),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.current_locals(), &[]);
+
+    // Break just after the `local.set`
+    inst.start_export("scale", [Value::I32(3)]).unwrap();
+    assert_eq!(inst.current_locals(), &[Value::I32(3), Value::I32(0)]);
+    assert_eq!(inst.run_n(4), Ok(RunProgress::Yielded));
+    assert_eq!(inst.current_locals(), &[Value::I32(3), Value::I32(30)]);
+
+    assert!(inst.set_current_local(2, Value::I32(0)).is_err());
+    assert!(inst.set_current_local(1, Value::I64(0)).is_err());
+    assert_eq!(inst.set_current_local(1, Value::I32(100)), Ok(()));
+    assert_eq!(inst.current_locals(), &[Value::I32(3), Value::I32(100)]);
+    assert_eq!(
+        inst.run_n(10),
+        Ok(RunProgress::Completed(Some(Value::I32(101))))
+    );
+    assert_eq!(inst.current_locals(), &[]);
+}
+
+#[test]
+fn test_local_by_name() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let scale = builder.function(
+        "scale",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[
+            Index(GETLOCAL, 0),
+            Const(Value::I32(10)),
+            Op(I32MUL),
+            Index(TEELOCAL, 1),
+            Op(END),
+        ],
+    );
+    let mut module = builder.build();
+    module.names.append_function(scale, "scale");
+    module
+        .names
+        .local_names
+        .push((scale, bumpalo::vec![in &arena; (0, "input"), (1, "scaled")]));
+
+    // Round trip through the binary format, to check the local names subsection
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    let module = WasmModule::preload(&arena, &bytes, false).unwrap();
+    assert_eq!(module.names.local_index(scale, "scaled"), Some(1));
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.start_export("scale", [Value::I32(3)]).unwrap();
+    assert_eq!(inst.run_n(4), Ok(RunProgress::Yielded));
+    assert_eq!(inst.local_by_name("input"), Some(Value::I32(3)));
+    assert_eq!(inst.local_by_name("scaled"), Some(Value::I32(30)));
+    assert_eq!(inst.local_by_name("scale"), None);
+}
+
+#[test]
+fn test_instructions_executed() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let three = builder.function(
+        "three",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(1)),
+            Const(Value::I32(2)),
+            Op(I32ADD),
+            Op(END),
+        ],
+    );
+    builder.function(
+        "six",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Index(CALL, three), Index(CALL, three), Op(I32ADD), Op(END)],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.instructions_executed(), 0);
+
+    assert_eq!(inst.call_export("three", []), Ok(Some(Value::I32(3))));
+    assert_eq!(inst.instructions_executed(), 4);
+
+    // Each call runs the 4 instructions of `three`, then `six` runs 4 of its own
+    assert_eq!(inst.call_export("six", []), Ok(Some(Value::I32(6))));
+    assert_eq!(inst.instructions_executed(), 4 + 12);
+
+    inst.reset().unwrap();
+    assert_eq!(inst.instructions_executed(), 0);
+    inst.start_export("six", []).unwrap();
+    assert_eq!(inst.run_n(5), Ok(RunProgress::Yielded));
+    assert_eq!(inst.instructions_executed(), 5);
+}
+
+#[test]
+fn test_instruction_index_at() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let import = builder.import_function("env", "log", &[], None);
+    let first = builder.function(
+        "first",
+        &[],
+        None,
+        &[ValueType::I32],
+        &[
+            Const(Value::I32(0x1234)),
+            Op(DROP),
+            Op(UNREACHABLE),
+            Op(END),
+        ],
+    );
+    let second = builder.function("second", &[], None, &[], &[Op(NOP), Op(END)]);
+    let mut module = builder.build();
+    module.code.section_offset = 0x100;
+    assert_eq!((import, first, second), (0, 1, 2));
+
+    let fn_offset = |i: usize| 0x100 + module.code.function_offsets[i] as usize;
+    // The builder writes a 5-byte padded body size, then the local declarations
+    let first_body = fn_offset(0) + 5 + 3;
+    let second_body = fn_offset(1) + 5 + 1;
+
+    // Start, middle and end of the first function
+    assert_eq!(module.instruction_index_at(first_body), Some((1, 0)));
+    assert_eq!(module.instruction_index_at(first_body + 1), Some((1, 0)));
+    assert_eq!(module.instruction_index_at(first_body + 3), Some((1, 1)));
+    assert_eq!(module.instruction_index_at(first_body + 4), Some((1, 2)));
+    assert_eq!(module.instruction_index_at(first_body + 5), Some((1, 3)));
+    assert_eq!(module.instruction_index_at(second_body), Some((2, 0)));
+    assert_eq!(module.instruction_index_at(second_body + 1), Some((2, 1)));
+
+    // Function headers, and anything outside the code section
+    assert_eq!(module.instruction_index_at(fn_offset(0)), None);
+    assert_eq!(module.instruction_index_at(first_body - 1), None);
+    assert_eq!(module.instruction_index_at(first_body + 6), None);
+    assert_eq!(module.instruction_index_at(second_body + 2), None);
+    assert_eq!(module.instruction_index_at(0x10), None);
+
+    // Interpreter positions work too. After a trap, the program counter is just past the
+    // instruction that trapped, so here it's at the `end`.
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert!(inst.call_export("first", []).is_err());
+    let file_offset = inst.program_counter() + module.code.section_offset as usize;
+    assert_eq!(module.instruction_index_at(file_offset), Some((1, 3)));
+}
+
+#[test]
+fn test_dump_state() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let explode = builder.function(
+        "explode",
+        &[ValueType::I32],
+        None,
+        &[ValueType::I64],
+        &[Const(Value::I32(42)), Op(UNREACHABLE), Op(END)],
+    );
+    let outer = builder.function(
+        "outer",
+        &[],
+        None,
+        &[],
+        &[Const(Value::I32(7)), Index(CALL, explode), Op(END)],
+    );
+    let mut module = builder.build();
+    module.names.function_names.push((explode, "explode"));
+    module.names.function_names.push((outer, "outer"));
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let err = inst.call_export("outer", []).unwrap_err();
+    let dump = inst.dump_state();
+
+    let file_offset = inst.program_counter() + module.code.section_offset as usize;
+    assert!(
+        dump.starts_with(&format!("program counter {file_offset:#x}")),
+        "{dump}"
+    );
+    assert!(dump.contains(&format!("func[{outer}]  outer")), "{dump}");
+    assert!(
+        dump.contains(&format!("func[{explode}]  explode")),
+        "{dump}"
+    );
+    assert!(dump.contains("args     0: I32(7)"), "{dump}");
+    assert!(dump.contains("locals   1: I64(0)"), "{dump}");
+    assert!(dump.contains("stack    [I32(42)]"), "{dump}");
+
+    // The error message has the same stack trace
+    assert!(err.contains(dump.lines().nth(2).unwrap()), "{err}");
+}
+
+#[test]
+fn test_trap_handler() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let explode = builder.function("explode", &[], None, &[], &[Op(UNREACHABLE), Op(END)]);
+    let outer = builder.function("outer", &[], None, &[], &[Index(CALL, explode), Op(END)]);
+    let mut module = builder.build();
+    module.names.function_names.push((explode, "explode"));
+    module.names.function_names.push((outer, "outer"));
+
+    let trapped = std::rc::Rc::new(std::cell::RefCell::new(std::vec::Vec::new()));
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    let recorder = trapped.clone();
+    inst.set_trap_handler(Box::new(move |error, frames| {
+        let is_unreachable = *error == Error::Trap(TrapKind::Unreachable);
+        recorder
+            .borrow_mut()
+            .push((is_unreachable, frames.to_vec()));
+    }));
+    let err = inst.call_export("outer", []).unwrap_err();
+
+    let trapped = trapped.borrow();
+    assert_eq!(trapped.len(), 1);
+    let (is_unreachable, frames) = &trapped[0];
+    assert!(is_unreachable);
+    let names: std::vec::Vec<_> = frames.iter().map(|f| (f.fn_index, f.fn_name)).collect();
+    assert_eq!(
+        names,
+        [(outer as usize, "outer"), (explode as usize, "explode")]
+    );
+    // The innermost frame is at the program counter, which is just past the `unreachable`
+    let file_offset = inst.program_counter() + module.code.section_offset as usize;
+    assert_eq!(frames[1].file_offset, file_offset);
+    assert_eq!(
+        module.code.bytes[frames[1].file_offset - module.code.section_offset as usize - 1],
+        UNREACHABLE as u8
+    );
+
+    // The handler replaces the stack trace in the error message
+    assert!(err.contains("unreachable"), "{err}");
+    assert!(!err.contains("func["), "{err}");
+}
+
 fn test_call_indirect_help(table_index: u32, elem_index: u32) -> Value {
     let arena = Bump::new();
     let mut module = WasmModule::new(&arena);
@@ -786,8 +1668,48 @@ fn test_call_indirect_help(table_index: u32, elem_index: u32) -> Value {
     inst.call_export(start_fn_name, []).unwrap().unwrap()
 }
 
-// #[test]
-// fn test_drop() {}
+#[test]
+fn test_drop() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    let values = [
+        Value::I32(1),
+        Value::I64(2),
+        Value::F32(3.0),
+        Value::F64(4.0),
+    ];
+    module.code.bytes.extend([OpCode::DROP as u8; 5]);
+
+    let mut state = Instance::new(&arena, 0, 0, [], DefaultImportDispatcher::default());
+    state.value_store.push(Value::I32(0));
+    for value in values {
+        state.value_store.push(value);
+    }
+
+    // Drop any type, from the top of the stack
+    for value in values.iter().rev() {
+        assert_eq!(state.value_store.peek(), Ok(*value));
+        state.execute_next_instruction(&module).unwrap();
+    }
+    assert_eq!(state.value_store.depth(), 1);
+    assert_eq!(state.value_store.peek(), Ok(Value::I32(0)));
+
+    state.execute_next_instruction(&module).unwrap();
+    assert_eq!(state.value_store.depth(), 0);
+}
+
+#[test]
+fn test_drop_empty_stack() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    module.code.bytes.push(OpCode::DROP as u8);
+
+    let mut state = Instance::new(&arena, 0, 0, [], DefaultImportDispatcher::default());
+    assert_eq!(
+        state.execute_next_instruction(&module).err(),
+        Some(Error::StackEmpty)
+    );
+}
 
 #[test]
 fn test_select() {
@@ -819,6 +1741,73 @@ fn test_select_help(first: Value, second: Value, condition: i32, expected: Value
     assert_eq!(result, expected);
 }
 
+/// Run one `select` or `select t` instruction on the given operands
+fn run_select(code: &[u8], first: Value, second: Value) -> Result<Value, Error> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    module.code.bytes.extend_from_slice(code);
+
+    let mut state = Instance::new(&arena, 0, 0, [], DefaultImportDispatcher::default());
+    state.value_store.push(first);
+    state.value_store.push(second);
+    state.value_store.push(Value::I32(1));
+    state.execute_next_instruction(&module)?;
+    state.value_store.pop()
+}
+
+#[test]
+fn test_select_mixed_types() {
+    let select = [OpCode::SELECT as u8];
+    assert_eq!(
+        run_select(&select, Value::I32(1), Value::I32(2)),
+        Ok(Value::I32(1))
+    );
+    assert_eq!(
+        run_select(&select, Value::I32(1), Value::F32(2.0)),
+        Err(Error::Type(ValueType::I32, ValueType::F32))
+    );
+    assert_eq!(
+        run_select(&select, Value::I64(1), Value::I32(2)),
+        Err(Error::Type(ValueType::I64, ValueType::I32))
+    );
+}
+
+#[test]
+fn test_select_typed() {
+    let select_i64 = [OpCode::SELECTT as u8, 1, ValueType::I64 as u8];
+    assert_eq!(
+        run_select(&select_i64, Value::I64(1), Value::I64(2)),
+        Ok(Value::I64(1))
+    );
+    // Both operands must have the type in the instruction, not just the same type
+    assert_eq!(
+        run_select(&select_i64, Value::I32(1), Value::I32(2)),
+        Err(Error::Type(ValueType::I64, ValueType::I32))
+    );
+    assert_eq!(
+        run_select(&select_i64, Value::I64(1), Value::F64(2.0)),
+        Err(Error::Type(ValueType::I64, ValueType::F64))
+    );
+
+    let select_two_types = [
+        OpCode::SELECTT as u8,
+        2,
+        ValueType::I32 as u8,
+        ValueType::I32 as u8,
+    ];
+    assert!(matches!(
+        run_select(&select_two_types, Value::I32(1), Value::I32(2)),
+        Err(Error::InvalidCode(_))
+    ));
+
+    // select t is how references are selected, but there are no references to select
+    let select_funcref = [OpCode::SELECTT as u8, 1, RefType::Func as u8];
+    assert!(matches!(
+        run_select(&select_funcref, Value::I32(1), Value::I32(2)),
+        Err(Error::InvalidCode(message)) if message.contains("reference")
+    ));
+}
+
 #[test]
 fn test_set_get_local() {
     let arena = Bump::new();
@@ -863,7 +1852,7 @@ fn test_set_get_local() {
     inst.execute_next_instruction(&module).unwrap();
     inst.execute_next_instruction(&module).unwrap();
     assert_eq!(inst.value_store.depth(), 5);
-    assert_eq!(inst.value_store.pop(), Value::I32(12345));
+    assert_eq!(inst.value_store.pop(), Ok(Value::I32(12345)));
 }
 
 #[test]
@@ -910,8 +1899,8 @@ fn test_tee_get_local() {
     inst.execute_next_instruction(&module).unwrap();
     inst.execute_next_instruction(&module).unwrap();
     assert_eq!(inst.value_store.depth(), 6);
-    assert_eq!(inst.value_store.pop(), Value::I32(12345));
-    assert_eq!(inst.value_store.pop(), Value::I32(12345));
+    assert_eq!(inst.value_store.pop(), Ok(Value::I32(12345)));
+    assert_eq!(inst.value_store.pop(), Ok(Value::I32(12345)));
 }
 
 #[test]
@@ -921,6 +1910,12 @@ fn test_global() {
     state
         .globals
         .extend_from_slice(&[Value::F64(1.11), Value::I32(222), Value::F64(3.33)]);
+    state
+        .global_types
+        .extend(state.globals.iter().map(|g| GlobalType {
+            value_type: ValueType::from(*g),
+            is_mutable: true,
+        }));
     let mut module = WasmModule::new(&arena);
 
     module.code.bytes.push(OpCode::GETGLOBAL as u8);
@@ -937,8 +1932,209 @@ fn test_global() {
     state.execute_next_instruction(&module).unwrap();
     state.execute_next_instruction(&module).unwrap();
     assert_eq!(state.value_store.depth(), 2);
-    assert_eq!(state.value_store.pop(), Value::I32(555));
-    assert_eq!(state.value_store.pop(), Value::I32(222));
+    assert_eq!(state.value_store.pop(), Ok(Value::I32(555)));
+    assert_eq!(state.value_store.pop(), Ok(Value::I32(222)));
+}
+
+#[test]
+fn test_global_mutability() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I32,
+            is_mutable: true,
+        },
+        init: ConstExpr::I32(111),
+    });
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I64,
+            is_mutable: false,
+        },
+        init: ConstExpr::I64(222),
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I64),
+    };
+    create_exported_function_no_locals(&mut module, "read", signature, |buf| {
+        // Return mutable + immutable, after overwriting the mutable one
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(333);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I64EXTENDSI32 as u8);
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.encode_u32(1);
+        buf.push(OpCode::I64ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: None,
+    };
+    create_exported_function_no_locals(&mut module, "write_immutable", signature, |buf| {
+        buf.push(OpCode::I64CONST as u8);
+        buf.encode_i64(444);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.encode_u32(1);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let sum: i64 = inst.call_export_typed("read", ()).unwrap();
+    assert_eq!(sum, 333 + 222);
+    assert_eq!(inst.globals[0], Value::I32(333));
+
+    let err = inst.call_export("write_immutable", []).unwrap_err();
+    assert!(err.contains("immutable global 1"), "{err}");
+    assert_eq!(inst.globals[1], Value::I64(222));
+}
+
+#[test]
+fn test_value_type_checks() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let increment_state = builder.import_function(
+        "env",
+        "increment_state",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+    );
+    builder.global(Value::I32(1), true);
+    builder.function(
+        "set_global_f32",
+        &[],
+        None,
+        &[],
+        &[Const(Value::F32(1.5)), Index(SETGLOBAL, 0), Op(END)],
+    );
+    builder.function(
+        "call_import_f32",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::F32(1.5)),
+            Index(CALL, increment_state),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, TestDispatcher { internal_state: 0 }, false).unwrap();
+
+    let err = inst.call_export("set_global_f32", []).unwrap_err();
+    assert!(err.contains("Expected I32, but found F32"), "{err}");
+    assert_eq!(inst.globals[0], Value::I32(1));
+
+    let err = inst.call_export("call_import_f32", []).unwrap_err();
+    assert!(err.contains("Expected I32, but found F32"), "{err}");
+    assert_eq!(inst.import_dispatcher.internal_state, 0);
+}
+
+/// Returns the same thing from every import, whatever its signature says
+struct FixedResultDispatcher(Option<Value>);
+
+impl ImportDispatcher for FixedResultDispatcher {
+    fn dispatch(
+        &mut self,
+        _module_name: &str,
+        _function_name: &str,
+        _arguments: &[Value],
+        _memory: &mut [u8],
+    ) -> Option<Value> {
+        self.0
+    }
+}
+
+#[test]
+fn test_import_result_arity() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let get = builder.import_function("env", "get", &[], Some(ValueType::I32));
+    let log = builder.import_function("env", "log", &[], None);
+    builder.function(
+        "call_get",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Index(CALL, get), Op(END)],
+    );
+    builder.function("call_log", &[], None, &[], &[Index(CALL, log), Op(END)]);
+    let module = builder.build();
+
+    let call = |result: Option<Value>, fn_name: &str| {
+        let dispatcher = FixedResultDispatcher(result);
+        let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+        inst.call_export(fn_name, [])
+    };
+
+    assert_eq!(
+        call(Some(Value::I32(5)), "call_get"),
+        Ok(Some(Value::I32(5)))
+    );
+    assert_eq!(call(None, "call_log"), Ok(None));
+
+    let err = call(None, "call_get").unwrap_err();
+    assert!(err.contains("env.get, called at file offset"), "{err}");
+    assert!(
+        err.contains("returned 0 values but its signature has 1 results"),
+        "{err}"
+    );
+
+    let err = call(Some(Value::I32(5)), "call_log").unwrap_err();
+    assert!(
+        err.contains("returned 1 values but its signature has 0 results"),
+        "{err}"
+    );
+
+    let err = call(Some(Value::F64(5.0)), "call_get").unwrap_err();
+    assert!(err.contains("Expected I32, but found F64"), "{err}");
+}
+
+#[test]
+fn test_value_stack_peek() {
+    let arena = Bump::new();
+    let mut state = default_state(&arena);
+    let mut module = WasmModule::new(&arena);
+
+    let values = [Value::I32(1), Value::F64(2.5), Value::I64(3)];
+    for value in values {
+        const_value(&mut module.code.bytes, value);
+    }
+
+    assert_eq!(state.value_stack_depth(), 0);
+    assert_eq!(state.value_stack_peek(0), None);
+
+    for _ in values {
+        state.execute_next_instruction(&module).unwrap();
+    }
+
+    assert_eq!(state.value_stack_depth(), 3);
+    assert_eq!(state.value_stack_peek(0), Some(Value::I64(3)));
+    assert_eq!(state.value_stack_peek(1), Some(Value::F64(2.5)));
+    assert_eq!(state.value_stack_peek(2), Some(Value::I32(1)));
+    assert_eq!(state.value_stack_peek(3), None);
+
+    // Peeking doesn't consume anything
+    assert_eq!(state.value_stack_depth(), 3);
+    assert_eq!(state.value_store.pop(), Ok(Value::I64(3)));
 }
 
 #[test]
@@ -951,7 +2147,7 @@ fn test_i32const() {
     module.code.bytes.encode_i32(12345);
 
     state.execute_next_instruction(&module).unwrap();
-    assert_eq!(state.value_store.pop(), Value::I32(12345))
+    assert_eq!(state.value_store.pop(), Ok(Value::I32(12345)))
 }
 
 #[test]
@@ -964,7 +2160,7 @@ fn test_i64const() {
     module.code.bytes.encode_i64(1234567890);
 
     state.execute_next_instruction(&module).unwrap();
-    assert_eq!(state.value_store.pop(), Value::I64(1234567890))
+    assert_eq!(state.value_store.pop(), Ok(Value::I64(1234567890)))
 }
 
 #[test]
@@ -977,7 +2173,7 @@ fn test_f32const() {
     module.code.bytes.encode_f32(123.45);
 
     state.execute_next_instruction(&module).unwrap();
-    assert_eq!(state.value_store.pop(), Value::F32(123.45))
+    assert_eq!(state.value_store.pop(), Ok(Value::F32(123.45)))
 }
 
 #[test]
@@ -990,5 +2186,593 @@ fn test_f64const() {
     module.code.bytes.encode_f64(12345.67890);
 
     state.execute_next_instruction(&module).unwrap();
-    assert_eq!(state.value_store.pop(), Value::F64(12345.67890))
+    assert_eq!(state.value_store.pop(), Ok(Value::F64(12345.67890)))
+}
+
+#[test]
+fn test_trap_unreachable() {
+    let arena = Bump::new();
+    let mut state = default_state(&arena);
+    let mut module = WasmModule::new(&arena);
+
+    module.code.bytes.push(OpCode::UNREACHABLE as u8);
+
+    let result = state.execute_next_instruction(&module);
+    assert_eq!(result.err(), Some(Error::Trap(TrapKind::Unreachable)));
+}
+
+#[test]
+fn test_trap_divide_by_zero() {
+    let arena = Bump::new();
+    let mut state = default_state(&arena);
+    let mut module = WasmModule::new(&arena);
+
+    module.code.bytes.push(OpCode::I32CONST as u8);
+    module.code.bytes.encode_i32(1);
+    module.code.bytes.push(OpCode::I32CONST as u8);
+    module.code.bytes.encode_i32(0);
+    module.code.bytes.push(OpCode::I32DIVS as u8);
+
+    state.execute_next_instruction(&module).unwrap();
+    state.execute_next_instruction(&module).unwrap();
+    let result = state.execute_next_instruction(&module);
+    assert_eq!(
+        result.err(),
+        Some(Error::Trap(TrapKind::IntegerDivideByZero))
+    );
+}
+
+#[test]
+fn test_reset() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+    module.data.append_segment(DataSegment {
+        mode: DataMode::active_at(0),
+        init: bumpalo::vec![in &arena; 10],
+    });
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I32,
+            is_mutable: true,
+        },
+        init: ConstExpr::I32(0),
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+
+    // Increment a byte in memory and a global, then return their sum
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::I32LOAD8U as u8);
+        buf.encode_u32(0);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::I32STORE8 as u8);
+        buf.encode_u32(0);
+        buf.encode_u32(0);
+
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::SETGLOBAL as u8);
+        buf.encode_u32(0);
+
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(0);
+        buf.push(OpCode::I32LOAD8U as u8);
+        buf.encode_u32(0);
+        buf.encode_u32(0);
+        buf.push(OpCode::GETGLOBAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let first = inst.call_export("test", []).unwrap();
+    assert_eq!(first, Some(Value::I32(11 + 1)));
+
+    let second = inst.call_export("test", []).unwrap();
+    assert_eq!(second, Some(Value::I32(12 + 2)));
+
+    inst.reset().unwrap();
+    assert_eq!(inst.memory[0], 10);
+    assert_eq!(inst.globals[0], Value::I32(0));
+
+    let after_reset = inst.call_export("test", []).unwrap();
+    assert_eq!(after_reset, first);
+}
+
+#[test]
+fn test_call_export_typed() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32, ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "add", signature, |buf| {
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let sum: i32 = inst.call_export_typed("add", (1i32, 2i32)).unwrap();
+    assert_eq!(sum, 3);
+
+    let wrapped: u32 = inst.call_export_typed("add", (u32::MAX, 2u32)).unwrap();
+    assert_eq!(wrapped, 1);
+
+    let wrong_return_type = inst.call_export_typed::<_, i64>("add", (1i32, 2i32));
+    assert!(wrong_return_type.is_err());
+
+    let wrong_arg_type = inst.call_export_typed::<_, i32>("add", (1i32, 2i64));
+    assert!(wrong_arg_type.is_err());
+}
+
+#[test]
+fn test_verify_local_type() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    let local_types = [(1, ValueType::I32)];
+    create_exported_function_with_locals(&mut module, "test", signature, &local_types, |buf| {
+        // Miscompiled: store an i64 in an i32 local
+        buf.push(OpCode::I64CONST as u8);
+        buf.encode_i64(1);
+        buf.push(OpCode::SETLOCAL as u8);
+        buf.encode_u32(0);
+        // Without verification, nothing goes wrong until the local is used
+        buf.push(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.push(OpCode::I32CONST as u8);
+        buf.encode_i32(1);
+        buf.push(OpCode::I32ADD as u8);
+        buf.push(OpCode::END as u8);
+    });
+
+    let error_offset = |verify: bool| {
+        let mut inst =
+            Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+                .unwrap();
+        inst.set_verify(verify);
+        let message = inst.call_export("test", []).unwrap_err();
+        assert!(message.contains("type mismatch"), "{message}");
+        let hex = message.split("file offset 0x").nth(1).unwrap();
+        let hex_digits: String = hex.chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+        u32::from_str_radix(&hex_digits, 16).unwrap()
+    };
+
+    let late_offset = error_offset(false);
+    let early_offset = error_offset(true);
+    assert!(
+        early_offset < late_offset,
+        "{early_offset:#x} {late_offset:#x}"
+    );
+}
+
+#[test]
+fn test_verify_return_type() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "test", signature, |buf| {
+        // Miscompiled: return an i64 from a function that should return i32
+        buf.push(OpCode::I64CONST as u8);
+        buf.encode_i64(1);
+        buf.push(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.set_verify(false);
+    assert_eq!(inst.call_export("test", []), Ok(Some(Value::I64(1))));
+
+    inst.reset().unwrap();
+    inst.set_verify(true);
+    let message = inst.call_export("test", []).unwrap_err();
+    assert!(message.contains("Expected I32, but found I64"), "{message}");
+}
+
+#[test]
+fn test_trap_on_overflow() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let binop_body = |op| [Index(GETLOCAL, 0), Index(GETLOCAL, 1), Op(op), Op(END)];
+    let i32_params = [ValueType::I32, ValueType::I32];
+    let i64_params = [ValueType::I64, ValueType::I64];
+    builder.function(
+        "i32add",
+        &i32_params,
+        Some(ValueType::I32),
+        &[],
+        &binop_body(I32ADD),
+    );
+    builder.function(
+        "i32sub",
+        &i32_params,
+        Some(ValueType::I32),
+        &[],
+        &binop_body(I32SUB),
+    );
+    builder.function(
+        "i64mul",
+        &i64_params,
+        Some(ValueType::I64),
+        &[],
+        &binop_body(I64MUL),
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // Wasm integer arithmetic wraps by default
+    let wrapped: i32 = inst.call_export_typed("i32add", (i32::MAX, 1)).unwrap();
+    assert_eq!(wrapped, i32::MIN);
+    let wrapped: i32 = inst.call_export_typed("i32sub", (i32::MIN, 1)).unwrap();
+    assert_eq!(wrapped, i32::MAX);
+    let wrapped: i64 = inst.call_export_typed("i64mul", (i64::MAX, 2i64)).unwrap();
+    assert_eq!(wrapped, -2);
+
+    inst.set_trap_on_overflow(true);
+
+    let sum: i32 = inst.call_export_typed("i32add", (1, 2)).unwrap();
+    assert_eq!(sum, 3);
+
+    for (name, args) in [
+        (
+            "i32add",
+            (i32::MAX, 1).into_values().collect::<std::vec::Vec<_>>(),
+        ),
+        ("i32sub", (i32::MIN, 1).into_values().collect()),
+        ("i64mul", (i64::MAX, 2i64).into_values().collect()),
+    ] {
+        inst.reset().unwrap();
+        let message = inst.call_export(name, args).unwrap_err();
+        assert!(message.contains("integer overflow"), "{name}: {message}");
+    }
+}
+
+#[test]
+fn test_run_n() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+
+    // Sum the numbers from n down to 1
+    builder.function(
+        "sum",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[
+            Block(LOOP, None),
+            Index(GETLOCAL, 1),
+            Index(GETLOCAL, 0),
+            Op(I32ADD),
+            Index(SETLOCAL, 1),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(TEELOCAL, 0),
+            Index(BRIF, 0),
+            Op(END),
+            Index(GETLOCAL, 1),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    inst.start_export("sum", [Value::I32(1000)]).unwrap();
+
+    let mut chunks = 0;
+    let result = loop {
+        chunks += 1;
+        match inst.run_n(100).unwrap() {
+            RunProgress::Yielded => {}
+            RunProgress::Completed(result) => break result,
+        }
+    };
+
+    assert_eq!(result, Some(Value::I32(500500)));
+    // 9 instructions per iteration, plus 4 outside the loop
+    assert_eq!(chunks, (1000 * 9 + 4) / 100 + 1);
+
+    // The same call in one go gives the same result
+    assert_eq!(
+        inst.call_export("sum", [Value::I32(1000)]),
+        Ok(Some(Value::I32(500500)))
+    );
+}
+
+#[test]
+fn test_exports() {
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    let global_index = builder.global(Value::I32(0), true);
+    let fn_index = builder.function(
+        "answer",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Instr::Const(Value::I32(42)), Instr::Op(OpCode::END)],
+    );
+    let mut module = builder.build();
+    module.export.append(Export {
+        name: "counter",
+        ty: ExportType::Global,
+        index: global_index,
+    });
+    module.export.append(Export {
+        name: "memory",
+        ty: ExportType::Mem,
+        index: 0,
+    });
+
+    let inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.exports(),
+        &[
+            Export {
+                name: "answer",
+                ty: ExportType::Func,
+                index: fn_index,
+            },
+            Export {
+                name: "counter",
+                ty: ExportType::Global,
+                index: global_index,
+            },
+            Export {
+                name: "memory",
+                ty: ExportType::Mem,
+                index: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_set_program_counter() {
+    use Instr::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function(
+        "f",
+        &[],
+        Some(ValueType::I32),
+        &[ValueType::I32],
+        &[
+            Const(Value::I32(7)),
+            Index(OpCode::SETLOCAL, 0),
+            Const(Value::I32(8)),
+            Index(OpCode::SETLOCAL, 0),
+            Index(OpCode::GETLOCAL, 0),
+            Op(OpCode::END),
+        ],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert!(inst.set_program_counter(0).is_err(), "nothing running yet");
+
+    // Pause just before setting the local to 8
+    inst.start_export("f", []).unwrap();
+    assert_eq!(inst.run_n(2), Ok(RunProgress::Yielded));
+    let pc = inst.program_counter();
+    assert_eq!(module.code.bytes[pc], OpCode::I32CONST as u8);
+
+    // The middle of `i32.const 8`, and past the end of the function
+    assert!(inst.set_program_counter(pc + 1).is_err());
+    assert!(inst.set_program_counter(module.code.bytes.len()).is_err());
+    assert_eq!(inst.program_counter(), pc);
+
+    // Skip over `i32.const 8; local.set 0` to `local.get 0`
+    let get_local = pc + 4;
+    assert_eq!(inst.set_program_counter(get_local), Ok(()));
+    assert_eq!(
+        inst.run_n(100),
+        Ok(RunProgress::Completed(Some(Value::I32(7))))
+    );
+}
+
+#[test]
+fn test_call_export_argument_mismatch() {
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function(
+        "f",
+        &[ValueType::I32, ValueType::F64],
+        Some(ValueType::I32),
+        &[],
+        &[Instr::Index(OpCode::GETLOCAL, 0), Instr::Op(OpCode::END)],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    assert_eq!(
+        inst.start_export("f", [Value::I32(1), Value::I32(2)]),
+        Err(Error::ArgumentTypeMismatch {
+            index: 1,
+            expected: ValueType::F64,
+            actual: ValueType::I32,
+        })
+    );
+    assert_eq!(inst.value_stack_depth(), 0);
+
+    assert_eq!(
+        inst.start_export("f", [Value::I32(1)]),
+        Err(Error::ArgumentCount {
+            expected: 2,
+            actual: 1
+        })
+    );
+    assert_eq!(
+        inst.start_export("f", (1, 2.0, 3)),
+        Err(Error::ArgumentCount {
+            expected: 2,
+            actual: 3
+        })
+    );
+    assert_eq!(inst.value_stack_depth(), 0);
+
+    assert_eq!(
+        inst.start_export("g", []),
+        Err(Error::ExportNotFound("g".to_string()))
+    );
+    assert_eq!(
+        inst.call_export("f", [Value::I32(1), Value::I32(2)]),
+        Err("Type mismatch on argument 1. Expected F64 but got I32".to_string())
+    );
+
+    assert_eq!(inst.call_export("f", (5, 2.0)), Ok(Some(Value::I32(5))));
+}
+
+#[test]
+fn test_verify_does_not_change_results() {
+    use Instr::*;
+    use OpCode::*;
+
+    // Sum of i*i for i in 1..=n, with a local.set and a global.set on every iteration
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let iterations = builder.global(Value::I64(0), true);
+    builder.function(
+        "sum_squares",
+        &[ValueType::I32],
+        Some(ValueType::I64),
+        &[ValueType::I64],
+        &[
+            Block(LOOP, None),
+            Index(GETLOCAL, 1),
+            Index(GETLOCAL, 0),
+            Op(I64EXTENDUI32),
+            Index(GETLOCAL, 0),
+            Op(I64EXTENDUI32),
+            Op(I64MUL),
+            Op(I64ADD),
+            Index(SETLOCAL, 1),
+            Index(GETGLOBAL, iterations),
+            Const(Value::I64(1)),
+            Op(I64ADD),
+            Index(SETGLOBAL, iterations),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(TEELOCAL, 0),
+            Index(BRIF, 0),
+            Op(END),
+            Index(GETLOCAL, 1),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    for verify in [false, true] {
+        let mut inst =
+            Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+                .unwrap();
+        inst.set_verify(verify);
+        assert_eq!(
+            inst.call_export("sum_squares", [Value::I32(100)]),
+            Ok(Some(Value::I64(338_350))),
+            "verify: {verify}"
+        );
+        assert_eq!(inst.globals[iterations as usize], Value::I64(100));
+    }
+}
+
+#[test]
+fn test_value_stack_capacity() {
+    use Instr::*;
+    use OpCode::*;
+
+    // sum(n) = if n == 0 { 0 } else { n + sum(n - 1) }
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function(
+        "sum",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Index(GETLOCAL, 0),
+            Block(IF, None),
+            Index(GETLOCAL, 0),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(CALL, 0),
+            Op(I32ADD),
+            Op(RETURN),
+            Op(END),
+            Const(Value::I32(0)),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+    let n = 5000;
+    let expected = Ok(Some(Value::I32(n * (n + 1) / 2)));
+
+    let mut unreserved =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(unreserved.call_export("sum", [Value::I32(n)]), expected);
+    let needed = unreserved.value_store.capacity();
+
+    let mut reserved =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+            .unwrap()
+            .with_value_stack_capacity(needed);
+    let capacity = reserved.value_store.capacity();
+    assert!(capacity >= needed);
+
+    // No reallocation, even when the buffer is reused for a second run
+    assert_eq!(reserved.call_export("sum", [Value::I32(n)]), expected);
+    assert_eq!(reserved.call_export("sum", [Value::I32(n)]), expected);
+    assert_eq!(reserved.value_store.capacity(), capacity);
 }
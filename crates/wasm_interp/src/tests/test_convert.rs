@@ -1,7 +1,7 @@
 #![cfg(test)]
 
-use super::test_op_example;
-use roc_wasm_module::{opcodes::OpCode::*, Value};
+use super::{run_op_example, test_op_error, test_op_example};
+use roc_wasm_module::{opcodes::OpCode::*, Value, ValueType};
 
 #[test]
 fn test_i32wrapi64() {
@@ -18,12 +18,15 @@ fn test_i32truncsf32() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i32truncsf32_oob() {
-    test_op_example(
+    let message = test_op_error(
         I32TRUNCSF32,
         [Value::F32(i32::MAX as f32 * 2.0)],
-        Value::I32(i32::MIN),
+        ValueType::I32,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -37,12 +40,15 @@ fn test_i32truncuf32() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i32truncuf32_oob() {
-    test_op_example(
+    let message = test_op_error(
         I32TRUNCUF32,
         [Value::F32(u32::MAX as f32 * 2.0)],
-        Value::I32(0),
+        ValueType::I32,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -52,12 +58,15 @@ fn test_i32truncsf64() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i32truncsf64_oob() {
-    test_op_example(
+    let message = test_op_error(
         I32TRUNCSF64,
         [Value::F64(i32::MAX as f64 * 2.0)],
-        Value::I32(i32::MIN),
+        ValueType::I32,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -71,12 +80,15 @@ fn test_i32truncuf64() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i32truncuf64_oob() {
-    test_op_example(
+    let message = test_op_error(
         I32TRUNCUF64,
         [Value::F64(u32::MAX as f64 * 2.0)],
-        Value::I32(0),
+        ValueType::I32,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -96,12 +108,15 @@ fn test_i64truncsf32() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i64truncsf32_oob() {
-    test_op_example(
+    let message = test_op_error(
         I64TRUNCSF32,
         [Value::F32(i64::MAX as f32 * 2.0)],
-        Value::I64(i64::MIN),
+        ValueType::I64,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -115,12 +130,15 @@ fn test_i64truncuf32() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i64truncuf32_oob() {
-    test_op_example(
+    let message = test_op_error(
         I64TRUNCUF32,
         [Value::F32(u64::MAX as f32 * 2.0)],
-        Value::I64(0),
+        ValueType::I64,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -130,12 +148,15 @@ fn test_i64truncsf64() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i64truncsf64_oob() {
-    test_op_example(
+    let message = test_op_error(
         I64TRUNCSF64,
         [Value::F64(i64::MAX as f64 * 2.0)],
-        Value::I64(i64::MIN),
+        ValueType::I64,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
@@ -149,15 +170,100 @@ fn test_i64truncuf64() {
 }
 
 #[test]
-#[should_panic(expected = "Cannot truncate")]
 fn test_i64truncuf64_oob() {
-    test_op_example(
-        I32TRUNCUF64,
+    let message = test_op_error(
+        I64TRUNCUF64,
         [Value::F64(u64::MAX as f64 * 2.0)],
-        Value::I32(0),
+        ValueType::I64,
+    );
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
     );
 }
 
+#[test]
+fn test_trunc_nan_and_infinity() {
+    let ops = [
+        (I32TRUNCSF32, ValueType::F32, ValueType::I32),
+        (I32TRUNCUF32, ValueType::F32, ValueType::I32),
+        (I32TRUNCSF64, ValueType::F64, ValueType::I32),
+        (I32TRUNCUF64, ValueType::F64, ValueType::I32),
+        (I64TRUNCSF32, ValueType::F32, ValueType::I64),
+        (I64TRUNCUF32, ValueType::F32, ValueType::I64),
+        (I64TRUNCSF64, ValueType::F64, ValueType::I64),
+        (I64TRUNCUF64, ValueType::F64, ValueType::I64),
+    ];
+    for (op, arg_type, ret_type) in ops {
+        for x in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let arg = match arg_type {
+                ValueType::F32 => Value::F32(x as f32),
+                _ => Value::F64(x),
+            };
+            let message = test_op_error(op, [arg], ret_type);
+            assert!(
+                message.contains("invalid conversion to integer"),
+                "{op:?} {arg:?}: {message}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_trunc_range_limits() {
+    // Just beyond i32::MAX, which f32 rounds up to 2^31
+    let message = test_op_error(I32TRUNCSF64, [Value::F64(2147483648.0)], ValueType::I32);
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
+    );
+    let message = test_op_error(I32TRUNCSF32, [Value::F32(2147483648.0)], ValueType::I32);
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
+    );
+    let message = test_op_error(I32TRUNCSF64, [Value::F64(-2147483649.0)], ValueType::I32);
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
+    );
+    let message = test_op_error(I32TRUNCUF64, [Value::F64(-1.0)], ValueType::I32);
+    assert!(
+        message.contains("invalid conversion to integer"),
+        "{message}"
+    );
+
+    // The fractional part is dropped before the range check
+    test_op_example(
+        I32TRUNCSF64,
+        [Value::F64(2147483647.9)],
+        Value::I32(i32::MAX),
+    );
+    test_op_example(
+        I32TRUNCSF64,
+        [Value::F64(-2147483648.9)],
+        Value::I32(i32::MIN),
+    );
+    test_op_example(I32TRUNCUF64, [Value::F64(-0.9)], Value::I32(0));
+    test_op_example(
+        I64TRUNCSF64,
+        [Value::F64(-(2f64.powi(63)))],
+        Value::I64(i64::MIN),
+    );
+}
+
+#[test]
+fn test_trunc_toward_zero() {
+    test_op_example(I32TRUNCSF32, [Value::F32(-2.9)], Value::I32(-2));
+    test_op_example(I32TRUNCSF64, [Value::F64(-2.9)], Value::I32(-2));
+    test_op_example(I64TRUNCSF32, [Value::F32(-2.9)], Value::I64(-2));
+    test_op_example(I64TRUNCSF64, [Value::F64(-2.9)], Value::I64(-2));
+    test_op_example(I32TRUNCUF32, [Value::F32(2.9)], Value::I32(2));
+    test_op_example(I32TRUNCUF64, [Value::F64(2.9)], Value::I32(2));
+    test_op_example(I64TRUNCUF32, [Value::F32(2.9)], Value::I64(2));
+    test_op_example(I64TRUNCUF64, [Value::F64(2.9)], Value::I64(2));
+}
+
 #[test]
 fn test_f32convertsi32() {
     test_op_example(F32CONVERTSI32, [Value::I32(-1)], Value::F32(-1.0));
@@ -183,6 +289,48 @@ fn test_f32demotef64() {
     test_op_example(F32DEMOTEF64, [Value::F64(12.375)], Value::F32(12.375));
 }
 
+#[test]
+fn test_f32demotef64_overflow() {
+    test_op_example(F32DEMOTEF64, [Value::F64(1e300)], Value::F32(f32::INFINITY));
+    test_op_example(
+        F32DEMOTEF64,
+        [Value::F64(-1e300)],
+        Value::F32(f32::NEG_INFINITY),
+    );
+    // Just above f32::MAX, but rounds down to it rather than overflowing
+    test_op_example(
+        F32DEMOTEF64,
+        [Value::F64(
+            f32::MAX as f64 * (1.0 + f32::EPSILON as f64 / 4.0),
+        )],
+        Value::F32(f32::MAX),
+    );
+}
+
+#[test]
+fn test_f32demotef64_rounding() {
+    // Halfway between 1.0 and the next f32 up. Ties round to even.
+    let halfway = 1.0 + f32::EPSILON as f64 / 2.0;
+    test_op_example(F32DEMOTEF64, [Value::F64(halfway)], Value::F32(1.0));
+
+    let above_halfway = halfway + f64::EPSILON;
+    test_op_example(
+        F32DEMOTEF64,
+        [Value::F64(above_halfway)],
+        Value::F32(1.0 + f32::EPSILON),
+    );
+}
+
+#[test]
+fn test_f32demotef64_nan() {
+    let result = run_op_example(F32DEMOTEF64, [Value::F64(f64::NAN)], ValueType::F32)
+        .unwrap()
+        .unwrap()
+        .expect_f32()
+        .unwrap();
+    assert!(result.is_nan());
+}
+
 #[test]
 fn test_f64convertsi32() {
     test_op_example(F64CONVERTSI32, [Value::I32(-1)], Value::F64(-1.0));
@@ -207,11 +355,85 @@ fn test_f64convertui64() {
     );
 }
 
+#[test]
+fn test_convert_rounds_to_nearest_even() {
+    // Integers just past the float's precision. Ties round to the even mantissa.
+    let f32_limit = 1 << 24;
+    test_op_example(
+        F32CONVERTSI32,
+        [Value::I32(f32_limit + 1)],
+        Value::F32(f32_limit as f32),
+    );
+    test_op_example(
+        F32CONVERTSI32,
+        [Value::I32(f32_limit + 3)],
+        Value::F32((f32_limit + 4) as f32),
+    );
+    test_op_example(
+        F32CONVERTSI32,
+        [Value::I32(-123_456_789)],
+        Value::F32(-123_456_792.0),
+    );
+
+    let f64_limit = 1i64 << 53;
+    test_op_example(
+        F64CONVERTSI64,
+        [Value::I64(f64_limit + 1)],
+        Value::F64(f64_limit as f64),
+    );
+    test_op_example(
+        F64CONVERTSI64,
+        [Value::I64(f64_limit + 3)],
+        Value::F64((f64_limit + 4) as f64),
+    );
+}
+
+#[test]
+fn test_convert_unsigned_above_i64_max() {
+    // Sources with the top bit set must not be treated as negative
+    let big = (1u64 << 63) + (1 << 11);
+    test_op_example(
+        F64CONVERTUI64,
+        [Value::from(big)],
+        Value::F64(9223372036854777856.0),
+    );
+    test_op_example(F32CONVERTUI64, [Value::from(big)], Value::F32(9.223372e18));
+    test_op_example(
+        F64CONVERTUI64,
+        [Value::from(u64::MAX)],
+        Value::F64(2f64.powi(64)),
+    );
+}
+
 #[test]
 fn test_f64promotef32() {
     test_op_example(F64PROMOTEF32, [Value::F32(12.375)], Value::F64(12.375));
 }
 
+#[test]
+fn test_f64promotef32_round_trip() {
+    for x in [
+        f32::MIN_POSITIVE,
+        f32::MAX,
+        f32::INFINITY,
+        -0.0,
+        0.1,
+        -1.0e-45, // subnormal
+    ] {
+        let promoted = run_op_example(F64PROMOTEF32, [Value::F32(x)], ValueType::F64)
+            .unwrap()
+            .unwrap();
+        assert_eq!(promoted, Value::F64(x as f64));
+
+        let demoted = run_op_example(F32DEMOTEF64, [promoted], ValueType::F32)
+            .unwrap()
+            .unwrap()
+            .expect_f32()
+            .unwrap();
+        assert_eq!(demoted.to_bits(), x.to_bits(), "{x:?}");
+    }
+}
+
 #[test]
 fn test_i32reinterpretf32() {
     test_op_example(
@@ -1,7 +1,7 @@
 #![cfg(test)]
 
-use super::test_op_example;
-use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value};
+use super::{run_op_example, test_op_example};
+use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value, ValueType};
 
 fn test_f32_comparison(op: OpCode, arg1: f32, arg2: f32, expected: bool) {
     test_op_example(
@@ -23,6 +23,34 @@ fn test_f32_unop(op: OpCode, arg: f32, expected: f32) {
     test_op_example(op, [Value::F32(arg)], Value::F32(expected))
 }
 
+/// Check the exact result, distinguishing -0.0 from +0.0. Any NaN is accepted for an expected NaN.
+fn test_f32_exact<A>(op: OpCode, args: A, expected: f32)
+where
+    A: IntoIterator<Item = f32>,
+{
+    let args = args.into_iter().map(Value::F32);
+    let result = run_op_example(op, args, ValueType::F32)
+        .unwrap()
+        .unwrap()
+        .expect_f32()
+        .unwrap();
+    if expected.is_nan() {
+        assert!(result.is_nan(), "{op:?} expected NaN but got {result:?}");
+    } else {
+        assert_eq!(result.to_bits(), expected.to_bits(), "{op:?} {result:?}");
+    }
+}
+
+/// Run a unary op on the raw bits of a float, to check the sign bit of NaN results
+fn test_f32_unop_bits(op: OpCode, arg: f32) -> u32 {
+    run_op_example(op, [Value::F32(arg)], ValueType::F32)
+        .unwrap()
+        .unwrap()
+        .expect_f32()
+        .unwrap()
+        .to_bits()
+}
+
 #[test]
 fn test_f32eq() {
     let op = F32EQ;
@@ -69,6 +97,26 @@ fn test_f32ge() {
     test_f32_comparison(op, -1.1, 1.1, false);
 }
 
+#[test]
+fn test_f32_compare_nan() {
+    // NaN is unordered: it's not equal to, less than or greater than anything, including itself
+    let nan = f32::NAN;
+    for (a, b) in [
+        (nan, 1.0),
+        (1.0, nan),
+        (nan, nan),
+        (nan, f32::INFINITY),
+        (-0.0, nan),
+    ] {
+        test_f32_comparison(F32EQ, a, b, false);
+        test_f32_comparison(F32NE, a, b, true);
+        test_f32_comparison(F32LT, a, b, false);
+        test_f32_comparison(F32GT, a, b, false);
+        test_f32_comparison(F32LE, a, b, false);
+        test_f32_comparison(F32GE, a, b, false);
+    }
+}
+
 #[test]
 fn test_f32abs() {
     let op = F32ABS;
@@ -85,6 +133,21 @@ fn test_f32neg() {
     test_f32_unop(op, -1.1, 1.1);
 }
 
+#[test]
+fn test_f32abs_neg_sign_bit() {
+    test_f32_exact(F32ABS, [-0.0], 0.0);
+    test_f32_exact(F32NEG, [0.0], -0.0);
+    test_f32_exact(F32NEG, [-0.0], 0.0);
+    test_f32_exact(F32ABS, [f32::NEG_INFINITY], f32::INFINITY);
+
+    // abs and neg only change the sign bit, even for NaN
+    let nan = f32::NAN;
+    let neg_nan = -f32::NAN;
+    assert_eq!(test_f32_unop_bits(F32NEG, nan), neg_nan.to_bits());
+    assert_eq!(test_f32_unop_bits(F32NEG, neg_nan), nan.to_bits());
+    assert_eq!(test_f32_unop_bits(F32ABS, neg_nan), nan.to_bits());
+}
+
 #[test]
 fn test_f32ceil() {
     let op = F32CEIL;
@@ -115,6 +178,12 @@ fn test_f32nearest() {
     test_f32_unop(op, -1.6, -2.0);
     test_f32_unop(op, 1.5, 2.0);
     test_f32_unop(op, 2.5, 2.0);
+    test_f32_unop(op, -2.5, -2.0);
+    test_f32_unop(op, -3.5, -4.0);
+    test_f32_exact(op, [0.5], 0.0);
+    test_f32_exact(op, [-0.5], -0.0);
+    test_f32_exact(op, [-0.4], -0.0);
+    test_f32_exact(op, [f32::NAN], f32::NAN);
 }
 
 #[test]
@@ -123,6 +192,24 @@ fn test_f32sqrt() {
     test_f32_unop(op, 4.0, 2.0);
 }
 
+#[test]
+fn test_f32sqrt_edge_cases() {
+    let op = F32SQRT;
+    test_f32_exact(op, [-1.0], f32::NAN);
+    test_f32_exact(op, [f32::NEG_INFINITY], f32::NAN);
+    test_f32_exact(op, [-0.0], -0.0);
+    test_f32_exact(op, [f32::INFINITY], f32::INFINITY);
+}
+
+#[test]
+fn test_f32_rounding_signed_zero() {
+    test_f32_exact(F32CEIL, [-0.5], -0.0);
+    test_f32_exact(F32TRUNC, [-0.5], -0.0);
+    test_f32_exact(F32FLOOR, [-0.5], -1.0);
+    test_f32_exact(F32FLOOR, [-0.0], -0.0);
+    test_f32_exact(F32FLOOR, [f32::NAN], f32::NAN);
+}
+
 #[test]
 fn test_f32add() {
     let op = F32ADD;
@@ -166,6 +253,11 @@ fn test_f32min() {
     let op = F32MIN;
     test_f32_binop(op, 1.1, 2.2, 1.1);
     test_f32_binop(op, -1.1, -2.2, -2.2);
+    test_f32_exact(op, [-0.0, 0.0], -0.0);
+    test_f32_exact(op, [0.0, -0.0], -0.0);
+    test_f32_exact(op, [f32::NAN, 1.0], f32::NAN);
+    test_f32_exact(op, [1.0, f32::NAN], f32::NAN);
+    test_f32_exact(op, [f32::NEG_INFINITY, f32::NAN], f32::NAN);
 }
 
 #[test]
@@ -173,6 +265,11 @@ fn test_f32max() {
     let op = F32MAX;
     test_f32_binop(op, 1.1, 2.2, 2.2);
     test_f32_binop(op, -1.1, -2.2, -1.1);
+    test_f32_exact(op, [-0.0, 0.0], 0.0);
+    test_f32_exact(op, [0.0, -0.0], 0.0);
+    test_f32_exact(op, [f32::NAN, 1.0], f32::NAN);
+    test_f32_exact(op, [1.0, f32::NAN], f32::NAN);
+    test_f32_exact(op, [f32::INFINITY, f32::NAN], f32::NAN);
 }
 
 #[test]
@@ -182,4 +279,9 @@ fn test_f32copysign() {
     test_f32_binop(op, -1.1, -2.2, -1.1);
     test_f32_binop(op, -1.1, 1.1, 1.1);
     test_f32_binop(op, 1.1, -1.1, -1.1);
+    test_f32_exact(op, [3.0, -1.0], -3.0);
+    test_f32_exact(op, [-3.0, 1.0], 3.0);
+    test_f32_exact(op, [0.0, -0.0], -0.0);
+    test_f32_exact(op, [-0.0, 0.0], 0.0);
+    test_f32_exact(op, [1.5, -f32::NAN], -1.5);
 }
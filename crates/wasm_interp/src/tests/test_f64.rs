@@ -1,7 +1,7 @@
 #![cfg(test)]
 
-use super::test_op_example;
-use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value};
+use super::{run_op_example, test_op_example};
+use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value, ValueType};
 
 fn test_f64_comparison(op: OpCode, arg1: f64, arg2: f64, expected: bool) {
     test_op_example(
@@ -23,6 +23,34 @@ fn test_f64_unop(op: OpCode, arg: f64, expected: f64) {
     test_op_example(op, [Value::F64(arg)], Value::F64(expected))
 }
 
+/// Check the exact result, distinguishing -0.0 from +0.0. Any NaN is accepted for an expected NaN.
+fn test_f64_exact<A>(op: OpCode, args: A, expected: f64)
+where
+    A: IntoIterator<Item = f64>,
+{
+    let args = args.into_iter().map(Value::F64);
+    let result = run_op_example(op, args, ValueType::F64)
+        .unwrap()
+        .unwrap()
+        .expect_f64()
+        .unwrap();
+    if expected.is_nan() {
+        assert!(result.is_nan(), "{op:?} expected NaN but got {result:?}");
+    } else {
+        assert_eq!(result.to_bits(), expected.to_bits(), "{op:?} {result:?}");
+    }
+}
+
+/// Run a unary op on the raw bits of a float, to check the sign bit of NaN results
+fn test_f64_unop_bits(op: OpCode, arg: f64) -> u64 {
+    run_op_example(op, [Value::F64(arg)], ValueType::F64)
+        .unwrap()
+        .unwrap()
+        .expect_f64()
+        .unwrap()
+        .to_bits()
+}
+
 #[test]
 fn test_f64eq() {
     let op = F64EQ;
@@ -69,6 +97,26 @@ fn test_f64ge() {
     test_f64_comparison(op, -1.1, 1.1, false);
 }
 
+#[test]
+fn test_f64_compare_nan() {
+    // NaN is unordered: it's not equal to, less than or greater than anything, including itself
+    let nan = f64::NAN;
+    for (a, b) in [
+        (nan, 1.0),
+        (1.0, nan),
+        (nan, nan),
+        (nan, f64::INFINITY),
+        (-0.0, nan),
+    ] {
+        test_f64_comparison(F64EQ, a, b, false);
+        test_f64_comparison(F64NE, a, b, true);
+        test_f64_comparison(F64LT, a, b, false);
+        test_f64_comparison(F64GT, a, b, false);
+        test_f64_comparison(F64LE, a, b, false);
+        test_f64_comparison(F64GE, a, b, false);
+    }
+}
+
 #[test]
 fn test_f64abs() {
     let op = F64ABS;
@@ -85,6 +133,21 @@ fn test_f64neg() {
     test_f64_unop(op, -1.1, 1.1);
 }
 
+#[test]
+fn test_f64abs_neg_sign_bit() {
+    test_f64_exact(F64ABS, [-0.0], 0.0);
+    test_f64_exact(F64NEG, [0.0], -0.0);
+    test_f64_exact(F64NEG, [-0.0], 0.0);
+    test_f64_exact(F64ABS, [f64::NEG_INFINITY], f64::INFINITY);
+
+    // abs and neg only change the sign bit, even for NaN
+    let nan = f64::NAN;
+    let neg_nan = -f64::NAN;
+    assert_eq!(test_f64_unop_bits(F64NEG, nan), neg_nan.to_bits());
+    assert_eq!(test_f64_unop_bits(F64NEG, neg_nan), nan.to_bits());
+    assert_eq!(test_f64_unop_bits(F64ABS, neg_nan), nan.to_bits());
+}
+
 #[test]
 fn test_f64ceil() {
     let op = F64CEIL;
@@ -115,6 +178,12 @@ fn test_f64nearest() {
     test_f64_unop(op, -1.6, -2.0);
     test_f64_unop(op, 1.5, 2.0);
     test_f64_unop(op, 2.5, 2.0);
+    test_f64_unop(op, -2.5, -2.0);
+    test_f64_unop(op, -3.5, -4.0);
+    test_f64_exact(op, [0.5], 0.0);
+    test_f64_exact(op, [-0.5], -0.0);
+    test_f64_exact(op, [-0.4], -0.0);
+    test_f64_exact(op, [f64::NAN], f64::NAN);
 }
 
 #[test]
@@ -123,6 +192,24 @@ fn test_f64sqrt() {
     test_f64_unop(op, 4.0, 2.0);
 }
 
+#[test]
+fn test_f64sqrt_edge_cases() {
+    let op = F64SQRT;
+    test_f64_exact(op, [-1.0], f64::NAN);
+    test_f64_exact(op, [f64::NEG_INFINITY], f64::NAN);
+    test_f64_exact(op, [-0.0], -0.0);
+    test_f64_exact(op, [f64::INFINITY], f64::INFINITY);
+}
+
+#[test]
+fn test_f64_rounding_signed_zero() {
+    test_f64_exact(F64CEIL, [-0.5], -0.0);
+    test_f64_exact(F64TRUNC, [-0.5], -0.0);
+    test_f64_exact(F64FLOOR, [-0.5], -1.0);
+    test_f64_exact(F64FLOOR, [-0.0], -0.0);
+    test_f64_exact(F64FLOOR, [f64::NAN], f64::NAN);
+}
+
 #[test]
 fn test_f64add() {
     let op = F64ADD;
@@ -166,6 +253,11 @@ fn test_f64min() {
     let op = F64MIN;
     test_f64_binop(op, 1.1, 2.2, 1.1);
     test_f64_binop(op, -1.1, -2.2, -2.2);
+    test_f64_exact(op, [-0.0, 0.0], -0.0);
+    test_f64_exact(op, [0.0, -0.0], -0.0);
+    test_f64_exact(op, [f64::NAN, 1.0], f64::NAN);
+    test_f64_exact(op, [1.0, f64::NAN], f64::NAN);
+    test_f64_exact(op, [f64::NEG_INFINITY, f64::NAN], f64::NAN);
 }
 
 #[test]
@@ -173,6 +265,11 @@ fn test_f64max() {
     let op = F64MAX;
     test_f64_binop(op, 1.1, 2.2, 2.2);
     test_f64_binop(op, -1.1, -2.2, -1.1);
+    test_f64_exact(op, [-0.0, 0.0], 0.0);
+    test_f64_exact(op, [0.0, -0.0], 0.0);
+    test_f64_exact(op, [f64::NAN, 1.0], f64::NAN);
+    test_f64_exact(op, [1.0, f64::NAN], f64::NAN);
+    test_f64_exact(op, [f64::INFINITY, f64::NAN], f64::NAN);
 }
 
 #[test]
@@ -182,4 +279,9 @@ fn test_f64copysign() {
     test_f64_binop(op, -1.1, -2.2, -1.1);
     test_f64_binop(op, -1.1, 1.1, 1.1);
     test_f64_binop(op, 1.1, -1.1, -1.1);
+    test_f64_exact(op, [3.0, -1.0], -3.0);
+    test_f64_exact(op, [-3.0, 1.0], 3.0);
+    test_f64_exact(op, [0.0, -0.0], -0.0);
+    test_f64_exact(op, [-0.0, 0.0], 0.0);
+    test_f64_exact(op, [1.5, -f64::NAN], -1.5);
 }
@@ -0,0 +1,265 @@
+//! Randomized robustness test for the run loop: mutate the code of valid modules, and check
+//! that the interpreter never panics. Invalid code must stop with an error, like a trap does.
+//! Only the instructions are mutated. The other sections, and the local declarations at the start
+//! of each function, come from the compiler, so the module parser still trusts them.
+//!
+//! Each run uses a fixed random seed, so failures are reproducible. To search further, set
+//! `ROC_WASM_INTERP_FUZZ_ITERATIONS` to something bigger than the default, and optionally
+//! `ROC_WASM_INTERP_FUZZ_SEED` to start from a different random seed:
+//!
+//! ```sh
+//! ROC_WASM_INTERP_FUZZ_ITERATIONS=1000000 cargo test -p roc_wasm_interp --release fuzz
+//! ```
+//!
+//! When an input panics, the test prints its seed and iteration so it can be replayed.
+//! To cover more instructions, add a function to `seed_modules`.
+
+use bumpalo::Bump;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use roc_wasm_module::{opcodes::OpCode, parse::Parse, ExportType, Value, ValueType, WasmModule};
+use std::ops::Range;
+
+use crate::tests::builder::{Instr::*, ModuleBuilder};
+use crate::{ImportDispatcher, Instance};
+
+const DEFAULT_ITERATIONS: u64 = 1_000;
+const INSTRUCTION_BUDGET: u64 = 10_000;
+
+/// Every import returns nothing, whatever its signature says
+struct NullDispatcher;
+
+impl ImportDispatcher for NullDispatcher {
+    fn dispatch(
+        &mut self,
+        _module_name: &str,
+        _function_name: &str,
+        _arguments: &[Value],
+        _memory: &mut [u8],
+    ) -> Option<Value> {
+        None
+    }
+}
+
+/// Valid modules to mutate, covering the main kinds of instruction and section
+fn seed_modules() -> Vec<Vec<u8>> {
+    use OpCode::*;
+    use ValueType::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    builder.data(0x10, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    let global = builder.global(Value::I32(7), true);
+    let import = builder.import_function("env", "log", &[I32], None);
+    let sig = builder.signature(&[I32], Some(I32));
+
+    // Loop from n down to 0, storing to memory and calling an import
+    let countdown = builder.function(
+        "countdown",
+        &[I32],
+        Some(I32),
+        &[I32],
+        &[
+            Block(BLOCK, None),
+            Block(LOOP, None),
+            Index(GETLOCAL, 0),
+            Op(I32EQZ),
+            Index(BRIF, 1),
+            Index(GETLOCAL, 0),
+            Index(GETLOCAL, 0),
+            Mem(I32STORE, 0x20),
+            Index(GETLOCAL, 0),
+            Index(CALL, import),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(1)),
+            Op(I32SUB),
+            Index(TEELOCAL, 0),
+            Index(GETLOCAL, 1),
+            Op(I32ADD),
+            Index(SETLOCAL, 1),
+            Index(BR, 0),
+            Op(END),
+            Op(END),
+            Index(GETLOCAL, 1),
+            Op(END),
+        ],
+    );
+    // Arithmetic in every type, with conversions, selects and globals
+    builder.function(
+        "arith",
+        &[I32, I64],
+        Some(F64),
+        &[F32],
+        &[
+            Index(GETLOCAL, 0),
+            Index(GETGLOBAL, global),
+            Op(I32MUL),
+            Index(SETGLOBAL, global),
+            Index(GETLOCAL, 1),
+            Const(Value::I64(3)),
+            Op(I64DIVS),
+            Op(I64EXTENDUI32),
+            Op(DROP),
+            Const(Value::F32(1.5)),
+            Index(TEELOCAL, 2),
+            Op(F64PROMOTEF32),
+            Const(Value::F64(2.5)),
+            Index(GETLOCAL, 0),
+            Op(SELECT),
+            Op(F64SQRT),
+            Op(END),
+        ],
+    );
+    // Conditional branches, if/else, memory size and growth, and indirect calls
+    builder.function(
+        "control",
+        &[I32],
+        Some(I32),
+        &[],
+        &[
+            Block(BLOCK, Some(I32)),
+            Block(BLOCK, None),
+            Index(GETLOCAL, 0),
+            Block(IF, None),
+            Index(CURRENTMEMORY, 0),
+            Index(GROWMEMORY, 0),
+            Op(DROP),
+            Op(ELSE),
+            Const(Value::I32(0x10)),
+            Mem(I64LOAD, 0),
+            Op(DROP),
+            Op(END),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(0)),
+            CallIndirect(CALLINDIRECT, sig),
+            Op(DROP),
+            Const(Value::I32(5)),
+            Index(GETLOCAL, 0),
+            Index(BRIF, 1),
+            Op(DROP),
+            Op(NOP),
+            Op(END),
+            Const(Value::I32(6)),
+            Op(END),
+            Op(END),
+        ],
+    );
+    let mut module = builder.build();
+    module
+        .element
+        .segments
+        .push(roc_wasm_module::sections::ElementSegment::new(&arena));
+    module.element.get_or_insert_fn(countdown);
+
+    let mut bytes = Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    vec![bytes]
+}
+
+/// The file offsets of the instructions in each function body, after its local declarations
+fn instruction_ranges(bytes: &[u8]) -> Vec<Range<usize>> {
+    let arena = Bump::new();
+    let module = WasmModule::preload(&arena, bytes, false).unwrap();
+    let code = &module.code;
+    code.function_offsets
+        .iter()
+        .map(|offset| {
+            let mut cursor = *offset as usize;
+            let body_size = u32::parse((), &code.bytes, &mut cursor).unwrap() as usize;
+            let body_end = cursor + body_size;
+            let local_group_count = u32::parse((), &code.bytes, &mut cursor).unwrap();
+            for _ in 0..local_group_count {
+                <(u32, ValueType)>::parse((), &code.bytes, &mut cursor).unwrap();
+            }
+            let section_offset = code.section_offset as usize;
+            (section_offset + cursor)..(section_offset + body_end)
+        })
+        .collect()
+}
+
+fn mutate(rng: &mut StdRng, bytes: &mut [u8], ranges: &[Range<usize>]) {
+    for _ in 0..rng.gen_range(1..=4) {
+        let range = ranges[rng.gen_range(0..ranges.len())].clone();
+        let i = rng.gen_range(range);
+        match rng.gen_range(0..4) {
+            0 => bytes[i] = rng.gen(),
+            1 => bytes[i] ^= 1 << rng.gen_range(0..8),
+            2 => bytes[i] = bytes[i].wrapping_add(rng.gen_range(1..4)),
+            _ => bytes[i] = [0, 0x7f, 0x80, 0xff][rng.gen_range(0..4)],
+        }
+    }
+}
+
+fn zero(ty: ValueType) -> Value {
+    match ty {
+        ValueType::I32 => Value::I32(0),
+        ValueType::I64 => Value::I64(0),
+        ValueType::F32 => Value::F32(0.0),
+        ValueType::F64 => Value::F64(0.0),
+    }
+}
+
+/// Load and run a module. Anything is fine, except a panic.
+fn run_module(bytes: &[u8]) {
+    let arena = Bump::new();
+    let module = match WasmModule::preload(&arena, bytes, false) {
+        Ok(module) => module,
+        Err(_) => return,
+    };
+    let mut inst = match Instance::for_module(&arena, &module, NullDispatcher, false) {
+        Ok(inst) => inst,
+        Err(_) => return,
+    };
+
+    let import_count = module.import.function_count();
+    for export in module.export.exports.iter() {
+        let internal_fn_index = match (export.index as usize).checked_sub(import_count) {
+            Some(i) if export.ty == ExportType::Func => i,
+            _ => continue,
+        };
+        let signature_index = match module.function.signatures.get(internal_fn_index) {
+            Some(i) => *i,
+            None => continue,
+        };
+        let (param_types, _) = module.types.look_up(signature_index);
+        let args: Vec<Value> = param_types.map(zero).collect();
+
+        if inst.reset().is_err() {
+            return;
+        }
+        if inst.start_export(export.name, args).is_ok() {
+            let _ = inst.run_n(INSTRUCTION_BUDGET);
+        }
+    }
+}
+
+#[test]
+fn fuzz_mutated_modules() {
+    let iterations = std::env::var("ROC_WASM_INTERP_FUZZ_ITERATIONS")
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(DEFAULT_ITERATIONS);
+    let seed = std::env::var("ROC_WASM_INTERP_FUZZ_SEED")
+        .map(|s| s.parse().unwrap())
+        .unwrap_or(0);
+
+    let seeds: Vec<_> = seed_modules()
+        .into_iter()
+        .map(|bytes| {
+            run_module(&bytes);
+            let ranges = instruction_ranges(&bytes);
+            (bytes, ranges)
+        })
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for iteration in 0..iterations {
+        let (seed_bytes, ranges) = &seeds[rng.gen_range(0..seeds.len())];
+        let mut bytes = seed_bytes.clone();
+        mutate(&mut rng, &mut bytes, ranges);
+        let result = std::panic::catch_unwind(|| run_module(&bytes));
+        assert!(
+            result.is_ok(),
+            "Panicked at iteration {iteration} with ROC_WASM_INTERP_FUZZ_SEED={seed}"
+        );
+    }
+}
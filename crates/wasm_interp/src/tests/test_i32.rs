@@ -1,7 +1,7 @@
 #![cfg(test)]
 
-use super::test_op_example;
-use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value};
+use super::{test_op_error, test_op_example};
+use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value, ValueType};
 
 fn test_i32_binop(op: OpCode, arg1: i32, arg2: i32, expected: i32) {
     test_op_example(
@@ -131,6 +131,24 @@ fn test_i32geu() {
     test_u32_binop(op, u32::MAX, u32::MIN, 1);
 }
 
+#[test]
+fn test_i32_compare_signed_vs_unsigned() {
+    // -1 is the smallest of the two as a signed number, but the largest as unsigned
+    let (a, b) = (-1, 1);
+    test_i32_binop(I32LTS, a, b, 1);
+    test_i32_binop(I32LTU, a, b, 0);
+    test_i32_binop(I32GTS, a, b, 0);
+    test_i32_binop(I32GTU, a, b, 1);
+    test_i32_binop(I32LES, a, b, 1);
+    test_i32_binop(I32LEU, a, b, 0);
+    test_i32_binop(I32GES, a, b, 0);
+    test_i32_binop(I32GEU, a, b, 1);
+
+    // i32::MIN is the most negative signed number, but above i32::MAX as unsigned
+    test_i32_binop(I32LTS, i32::MIN, i32::MAX, 1);
+    test_i32_binop(I32LTU, i32::MIN, i32::MAX, 0);
+}
+
 #[test]
 fn test_i32clz() {
     let op = I32CLZ;
@@ -167,6 +185,13 @@ fn test_i32add() {
     test_i32_binop(op, i32::MAX, 1, i32::MIN);
 }
 
+#[test]
+fn test_i32add_type_mismatch() {
+    // The reported type comes from the actual value on the stack
+    let message = test_op_error(I32ADD, [Value::I32(1), Value::F64(2.0)], ValueType::I32);
+    assert!(message.contains("Expected I32, but found F64"), "{message}");
+}
+
 #[test]
 fn test_i32sub() {
     let op = I32SUB;
@@ -190,13 +215,20 @@ fn test_i32divs() {
     let op = I32DIVS;
     test_i32_binop(op, -1, -1, 1);
     test_i32_binop(op, 6, 3, 2);
-    test_i32_binop(op, i32::MIN, -1, i32::MIN);
 }
 
 #[test]
-#[should_panic(expected = "divide by zero")]
+fn test_i32divs_overflow() {
+    let args = [Value::from(i32::MIN), Value::from(-1i32)];
+    let message = test_op_error(I32DIVS, args, ValueType::I32);
+    assert!(message.contains("integer overflow"), "{message}");
+}
+
+#[test]
 fn test_i32divs_zero() {
-    test_i32_binop(I32DIVS, 1, 0, -1);
+    let args = [Value::from(1i32), Value::from(0i32)];
+    let message = test_op_error(I32DIVS, args, ValueType::I32);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -207,9 +239,10 @@ fn test_i32divu() {
 }
 
 #[test]
-#[should_panic(expected = "divide by zero")]
 fn test_i32divu_zero() {
-    test_i32_binop(I32DIVU, 1, 0, -1);
+    let args = [Value::from(1i32), Value::from(0i32)];
+    let message = test_op_error(I32DIVU, args, ValueType::I32);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -218,13 +251,15 @@ fn test_i32rems() {
     test_i32_binop(op, 5, 2, 1);
     // test_i32_binop(op, 5, -2, 1); // TODO: we don't match Wasmer, we get 0
     test_i32_binop(op, -5, 2, -1);
+    test_i32_binop(op, i32::MIN, -1, 0);
     // test_i32_binop(op, -5, -2, -1); // TODO: we don't match Wasmer, we get 0
 }
 
 #[test]
-#[should_panic(expected = "divisor of zero")]
 fn test_i32rems_zero() {
-    test_i32_binop(I32REMS, 1, 0, -1);
+    let args = [Value::from(1i32), Value::from(0i32)];
+    let message = test_op_error(I32REMS, args, ValueType::I32);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -234,9 +269,10 @@ fn test_i32remu() {
 }
 
 #[test]
-#[should_panic(expected = "divisor of zero")]
 fn test_i32remu_zero() {
-    test_i32_binop(I32REMU, 1, 0, -1);
+    let args = [Value::from(1i32), Value::from(0i32)];
+    let message = test_op_error(I32REMU, args, ValueType::I32);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -258,28 +294,53 @@ fn test_i32xor() {
 fn test_i32shl() {
     test_u32_binop(I32SHL, 0xffff_ffff, 8, 0xffff_ff00);
     test_u32_binop(I32SHL, 0xffff_ffff, 40, 0xffff_ff00);
+    test_u32_binop(I32SHL, 0x1234_5678, 32, 0x1234_5678);
+    test_u32_binop(I32SHL, 0x1234_5678, 0xffff_ffe4, 0x2345_6780);
 }
 
 #[test]
 fn test_i32shrs() {
     test_u32_binop(I32SHRS, 0xffff_0000, 8, 0xffff_ff00);
     test_u32_binop(I32SHRS, 0xffff_0000, 40, 0xffff_ff00);
+    test_u32_binop(I32SHRS, 0xffff_0000, 32, 0xffff_0000);
+    // The shift amount is unsigned, so this is a shift by 33 % 32 = 1, not by -31
+    test_u32_binop(I32SHRS, 0xffff_0000, 0x8000_0021, 0xffff_8000);
+    test_i32_binop(I32SHRS, i32::MIN, 31, -1);
+    test_i32_binop(I32SHRS, -100, 2, -25);
+    test_i32_binop(I32SHRS, 100, 2, 25);
 }
 
 #[test]
 fn test_i32shru() {
     test_u32_binop(I32SHRU, 0xffff_0000, 8, 0x00ff_ff00);
     test_u32_binop(I32SHRU, 0xffff_0000, 40, 0x00ff_ff00);
+    test_u32_binop(I32SHRU, 0xffff_0000, 32, 0xffff_0000);
+    test_u32_binop(I32SHRU, 0xffff_0000, 0x8000_0021, 0x7fff_8000);
+    test_u32_binop(I32SHRU, 0x8000_0000, 31, 1);
 }
 
 #[test]
 fn test_i32rotl() {
     test_u32_binop(I32ROTL, 0xff00_0000, 4, 0xf000_000f);
     test_u32_binop(I32ROTL, 0xff00_0000, 36, 0xf000_000f);
+    test_u32_binop(I32ROTL, 0x1234_5678, 0, 0x1234_5678);
+    test_u32_binop(I32ROTL, 0x1234_5678, 32, 0x1234_5678);
+    test_u32_binop(I32ROTL, 0x8000_0001, u32::MAX, 0xc000_0000);
+    test_u32_binop(I32ROTL, 0, 7, 0);
 }
 
 #[test]
 fn test_i32rotr() {
     test_u32_binop(I32ROTR, 0x0000_00ff, 4, 0xf000_000f);
     test_u32_binop(I32ROTR, 0x0000_00ff, 36, 0xf000_000f);
+    test_u32_binop(I32ROTR, 0x1234_5678, 0, 0x1234_5678);
+    test_u32_binop(I32ROTR, 0x1234_5678, 32, 0x1234_5678);
+    test_u32_binop(I32ROTR, 0x8000_0001, u32::MAX, 0x0000_0003);
+    test_u32_binop(I32ROTR, 0, 7, 0);
+}
+
+#[test]
+fn test_i32rotl_type_mismatch() {
+    let message = test_op_error(I32ROTL, [Value::I64(1), Value::I32(1)], ValueType::I32);
+    assert!(message.contains("type mismatch"), "{message}");
 }
@@ -1,7 +1,7 @@
 #![cfg(test)]
 
-use super::test_op_example;
-use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value};
+use super::{test_op_error, test_op_example};
+use roc_wasm_module::{opcodes::OpCode, opcodes::OpCode::*, Value, ValueType};
 
 fn test_i64_comparison(op: OpCode, arg1: i64, arg2: i64, expected: bool) {
     test_op_example(
@@ -147,6 +147,24 @@ fn test_i64geu() {
     test_u64_comparison(op, u64::MAX, u64::MIN, true);
 }
 
+#[test]
+fn test_i64_compare_signed_vs_unsigned() {
+    // -1 is the smallest of the two as a signed number, but the largest as unsigned
+    let (a, b) = (-1, 1);
+    test_i64_comparison(I64LTS, a, b, true);
+    test_i64_comparison(I64LTU, a, b, false);
+    test_i64_comparison(I64GTS, a, b, false);
+    test_i64_comparison(I64GTU, a, b, true);
+    test_i64_comparison(I64LES, a, b, true);
+    test_i64_comparison(I64LEU, a, b, false);
+    test_i64_comparison(I64GES, a, b, false);
+    test_i64_comparison(I64GEU, a, b, true);
+
+    // i64::MIN is the most negative signed number, but above i64::MAX as unsigned
+    test_i64_comparison(I64LTS, i64::MIN, i64::MAX, true);
+    test_i64_comparison(I64LTU, i64::MIN, i64::MAX, false);
+}
+
 #[test]
 fn test_i64clz() {
     let op = I64CLZ;
@@ -206,13 +224,20 @@ fn test_i64divs() {
     let op = I64DIVS;
     test_i64_binop(op, -1, -1, 1);
     test_i64_binop(op, 6, 3, 2);
-    test_i64_binop(op, i64::MIN, -1, i64::MIN);
 }
 
 #[test]
-#[should_panic(expected = "divide by zero")]
+fn test_i64divs_overflow() {
+    let args = [Value::from(i64::MIN), Value::from(-1i64)];
+    let message = test_op_error(I64DIVS, args, ValueType::I64);
+    assert!(message.contains("integer overflow"), "{message}");
+}
+
+#[test]
 fn test_i64divs_zero() {
-    test_i64_binop(I64DIVS, 1, 0, -1);
+    let args = [Value::from(1i64), Value::from(0i64)];
+    let message = test_op_error(I64DIVS, args, ValueType::I64);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -223,9 +248,10 @@ fn test_i64divu() {
 }
 
 #[test]
-#[should_panic(expected = "divide by zero")]
 fn test_i64divu_zero() {
-    test_i64_binop(I64DIVU, 1, 0, -1);
+    let args = [Value::from(1i64), Value::from(0i64)];
+    let message = test_op_error(I64DIVU, args, ValueType::I64);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -234,13 +260,15 @@ fn test_i64rems() {
     test_i64_binop(op, 5, 2, 1);
     // test_i64_binop(op, 5, -2, 1); // TODO: we don't match Wasmer, we get 0
     test_i64_binop(op, -5, 2, -1);
+    test_i64_binop(op, i64::MIN, -1, 0);
     // test_i64_binop(op, -5, -2, -1); // TODO: we don't match Wasmer, we get 0
 }
 
 #[test]
-#[should_panic(expected = "divisor of zero")]
 fn test_i64rems_zero() {
-    test_i64_binop(I64REMS, 1, 0, -1);
+    let args = [Value::from(1i64), Value::from(0i64)];
+    let message = test_op_error(I64REMS, args, ValueType::I64);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -250,9 +278,10 @@ fn test_i64remu() {
 }
 
 #[test]
-#[should_panic(expected = "divisor of zero")]
 fn test_i64remu_zero() {
-    test_i64_binop(I64REMU, 1, 0, -1);
+    let args = [Value::from(1i64), Value::from(0i64)];
+    let message = test_op_error(I64REMU, args, ValueType::I64);
+    assert!(message.contains("integer divide by zero"), "{message}");
 }
 
 #[test]
@@ -289,28 +318,72 @@ fn test_i64xor() {
 fn test_i64shl() {
     test_u64_binop(I64SHL, 0xffff_ffff_ffff_ffff, 8, 0xffff_ffff_ffff_ff00);
     test_u64_binop(I64SHL, 0xffff_ffff_ffff_ffff, 72, 0xffff_ffff_ffff_ff00);
+    test_u64_binop(I64SHL, 0x1234_5678, 64, 0x1234_5678);
+    test_u64_binop(I64SHL, 0x1234_5678, 0xffff_ffff_ffff_ffc4, 0x1_2345_6780);
 }
 
 #[test]
 fn test_i64shrs() {
     test_u64_binop(I64SHRS, 0xffff_ffff_0000_0000, 8, 0xffff_ffff_ff00_0000);
     test_u64_binop(I64SHRS, 0xffff_ffff_0000_0000, 72, 0xffff_ffff_ff00_0000);
+    test_u64_binop(I64SHRS, 0xffff_ffff_0000_0000, 64, 0xffff_ffff_0000_0000);
+    // The shift amount is unsigned, so this is a shift by 65 % 64 = 1, not by -63
+    test_u64_binop(
+        I64SHRS,
+        0xffff_ffff_0000_0000,
+        0x8000_0000_0000_0041,
+        0xffff_ffff_8000_0000,
+    );
+    test_i64_binop(I64SHRS, i64::MIN, 63, -1);
+    test_i64_binop(I64SHRS, -100, 2, -25);
 }
 
 #[test]
 fn test_i64shru() {
     test_u64_binop(I64SHRU, 0xffff_ffff_0000_0000, 8, 0x00ff_ffff_ff00_0000);
     test_u64_binop(I64SHRU, 0xffff_ffff_0000_0000, 72, 0x00ff_ffff_ff00_0000);
+    test_u64_binop(I64SHRU, 0xffff_ffff_0000_0000, 64, 0xffff_ffff_0000_0000);
+    test_u64_binop(
+        I64SHRU,
+        0xffff_ffff_0000_0000,
+        0x8000_0000_0000_0041,
+        0x7fff_ffff_8000_0000,
+    );
+    test_u64_binop(I64SHRU, 0x8000_0000_0000_0000, 63, 1);
 }
 
 #[test]
 fn test_i64rotl() {
     test_u64_binop(I64ROTL, 0xff00_0000_0000_0000, 4, 0xf000_0000_0000_000f);
     test_u64_binop(I64ROTL, 0xff00_0000_0000_0000, 68, 0xf000_0000_0000_000f);
+    test_u64_binop(I64ROTL, 0x1234_5678_9abc_def0, 0, 0x1234_5678_9abc_def0);
+    test_u64_binop(I64ROTL, 0x1234_5678_9abc_def0, 64, 0x1234_5678_9abc_def0);
+    test_u64_binop(
+        I64ROTL,
+        0x8000_0000_0000_0001,
+        u64::MAX,
+        0xc000_0000_0000_0000,
+    );
+    test_u64_binop(I64ROTL, 0, 7, 0);
 }
 
 #[test]
 fn test_i64rotr() {
     test_u64_binop(I64ROTR, 0x0000_0000_0000_00ff, 4, 0xf000_0000_0000_000f);
     test_u64_binop(I64ROTR, 0x0000_0000_0000_00ff, 68, 0xf000_0000_0000_000f);
+    test_u64_binop(I64ROTR, 0x1234_5678_9abc_def0, 0, 0x1234_5678_9abc_def0);
+    test_u64_binop(I64ROTR, 0x1234_5678_9abc_def0, 64, 0x1234_5678_9abc_def0);
+    test_u64_binop(
+        I64ROTR,
+        0x8000_0000_0000_0001,
+        u64::MAX,
+        0x0000_0000_0000_0003,
+    );
+    test_u64_binop(I64ROTR, 0, 7, 0);
+}
+
+#[test]
+fn test_i64clz_type_mismatch() {
+    let message = test_op_error(I64CLZ, [Value::I32(1)], ValueType::I64);
+    assert!(message.contains("type mismatch"), "{message}");
 }
@@ -1,10 +1,11 @@
+use super::builder::{Instr, ModuleBuilder};
 use super::create_exported_function_no_locals;
-use crate::{DefaultImportDispatcher, Instance};
+use crate::{DefaultImportDispatcher, Error, Instance, TrapKind};
 use bumpalo::{collections::Vec, Bump};
 use roc_wasm_module::{
-    opcodes::OpCode,
+    opcodes::{AtomicInstruction, MemoryInstruction, OpCode},
     sections::{DataMode, DataSegment, MemorySection},
-    ConstExpr, SerialBuffer, Signature, Value, ValueType, WasmModule,
+    ConstExpr, Global, GlobalType, SerialBuffer, Signature, Value, ValueType, WasmModule,
 };
 
 #[test]
@@ -20,7 +21,7 @@ fn test_currentmemory() {
 
     let mut state = Instance::new(&arena, pages, pc, [], DefaultImportDispatcher::default());
     state.execute_next_instruction(&module).unwrap();
-    assert_eq!(state.value_store.pop(), Value::I32(3))
+    assert_eq!(state.value_store.pop(), Ok(Value::I32(3)))
 }
 
 #[test]
@@ -49,6 +50,77 @@ fn test_growmemory() {
     assert_eq!(state.memory.len(), 5 * MemorySection::PAGE_SIZE as usize);
 }
 
+#[test]
+fn test_growmemory_host_limit() {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+
+    // The module declares 1 page and no maximum
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena; ValueType::I32],
+        ret_type: Some(ValueType::I32),
+    };
+    create_exported_function_no_locals(&mut module, "grow", signature, |buf| {
+        buf.append_u8(OpCode::GETLOCAL as u8);
+        buf.encode_u32(0);
+        buf.append_u8(OpCode::GROWMEMORY as u8);
+        buf.encode_u32(0);
+        buf.append_u8(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_max_memory_pages(2);
+
+    let grow_beyond_limit = inst.call_export("grow", [Value::I32(2)]).unwrap();
+    assert_eq!(grow_beyond_limit, Some(Value::I32(-1)));
+    assert_eq!(inst.memory.len(), MemorySection::PAGE_SIZE as usize);
+
+    let grow_to_limit = inst.call_export("grow", [Value::I32(1)]).unwrap();
+    assert_eq!(grow_to_limit, Some(Value::I32(1)));
+    assert_eq!(inst.memory.len(), 2 * MemorySection::PAGE_SIZE as usize);
+
+    let grow_again = inst.call_export("grow", [Value::I32(1)]).unwrap();
+    assert_eq!(grow_again, Some(Value::I32(-1)));
+}
+
+#[test]
+fn test_grow_memory_from_host() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    builder.function(
+        "load",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[Index(GETLOCAL, 0), Mem(I32LOAD, 0), Op(END)],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_max_memory_pages(3);
+
+    let addr = 2 * MemorySection::PAGE_SIZE as i32 + 100;
+    assert!(inst.call_export("load", [Value::I32(addr)]).is_err());
+
+    assert_eq!(inst.grow_memory(2), Ok(1));
+    assert_eq!(inst.memory.len(), 3 * MemorySection::PAGE_SIZE as usize);
+    assert_eq!(
+        inst.call_export("load", [Value::I32(addr)]),
+        Ok(Some(Value::I32(0)))
+    );
+
+    assert!(inst.grow_memory(1).is_err());
+    assert_eq!(inst.memory.len(), 3 * MemorySection::PAGE_SIZE as usize);
+    assert_eq!(inst.grow_memory(0), Ok(3));
+}
+
 #[test]
 fn test_memory_fill() {
     let arena = Bump::new();
@@ -119,6 +191,193 @@ fn test_memory_copy() {
     assert_eq!(dest_slice, &[0xAA; SIZE as usize])
 }
 
+/// Run `memory.copy` on a page of memory filled with 0, 1, 2, ... and return the memory after it
+fn run_memory_copy(destination: u32, source: u32, size: u32) -> Result<std::vec::Vec<u8>, Error> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+    module
+        .code
+        .bytes
+        .extend([OpCode::MEMORY as u8, 10, 0x0, 0x0]);
+
+    let mut state = Instance::new(&arena, 1, 0, [], DefaultImportDispatcher::default());
+    for (i, byte) in state.memory.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    state.value_store.push(Value::I32(destination as i32));
+    state.value_store.push(Value::I32(source as i32));
+    state.value_store.push(Value::I32(size as i32));
+
+    state.execute_next_instruction(&module)?;
+    Ok(state.memory.to_vec())
+}
+
+#[test]
+fn test_memory_copy_overlapping() {
+    // The spec says the result is as if the bytes were copied through a temporary buffer
+    for (destination, source, size) in [(0x10, 0x14, 12), (0x14, 0x10, 12), (0x10, 0x10, 8)] {
+        let mut expected: std::vec::Vec<u8> = (0..MemorySection::PAGE_SIZE as usize)
+            .map(|i| i as u8)
+            .collect();
+        let temporary = expected[source..][..size].to_vec();
+        expected[destination..][..size].copy_from_slice(&temporary);
+
+        let memory = run_memory_copy(destination as u32, source as u32, size as u32).unwrap();
+        assert_eq!(
+            memory[..0x40],
+            expected[..0x40],
+            "copy {size} bytes from {source:#x} to {destination:#x}"
+        );
+    }
+}
+
+#[test]
+fn test_memory_copy_out_of_bounds() {
+    let page_size = MemorySection::PAGE_SIZE;
+    assert_eq!(
+        run_memory_copy(0, page_size - 4, 8),
+        Err(Error::Trap(TrapKind::MemoryOutOfBounds(
+            page_size + 3,
+            page_size
+        )))
+    );
+    assert_eq!(
+        run_memory_copy(page_size - 4, 0, 8),
+        Err(Error::Trap(TrapKind::MemoryOutOfBounds(
+            page_size + 3,
+            page_size
+        )))
+    );
+    // The addition mustn't wrap around to a valid address
+    assert!(run_memory_copy(0, u32::MAX, 2).is_err());
+    assert!(run_memory_copy(u32::MAX, 0, 2).is_err());
+    // Zero bytes at the end of memory is fine, but not beyond it
+    assert!(run_memory_copy(page_size, 0, 0).is_ok());
+    assert!(run_memory_copy(page_size + 1, 0, 0).is_err());
+}
+
+#[test]
+fn test_load_from_empty_memory() {
+    use Instr::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(0);
+    builder.function(
+        "load",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Const(Value::I32(0)),
+            Mem(OpCode::I32LOAD, 0),
+            Op(OpCode::END),
+        ],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let error = inst.call_export("load", []).unwrap_err();
+    assert!(error.contains("memory is 0x0 bytes"), "{error}");
+}
+
+const PASSIVE_DATA: &[u8] = b"passive!";
+
+/// Run `memory.init` on a passive segment, returning the instance's memory.
+/// There's also an active segment at index 0, to make sure it isn't confused with the passive one.
+fn run_memory_init(
+    destination: u32,
+    source: u32,
+    size: u32,
+    drop_first: bool,
+) -> Result<std::vec::Vec<u8>, String> {
+    let arena = Bump::new();
+    let mut module = WasmModule::new(&arena);
+    let start_fn_name = "test";
+
+    module.memory = MemorySection::new(&arena, MemorySection::PAGE_SIZE);
+
+    module.data.append_segment(DataSegment {
+        mode: DataMode::active_at(0),
+        init: Vec::from_iter_in(b"active".iter().copied(), &arena),
+    });
+    let passive_index = module.data.append_segment(DataSegment {
+        mode: DataMode::Passive,
+        init: Vec::from_iter_in(PASSIVE_DATA.iter().copied(), &arena),
+    });
+
+    let signature = Signature {
+        param_types: bumpalo::vec![in &arena],
+        ret_type: None,
+    };
+
+    create_exported_function_no_locals(&mut module, start_fn_name, signature, |buf| {
+        if drop_first {
+            buf.append_u8(OpCode::MEMORY as u8);
+            buf.append_u8(MemoryInstruction::DataDrop as u8);
+            buf.encode_u32(passive_index);
+        }
+        buf.append_u8(OpCode::I32CONST as u8);
+        buf.encode_u32(destination);
+        buf.append_u8(OpCode::I32CONST as u8);
+        buf.encode_u32(source);
+        buf.append_u8(OpCode::I32CONST as u8);
+        buf.encode_u32(size);
+        buf.append_u8(OpCode::MEMORY as u8);
+        buf.append_u8(MemoryInstruction::MemoryInit as u8);
+        buf.encode_u32(passive_index);
+        buf.append_u8(0); // memory index
+        buf.append_u8(OpCode::END as u8);
+    });
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // passive segments are not loaded at instantiation
+    assert_eq!(&inst.memory[..8], b"active\0\0");
+
+    inst.call_export(start_fn_name, [])?;
+    Ok(inst.memory.to_vec())
+}
+
+#[test]
+fn test_memory_init() {
+    let memory = run_memory_init(0x10, 1, 6, false).unwrap();
+    assert_eq!(&memory[0x10..][..6], b"assive");
+    assert_eq!(&memory[..6], b"active");
+}
+
+#[test]
+fn test_memory_init_out_of_bounds() {
+    // reading past the end of the segment
+    let error = run_memory_init(0x10, 4, 5, false).unwrap_err();
+    assert!(error.contains("data segment 1"), "{error}");
+
+    // writing past the end of memory
+    let error = run_memory_init(MemorySection::PAGE_SIZE - 2, 0, 4, false).unwrap_err();
+    assert!(error.contains("tried to access memory"), "{error}");
+
+    // zero-length init at the very end of the segment and memory is allowed
+    run_memory_init(
+        MemorySection::PAGE_SIZE,
+        PASSIVE_DATA.len() as u32,
+        0,
+        false,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_memory_init_after_data_drop() {
+    let error = run_memory_init(0x10, 0, 1, true).unwrap_err();
+    assert!(error.contains("data segment 1"), "{error}");
+
+    // a dropped segment behaves like an empty one
+    run_memory_init(0x10, 0, 0, true).unwrap();
+}
+
 fn test_load(load_op: OpCode, ty: ValueType, data: &[u8], addr: u32, offset: u32) -> Value {
     let arena = Bump::new();
     let mut module = WasmModule::new(&arena);
@@ -130,6 +389,7 @@ fn test_load(load_op: OpCode, ty: ValueType, data: &[u8], addr: u32, offset: u32
 
     module.data.append_segment(DataSegment {
         mode: DataMode::Active {
+            memory_index: 0,
             offset: ConstExpr::I32(addr as i32),
         },
         init: Vec::from_iter_in(data.iter().copied(), &arena),
@@ -213,21 +473,19 @@ fn test_f64load() {
 
 #[test]
 fn test_i32load8s() {
-    let value: i8 = -42;
-    let bytes = value.to_le_bytes();
+    let bytes = [0xf0];
     assert_eq!(
         test_load(OpCode::I32LOAD8S, ValueType::I32, &bytes, 0x11, 0),
-        Value::I32(value as i32)
+        Value::I32(-16)
     );
 }
 
 #[test]
 fn test_i32load8u() {
-    let value: u8 = 42;
-    let bytes = value.to_le_bytes();
+    let bytes = [0xf0];
     assert_eq!(
         test_load(OpCode::I32LOAD8U, ValueType::I32, &bytes, 0x11, 0),
-        Value::I32(value as i32)
+        Value::I32(0xf0)
     );
 }
 
@@ -243,31 +501,29 @@ fn test_i32load16s() {
 
 #[test]
 fn test_i32load16u() {
-    let value: u16 = 42;
+    let value: i16 = -42;
     let bytes = value.to_le_bytes();
     assert_eq!(
         test_load(OpCode::I32LOAD16U, ValueType::I32, &bytes, 0x11, 0),
-        Value::I32(value as i32)
+        Value::I32(value as u16 as i32)
     );
 }
 
 #[test]
 fn test_i64load8s() {
-    let value: i8 = -42;
-    let bytes = value.to_le_bytes();
+    let bytes = [0xf0];
     assert_eq!(
         test_load(OpCode::I64LOAD8S, ValueType::I64, &bytes, 0x11, 0),
-        Value::I64(value as i64)
+        Value::I64(-16)
     );
 }
 
 #[test]
 fn test_i64load8u() {
-    let value: u8 = 42;
-    let bytes = value.to_le_bytes();
+    let bytes = [0xf0];
     assert_eq!(
-        test_load(OpCode::I32LOAD8U, ValueType::I32, &bytes, 0x11, 0),
-        Value::I32(value as i32)
+        test_load(OpCode::I64LOAD8U, ValueType::I64, &bytes, 0x11, 0),
+        Value::I64(0xf0)
     );
 }
 
@@ -276,18 +532,18 @@ fn test_i64load16s() {
     let value: i16 = -42;
     let bytes = value.to_le_bytes();
     assert_eq!(
-        test_load(OpCode::I64LOAD8S, ValueType::I64, &bytes, 0x11, 0),
+        test_load(OpCode::I64LOAD16S, ValueType::I64, &bytes, 0x11, 0),
         Value::I64(value as i64)
     );
 }
 
 #[test]
 fn test_i64load16u() {
-    let value: u16 = 42;
+    let value: i16 = -42;
     let bytes = value.to_le_bytes();
     assert_eq!(
-        test_load(OpCode::I32LOAD8U, ValueType::I32, &bytes, 0x11, 0),
-        Value::I32(value as i32)
+        test_load(OpCode::I64LOAD16U, ValueType::I64, &bytes, 0x11, 0),
+        Value::I64(value as u16 as i64)
     );
 }
 
@@ -296,19 +552,245 @@ fn test_i64load32s() {
     let value: i32 = -42;
     let bytes = value.to_le_bytes();
     assert_eq!(
-        test_load(OpCode::I64LOAD8S, ValueType::I64, &bytes, 0x11, 0),
+        test_load(OpCode::I64LOAD32S, ValueType::I64, &bytes, 0x11, 0),
         Value::I64(value as i64)
     );
 }
 
 #[test]
 fn test_i64load32u() {
-    let value: u32 = 42;
+    let value: i32 = -42;
     let bytes = value.to_le_bytes();
     assert_eq!(
-        test_load(OpCode::I32LOAD8U, ValueType::I32, &bytes, 0x11, 0),
-        Value::I32(value as i32)
+        test_load(OpCode::I64LOAD32U, ValueType::I64, &bytes, 0x11, 0),
+        Value::I64(value as u32 as i64)
+    );
+}
+
+/// Store a value at address 0, then load it back with a different instruction
+fn test_store_then_load(store_op: OpCode, value: Value, load_op: OpCode) -> Value {
+    use Instr::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+
+    let ret_type = match load_op {
+        OpCode::I32LOAD8S | OpCode::I32LOAD8U | OpCode::I32LOAD16S | OpCode::I32LOAD16U => {
+            ValueType::I32
+        }
+        _ => ValueType::I64,
+    };
+    builder.function(
+        "test",
+        &[],
+        Some(ret_type),
+        &[],
+        &[
+            Const(Value::I32(0)),
+            Const(value),
+            Mem(store_op, 0),
+            Const(Value::I32(0)),
+            Mem(load_op, 0),
+            Op(OpCode::END),
+        ],
     );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.call_export("test", []).unwrap().unwrap()
+}
+
+#[test]
+fn test_partial_width_store_then_load() {
+    use OpCode::*;
+
+    // Stores truncate, so only the low bits of these values are kept
+    let i32_value = Value::I32(0x1234_80f0);
+    let i64_value = Value::I64(0x1234_5678_8000_80f0);
+
+    let cases = [
+        (I32STORE8, i32_value, I32LOAD8S, Value::I32(-16)),
+        (I32STORE8, i32_value, I32LOAD8U, Value::I32(0xf0)),
+        (I32STORE16, i32_value, I32LOAD16S, Value::I32(-0x7f10)),
+        (I32STORE16, i32_value, I32LOAD16U, Value::I32(0x80f0)),
+        (I64STORE8, i64_value, I64LOAD8S, Value::I64(-16)),
+        (I64STORE8, i64_value, I64LOAD8U, Value::I64(0xf0)),
+        (I64STORE16, i64_value, I64LOAD16S, Value::I64(-0x7f10)),
+        (I64STORE16, i64_value, I64LOAD16U, Value::I64(0x80f0)),
+        (I64STORE32, i64_value, I64LOAD32S, Value::I64(-0x7fff_7f10)),
+        (I64STORE32, i64_value, I64LOAD32U, Value::I64(0x8000_80f0)),
+    ];
+
+    for (store_op, value, load_op, expected) in cases {
+        assert_eq!(
+            test_store_then_load(store_op, value, load_op),
+            expected,
+            "{store_op:?} then {load_op:?}"
+        );
+    }
+}
+
+const LOADS: [OpCode; 14] = {
+    use OpCode::*;
+    [
+        I32LOAD, I64LOAD, F32LOAD, F64LOAD, I32LOAD8S, I32LOAD8U, I32LOAD16S, I32LOAD16U,
+        I64LOAD8S, I64LOAD8U, I64LOAD16S, I64LOAD16U, I64LOAD32S, I64LOAD32U,
+    ]
+};
+
+const STORES: [OpCode; 9] = {
+    use OpCode::*;
+    [
+        I32STORE, I64STORE, F32STORE, F64STORE, I32STORE8, I32STORE16, I64STORE8, I64STORE16,
+        I64STORE32,
+    ]
+};
+
+/// The type of value that a load produces, or a store consumes
+fn memory_op_type(op: OpCode) -> ValueType {
+    use OpCode::*;
+    match op {
+        I32LOAD | I32LOAD8S | I32LOAD8U | I32LOAD16S | I32LOAD16U | I32STORE | I32STORE8
+        | I32STORE16 => ValueType::I32,
+        I64LOAD | I64LOAD8S | I64LOAD8U | I64LOAD16S | I64LOAD16U | I64LOAD32S | I64LOAD32U
+        | I64STORE | I64STORE8 | I64STORE16 | I64STORE32 => ValueType::I64,
+        F32LOAD | F32STORE => ValueType::F32,
+        F64LOAD | F64STORE => ValueType::F64,
+        _ => panic!("{op:?} is not a load or store"),
+    }
+}
+
+fn zero(ty: ValueType) -> Value {
+    match ty {
+        ValueType::I32 => Value::I32(0),
+        ValueType::I64 => Value::I64(0),
+        ValueType::F32 => Value::F32(0.0),
+        ValueType::F64 => Value::F64(0.0),
+    }
+}
+
+/// Run a load or store at `addr` with a memory of 1 page, returning the error message
+fn test_memory_access_error(op: OpCode, addr: u32, offset: u32) -> String {
+    use Instr::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+
+    let ty = memory_op_type(op);
+    let mut body = std::vec::Vec::new();
+    body.push(Const(Value::I32(addr as i32)));
+    let ret_type = if STORES.contains(&op) {
+        body.push(Const(zero(ty)));
+        None
+    } else {
+        Some(ty)
+    };
+    body.push(Mem(op, offset));
+    body.push(Op(OpCode::END));
+    builder.function("test", &[], ret_type, &[], &body);
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.call_export("test", []).unwrap_err()
+}
+
+#[test]
+fn test_memory_access_out_of_bounds() {
+    let page = MemorySection::PAGE_SIZE;
+
+    // The access starts in bounds but runs off the end of memory
+    let message = test_memory_access_error(OpCode::I32LOAD, page - 2, 0);
+    assert!(
+        message.contains("tried to access memory at 0x10001"),
+        "{message}"
+    );
+
+    let message = test_memory_access_error(OpCode::I64LOAD, page - 8, 1);
+    assert!(
+        message.contains("tried to access memory at 0x10000"),
+        "{message}"
+    );
+
+    let message = test_memory_access_error(OpCode::I64STORE16, page - 1, 0);
+    assert!(
+        message.contains("tried to access memory at 0x10000"),
+        "{message}"
+    );
+
+    // The effective address must not wrap around to the start of memory
+    let message = test_memory_access_error(OpCode::I32LOAD16U, u32::MAX, 1);
+    assert!(
+        message.contains("tried to access memory at 0xffffffff"),
+        "{message}"
+    );
+}
+
+#[test]
+fn test_memory_access_offset_overflow() {
+    // 0xfffffff0 + 0x20 would wrap around to 0x10, which is in bounds. It must trap instead.
+    for op in LOADS.into_iter().chain(STORES) {
+        let message = test_memory_access_error(op, 0xffff_fff0, 0x20);
+        assert!(
+            message.contains("tried to access memory at 0xffffffff"),
+            "{op:?}: {message}"
+        );
+    }
+}
+
+#[test]
+fn test_memory_access_offset() {
+    use Instr::*;
+    use OpCode::*;
+
+    let cases = [
+        (I32STORE, I32LOAD, Value::I32(-0x1234_5678)),
+        (I64STORE, I64LOAD, Value::I64(-0x1234_5678_9abc_def0)),
+        (F32STORE, F32LOAD, Value::F32(1.1)),
+        (F64STORE, F64LOAD, Value::F64(-2.2)),
+        (I32STORE8, I32LOAD8U, Value::I32(0xab)),
+        (I32STORE16, I32LOAD16U, Value::I32(0xabcd)),
+        (I64STORE8, I64LOAD8U, Value::I64(0xab)),
+        (I64STORE16, I64LOAD16U, Value::I64(0xabcd)),
+        (I64STORE32, I64LOAD32U, Value::I64(0xabcd_ef01)),
+    ];
+
+    for (store_op, load_op, value) in cases {
+        let arena = Bump::new();
+        let mut builder = ModuleBuilder::new(&arena);
+        builder.memory(1);
+        // Like a struct field at offset 0x18 from a pointer to 0x100,
+        // then the same address split between the pointer and the offset differently
+        builder.function(
+            "test",
+            &[],
+            Some(ValueType::from(value)),
+            &[],
+            &[
+                Const(Value::I32(0x100)),
+                Const(value),
+                Mem(store_op, 0x18),
+                Const(Value::I32(0x110)),
+                Mem(load_op, 0x8),
+                Op(END),
+            ],
+        );
+        let module = builder.build();
+
+        let mut inst =
+            Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false)
+                .unwrap();
+        assert_eq!(
+            inst.call_export("test", []),
+            Ok(Some(value)),
+            "{store_op:?} then {load_op:?}"
+        );
+        assert!(inst.memory[..0x118].iter().all(|b| *b == 0), "{store_op:?}");
+        assert_ne!(inst.memory[0x118], 0, "{store_op:?}");
+    }
 }
 
 fn test_store<'a>(
@@ -516,3 +998,433 @@ fn test_i64store32() {
         &[0xf0, 0xde, 0xbc, 0x9a, 0x00, 0x00, 0x00, 0x00]
     );
 }
+
+/// Run an atomic instruction on a memory that has `0x1234_5678` at address 8
+fn run_atomic(op: AtomicInstruction, args: &[Value]) -> Result<Option<Value>, String> {
+    use Instr::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    builder.data(8, &0x1234_5678u32.to_le_bytes());
+
+    let mut body: std::vec::Vec<Instr> = args.iter().map(|arg| Const(*arg)).collect();
+    body.push(Atomic(op, 0));
+    body.push(Op(OpCode::END));
+    builder.function("test", &[], Some(ValueType::I32), &[], &body);
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.call_export("test", [])
+}
+
+#[test]
+fn test_atomic_notify() {
+    let args = [Value::I32(8), Value::I32(1)];
+    let woken = run_atomic(AtomicInstruction::MemoryAtomicNotify, &args);
+    assert_eq!(woken, Ok(Some(Value::I32(0))));
+}
+
+#[test]
+fn test_atomic_wait32() {
+    let not_equal = [Value::I32(8), Value::I32(0), Value::I64(-1)];
+    let result = run_atomic(AtomicInstruction::MemoryAtomicWait32, &not_equal);
+    assert_eq!(result, Ok(Some(Value::I32(1))));
+
+    let equal_with_timeout = [Value::I32(8), Value::I32(0x1234_5678), Value::I64(1000)];
+    let result = run_atomic(AtomicInstruction::MemoryAtomicWait32, &equal_with_timeout);
+    assert_eq!(result, Ok(Some(Value::I32(2))));
+
+    let equal_forever = [Value::I32(8), Value::I32(0x1234_5678), Value::I64(-1)];
+    let message = run_atomic(AtomicInstruction::MemoryAtomicWait32, &equal_forever).unwrap_err();
+    assert!(message.contains("wait forever"), "{message}");
+}
+
+#[test]
+fn test_atomic_wait64() {
+    let not_equal = [
+        Value::I32(8),
+        Value::I64(0x1234_5678_0000_0000),
+        Value::I64(0),
+    ];
+    let result = run_atomic(AtomicInstruction::MemoryAtomicWait64, &not_equal);
+    assert_eq!(result, Ok(Some(Value::I32(1))));
+
+    let equal = [Value::I32(8), Value::I64(0x1234_5678), Value::I64(0)];
+    let result = run_atomic(AtomicInstruction::MemoryAtomicWait64, &equal);
+    assert_eq!(result, Ok(Some(Value::I32(2))));
+}
+
+#[test]
+fn test_atomic_address_checks() {
+    let unaligned = [Value::I32(6), Value::I32(0), Value::I64(0)];
+    let message = run_atomic(AtomicInstruction::MemoryAtomicWait32, &unaligned).unwrap_err();
+    assert!(message.contains("not aligned to 4 bytes"), "{message}");
+
+    let out_of_bounds = [Value::I32(MemorySection::PAGE_SIZE as i32), Value::I32(1)];
+    let message = run_atomic(AtomicInstruction::MemoryAtomicNotify, &out_of_bounds).unwrap_err();
+    assert!(message.contains("tried to access memory"), "{message}");
+}
+
+#[test]
+fn test_multi_memory() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    assert_eq!(builder.memory(1), 0);
+    assert_eq!(builder.memory(2), 1);
+    builder.data(0, &[1, 2, 3, 4]);
+    builder.data_in(1, 4, &[5, 6, 7, 8]);
+
+    builder.function(
+        "store_in_memory_1",
+        &[],
+        None,
+        &[],
+        &[
+            Const(Value::I32(0)),
+            Const(Value::I32(0x7777_7777)),
+            MemAt(I32STORE, 1, 0),
+            Op(END),
+        ],
+    );
+    builder.function(
+        "load_from_memory_1",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Const(Value::I32(0)), MemAt(I32LOAD, 1, 4), Op(END)],
+    );
+    builder.function(
+        "memory_1_size",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Index(CURRENTMEMORY, 1), Op(END)],
+    );
+    let module = builder.build();
+
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    assert_eq!(inst.other_memories.len(), 1);
+    assert_eq!(inst.memory.len(), MemorySection::PAGE_SIZE as usize);
+    assert_eq!(&inst.memory[..8], &[1, 2, 3, 4, 0, 0, 0, 0]);
+    assert_eq!(&inst.other_memories[0][..8], &[0, 0, 0, 0, 5, 6, 7, 8]);
+
+    let size = inst.call_export("memory_1_size", []).unwrap();
+    assert_eq!(size, Some(Value::I32(2)));
+
+    let loaded = inst.call_export("load_from_memory_1", []).unwrap();
+    assert_eq!(loaded, Some(Value::I32(i32::from_le_bytes([5, 6, 7, 8]))));
+
+    inst.call_export("store_in_memory_1", []).unwrap();
+    assert_eq!(&inst.other_memories[0][..4], &[0x77; 4]);
+    assert_eq!(&inst.memory[..4], &[1, 2, 3, 4]);
+}
+
+#[test]
+fn test_read_strings() {
+    use crate::{read_cstr, read_utf8, StringError};
+
+    let mut memory = [0u8; 16];
+    memory[4..10].copy_from_slice(b"hello\0");
+    memory[12..14].copy_from_slice(&[0xff, 0]);
+
+    assert_eq!(read_utf8(&memory, 4, 5), Ok("hello"));
+    assert_eq!(read_cstr(&memory, 4), Ok("hello"));
+    assert_eq!(read_utf8(&memory, 16, 0), Ok(""));
+
+    assert_eq!(
+        read_utf8(&memory, 12, 5),
+        Err(StringError::OutOfBounds { addr: 12, len: 5 })
+    );
+    assert_eq!(
+        read_utf8(&memory, u32::MAX, 2),
+        Err(StringError::OutOfBounds {
+            addr: u32::MAX,
+            len: 2
+        })
+    );
+    assert!(matches!(
+        read_cstr(&memory, 17),
+        Err(StringError::OutOfBounds { .. })
+    ));
+    // no zero terminator before the end of memory
+    assert!(matches!(
+        read_cstr(&memory[..13], 12),
+        Err(StringError::OutOfBounds { .. })
+    ));
+
+    assert!(matches!(
+        read_utf8(&memory, 12, 1),
+        Err(StringError::InvalidUtf8(_))
+    ));
+    assert!(matches!(
+        read_cstr(&memory, 12),
+        Err(StringError::InvalidUtf8(_))
+    ));
+}
+
+/// Provides `env.__memory_base`, and an `env.inc` function to check function imports after it
+struct MemoryBaseDispatcher {
+    memory_base: Value,
+}
+
+impl crate::ImportDispatcher for MemoryBaseDispatcher {
+    fn dispatch(
+        &mut self,
+        _module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        _memory: &mut [u8],
+    ) -> Option<Value> {
+        assert_eq!(function_name, "inc");
+        Some(Value::I32(arguments[0].expect_i32().unwrap() + 1))
+    }
+
+    fn global(&self, module_name: &str, global_name: &str) -> Option<Value> {
+        match (module_name, global_name) {
+            ("env", "__memory_base") => Some(self.memory_base),
+            _ => None,
+        }
+    }
+}
+
+/// Like position-independent code, with data at an address given by an imported global
+fn memory_base_module(arena: &Bump) -> std::vec::Vec<u8> {
+    use Instr::*;
+    use OpCode::*;
+
+    let mut builder = ModuleBuilder::new(arena);
+    builder.memory(1);
+    let memory_base = builder.import_global("env", "__memory_base", ValueType::I32, false);
+    let inc = builder.import_function("env", "inc", &[ValueType::I32], Some(ValueType::I32));
+    builder.data_at_global(memory_base, b"hi");
+    builder.function(
+        "main",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[
+            Index(GETGLOBAL, memory_base + 1),
+            Mem(I32LOAD8U, 1),
+            Index(CALL, inc),
+            Op(END),
+        ],
+    );
+    let mut module = builder.build();
+    // A copy of the imported global, to check global initializers can refer to it too
+    module.global.append(Global {
+        ty: GlobalType {
+            value_type: ValueType::I32,
+            is_mutable: false,
+        },
+        init: ConstExpr::GlobalGet(memory_base),
+    });
+
+    let mut bytes = std::vec::Vec::with_capacity(module.size());
+    module.serialize(&mut bytes);
+    bytes
+}
+
+#[test]
+fn test_imported_global_in_const_expr() {
+    let arena = Bump::new();
+    let bytes = memory_base_module(&arena);
+    let module = WasmModule::preload(&arena, &bytes, false).unwrap();
+
+    let dispatcher = MemoryBaseDispatcher {
+        memory_base: Value::I32(0x100),
+    };
+    let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+    assert_eq!(&inst.memory[0x100..0x102], b"hi");
+    assert_eq!(inst.globals.as_slice(), &[Value::I32(0x100); 2]);
+    assert_eq!(
+        inst.call_export("main", []),
+        Ok(Some(Value::I32(b'i' as i32 + 1)))
+    );
+
+    // Resetting reads the global again
+    inst.import_dispatcher.memory_base = Value::I32(0x200);
+    inst.reset().unwrap();
+    assert_eq!(&inst.memory[0x200..0x202], b"hi");
+    assert_eq!(&inst.memory[0x100..0x102], &[0, 0]);
+    assert_eq!(inst.globals.as_slice(), &[Value::I32(0x200); 2]);
+}
+
+#[test]
+fn test_imported_global_errors() {
+    let arena = Bump::new();
+    let bytes = memory_base_module(&arena);
+    let module = WasmModule::preload(&arena, &bytes, false).unwrap();
+
+    let result = Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false);
+    assert_eq!(
+        result.err(),
+        Some("The import dispatcher has no value for global env.__memory_base".to_string())
+    );
+
+    let dispatcher = MemoryBaseDispatcher {
+        memory_base: Value::I64(0x100),
+    };
+    assert!(Instance::for_module(&arena, &module, dispatcher, false).is_err());
+
+    // The data doesn't fit in memory at this address
+    let dispatcher = MemoryBaseDispatcher {
+        memory_base: Value::I32(-1),
+    };
+    assert!(Instance::for_module(&arena, &module, dispatcher, false).is_err());
+}
+
+#[test]
+fn test_reset_imported_global_errors() {
+    let arena = Bump::new();
+    let bytes = memory_base_module(&arena);
+    let module = WasmModule::preload(&arena, &bytes, false).unwrap();
+    let dispatcher = MemoryBaseDispatcher {
+        memory_base: Value::I32(0x100),
+    };
+    let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+
+    // The data doesn't fit in memory at the new address
+    inst.import_dispatcher.memory_base = Value::I32(-1);
+    assert!(inst.reset().is_err());
+
+    inst.import_dispatcher.memory_base = Value::I64(0x100);
+    assert!(inst.reset().is_err());
+
+    // Once the dispatcher has a valid address again, resetting works
+    inst.import_dispatcher.memory_base = Value::I32(0x200);
+    inst.reset().unwrap();
+    assert_eq!(&inst.memory[0x200..0x202], b"hi");
+    assert_eq!(
+        inst.call_export("main", []),
+        Ok(Some(Value::I32(b'i' as i32 + 1)))
+    );
+}
+
+#[test]
+fn test_grow_memory_spec_limit() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    builder.function(
+        "grow",
+        &[ValueType::I32],
+        Some(ValueType::I32),
+        &[],
+        &[Index(GETLOCAL, 0), Index(GROWMEMORY, 0), Op(END)],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    // The module declares no maximum, and the host sets no cap
+    let max_pages = MemorySection::MAX_PAGES as i32;
+    for pages in [max_pages, i32::MAX, -1] {
+        assert_eq!(
+            inst.call_export("grow", [Value::I32(pages)]),
+            Ok(Some(Value::I32(-1))),
+            "{pages}"
+        );
+    }
+    assert_eq!(inst.memory.len(), MemorySection::PAGE_SIZE as usize);
+    assert!(inst.grow_memory(MemorySection::MAX_PAGES).is_err());
+}
+
+#[test]
+fn test_total_memory_budget() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    builder.function(
+        "grow",
+        &[],
+        Some(ValueType::I32),
+        &[],
+        &[Const(Value::I32(1)), Index(GROWMEMORY, 0), Op(END)],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+    inst.set_total_memory_budget(3 * MemorySection::PAGE_SIZE as u64);
+
+    // Resetting shrinks memory again, but doesn't give back the budget
+    for _ in 0..3 {
+        assert_eq!(inst.call_export("grow", []), Ok(Some(Value::I32(1))));
+        inst.reset().unwrap();
+    }
+    let result = inst.call_export("grow", []);
+    assert!(
+        result.as_ref().unwrap_err().contains("total memory budget"),
+        "{result:?}"
+    );
+    assert_eq!(inst.memory.len(), MemorySection::PAGE_SIZE as usize);
+
+    inst.reset().unwrap();
+    assert!(inst.grow_memory(1).is_err());
+    assert_eq!(inst.grow_memory(0), Ok(1));
+}
+
+#[test]
+fn test_memory_checksum() {
+    use Instr::*;
+    use OpCode::*;
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    builder.data(0x10, b"checksum");
+    // Store the squares of 0 to 15 as i32s at 0x100
+    builder.function(
+        "squares",
+        &[],
+        None,
+        &[ValueType::I32],
+        &[
+            Block(LOOP, None),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(4)),
+            Op(I32MUL),
+            Index(GETLOCAL, 0),
+            Index(GETLOCAL, 0),
+            Op(I32MUL),
+            Mem(I32STORE, 0x100),
+            Index(GETLOCAL, 0),
+            Const(Value::I32(1)),
+            Op(I32ADD),
+            Index(TEELOCAL, 0),
+            Const(Value::I32(16)),
+            Op(I32LTU),
+            Index(BRIF, 0),
+            Op(END),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+    let mut inst =
+        Instance::for_module(&arena, &module, DefaultImportDispatcher::default(), false).unwrap();
+
+    let initial = inst.memory_checksum();
+    inst.call_export("squares", []).unwrap();
+    assert_eq!(inst.memory[0x13c..0x140], 225u32.to_le_bytes());
+    let after = inst.memory_checksum();
+    assert_ne!(after, initial);
+
+    // The checksum only depends on the contents, so it's the same in every run and every version
+    assert_eq!(after, 0x14bdc8e0c2f1e5f8);
+
+    inst.reset().unwrap();
+    assert_eq!(inst.memory_checksum(), initial);
+    inst.call_export("squares", []).unwrap();
+    assert_eq!(inst.memory_checksum(), after);
+}
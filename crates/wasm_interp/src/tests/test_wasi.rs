@@ -0,0 +1,825 @@
+#![cfg(test)]
+
+use crate::wasi::{Errno, WasiDispatcher, WasiFile, WasiResult};
+use bumpalo::Bump;
+use roc_wasm_module::Value;
+
+fn errno(result: WasiResult) -> i32 {
+    match result {
+        WasiResult::Return(Some(value)) => value.expect_i32().unwrap(),
+        _ => panic!("Expected an error code but got {result:?}"),
+    }
+}
+
+#[test]
+fn test_fd_close() {
+    let mut wasi = WasiDispatcher::default();
+    let mut memory = [0; 64];
+
+    // fd 4 hasn't been opened
+    let result = wasi.dispatch("fd_close", &[Value::I32(4)], &mut memory);
+    assert_eq!(errno(result), Errno::Badf as i32);
+
+    // pretend a file was opened at fd 4
+    wasi.files.push(WasiFile::ReadWrite(vec![]));
+    let result = wasi.dispatch("fd_close", &[Value::I32(4)], &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+
+    // can't close it twice
+    let result = wasi.dispatch("fd_close", &[Value::I32(4)], &mut memory);
+    assert_eq!(errno(result), Errno::Badf as i32);
+}
+
+#[test]
+fn test_fd_fdstat_get() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.files.push(WasiFile::ReadOnly(vec![]));
+    let mut memory = [0xff; 64];
+    let stat_ptr = 8;
+
+    for std_fd in 0..3 {
+        let args = [Value::I32(std_fd), Value::I32(stat_ptr)];
+        let result = wasi.dispatch("fd_fdstat_get", &args, &mut memory);
+        assert_eq!(errno(result), Errno::Success as i32);
+        assert_eq!(memory[8], 2); // character device
+        assert_eq!(&memory[9..32], &[0; 23]);
+    }
+
+    let args = [Value::I32(3), Value::I32(stat_ptr)];
+    let result = wasi.dispatch("fd_fdstat_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(memory[8], 3); // directory
+
+    let args = [Value::I32(4), Value::I32(stat_ptr)];
+    let result = wasi.dispatch("fd_fdstat_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(memory[8], 4); // regular file
+    assert_eq!(&memory[16..24], &2u64.to_le_bytes()); // fd_read
+
+    let args = [Value::I32(5), Value::I32(stat_ptr)];
+    let result = wasi.dispatch("fd_fdstat_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Badf as i32);
+}
+
+const ARGS: [&[u8]; 4] = [b"app.wasm", b"", "héllo".as_bytes(), b"x"];
+
+#[test]
+fn test_args_sizes_get() {
+    let mut wasi = WasiDispatcher::new(&ARGS);
+    let mut memory = [0; 16];
+    let ptr_argc = 0;
+    let ptr_argv_buf_size = 4;
+
+    let args = [Value::I32(ptr_argc), Value::I32(ptr_argv_buf_size)];
+    let result = wasi.dispatch("args_sizes_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+
+    let argc = u32::from_le_bytes(memory[0..4].try_into().unwrap());
+    let argv_buf_size = u32::from_le_bytes(memory[4..8].try_into().unwrap());
+    assert_eq!(argc, 4);
+    // "héllo" is 6 bytes in UTF-8, and each arg has a NUL terminator
+    assert_eq!(argv_buf_size, 9 + 1 + 7 + 2);
+}
+
+#[test]
+fn test_args_get() {
+    let mut wasi = WasiDispatcher::new(&ARGS);
+    let mut memory = [0xff; 64];
+    let ptr_argv = 4;
+    let ptr_argv_buf = 32;
+
+    let args = [Value::I32(ptr_argv), Value::I32(ptr_argv_buf)];
+    let result = wasi.dispatch("args_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+
+    let pointers: std::vec::Vec<u32> = memory[4..20]
+        .chunks(4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(pointers, [32, 41, 42, 49]);
+
+    let expected_buf = b"app.wasm\0\0h\xc3\xa9llo\0x\0";
+    assert_eq!(&memory[32..][..expected_buf.len()], expected_buf);
+
+    // Nothing written outside the pointer array and the string buffer
+    assert_eq!(&memory[0..4], &[0xff; 4]);
+    assert_eq!(&memory[20..32], &[0xff; 12]);
+    assert!(memory[32 + expected_buf.len()..].iter().all(|b| *b == 0xff));
+}
+
+#[test]
+fn test_args_sizes_match_args_get() {
+    let mut wasi = WasiDispatcher::new(&ARGS);
+    let mut memory = [0; 8];
+    let args = [Value::I32(0), Value::I32(4)];
+    wasi.dispatch("args_sizes_get", &args, &mut memory);
+    let argv_buf_size = u32::from_le_bytes(memory[4..8].try_into().unwrap()) as usize;
+
+    // args_get must fit exactly into a buffer of the reported size
+    let ptr_argv = 0;
+    let ptr_argv_buf = 16;
+    let mut memory = vec![0xff; ptr_argv_buf + argv_buf_size + 1];
+    let args = [Value::I32(ptr_argv), Value::I32(ptr_argv_buf as i32)];
+    wasi.dispatch("args_get", &args, &mut memory);
+
+    assert_eq!(memory[ptr_argv_buf + argv_buf_size - 1], 0);
+    assert_eq!(memory[ptr_argv_buf + argv_buf_size], 0xff);
+}
+
+#[test]
+fn test_environ() {
+    let env: [&[u8]; 2] = [b"HOME=/home/roc", b"EMPTY="];
+    let mut wasi = WasiDispatcher::new(&[]);
+    wasi.env = &env;
+    let mut memory = [0xff; 64];
+
+    let args = [Value::I32(0), Value::I32(4)];
+    let result = wasi.dispatch("environ_sizes_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(&memory[0..4], &2u32.to_le_bytes());
+    assert_eq!(&memory[4..8], &22u32.to_le_bytes());
+
+    let ptr_environ = 8;
+    let ptr_environ_buf = 16;
+    let args = [Value::I32(ptr_environ), Value::I32(ptr_environ_buf)];
+    let result = wasi.dispatch("environ_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(&memory[8..12], &16u32.to_le_bytes());
+    assert_eq!(&memory[12..16], &31u32.to_le_bytes());
+    assert_eq!(&memory[16..38], b"HOME=/home/roc\0EMPTY=\0");
+    assert_eq!(memory[38], 0xff);
+}
+
+#[test]
+fn test_with_config_captured_stdout() {
+    use crate::tests::builder::{Instr::*, ModuleBuilder};
+    use crate::{wasi::MODULE_NAME, DefaultImportDispatcher, Instance};
+    use roc_wasm_module::{opcodes::OpCode::*, ValueType::I32};
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    let args_sizes_get =
+        builder.import_function(MODULE_NAME, "args_sizes_get", &[I32, I32], Some(I32));
+    let args_get = builder.import_function(MODULE_NAME, "args_get", &[I32, I32], Some(I32));
+    let fd_write = builder.import_function(MODULE_NAME, "fd_write", &[I32; 4], Some(I32));
+
+    // Echo the whole argv buffer to stdout, including the zero terminators
+    let argv_buf_size_ptr = 4;
+    let iov_ptr = 8;
+    let argv_ptr = 16;
+    let argv_buf_ptr = 64;
+    builder.function(
+        "main",
+        &[],
+        Some(I32),
+        &[],
+        &[
+            Const(Value::I32(0)),
+            Const(Value::I32(argv_buf_size_ptr)),
+            Index(CALL, args_sizes_get),
+            Op(DROP),
+            Const(Value::I32(argv_ptr)),
+            Const(Value::I32(argv_buf_ptr)),
+            Index(CALL, args_get),
+            Op(DROP),
+            // iovec { iov_base: argv_buf_ptr, iov_len: argv_buf_size }
+            Const(Value::I32(iov_ptr)),
+            Const(Value::I32(argv_buf_ptr)),
+            Mem(I32STORE, 0),
+            Const(Value::I32(iov_ptr)),
+            Const(Value::I32(argv_buf_size_ptr)),
+            Mem(I32LOAD, 0),
+            Mem(I32STORE, 4),
+            // fd_write(stdout, iovs, iovs_len, nwritten)
+            Const(Value::I32(1)),
+            Const(Value::I32(iov_ptr)),
+            Const(Value::I32(1)),
+            Const(Value::I32(0)),
+            Index(CALL, fd_write),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let args: [&[u8]; 2] = [b"app", b"hello"];
+    let env: [&[u8]; 1] = [b"KEY=value"];
+    let dispatcher = DefaultImportDispatcher::with_config(
+        &args,
+        WasiFile::WriteOnly(vec![]),
+        WasiFile::HostSystemFile,
+        &env,
+    );
+    let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+
+    let result = inst.call_export("main", []);
+    assert_eq!(result, Ok(Some(Value::I32(Errno::Success as i32))));
+
+    let wasi = &inst.import_dispatcher.wasi;
+    assert_eq!(wasi.env, &env);
+    match &wasi.files[1] {
+        WasiFile::WriteOnly(stdout) => assert_eq!(stdout.as_slice(), b"app\0hello\0"),
+        _ => panic!("Expected stdout to be captured"),
+    }
+    assert!(matches!(wasi.files[2], WasiFile::HostSystemFile));
+}
+
+/// Host functions in the `env` module
+struct EnvDispatcher {
+    calls: std::rc::Rc<std::cell::Cell<u32>>,
+}
+
+impl crate::ImportDispatcher for EnvDispatcher {
+    fn dispatch(
+        &mut self,
+        module_name: &str,
+        function_name: &str,
+        arguments: &[Value],
+        _memory: &mut [u8],
+    ) -> Option<Value> {
+        assert_eq!((module_name, function_name), ("env", "double"));
+        self.calls.set(self.calls.get() + 1);
+        Some(Value::I32(2 * arguments[0].expect_i32().unwrap()))
+    }
+
+    fn handles(&self, module_name: &str, _function_name: &str) -> bool {
+        module_name == "env"
+    }
+}
+
+#[test]
+fn test_chained_dispatcher() {
+    use crate::tests::builder::{Instr::*, ModuleBuilder};
+    use crate::ImportDispatcher;
+    use crate::{wasi::MODULE_NAME, ChainedDispatcher, DefaultImportDispatcher, Instance};
+    use roc_wasm_module::{opcodes::OpCode::*, ValueType::I32};
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    let args_sizes_get =
+        builder.import_function(MODULE_NAME, "args_sizes_get", &[I32, I32], Some(I32));
+    let double = builder.import_function("env", "double", &[I32], Some(I32));
+    builder.function(
+        "main",
+        &[],
+        Some(I32),
+        &[],
+        &[
+            Const(Value::I32(0)),
+            Const(Value::I32(4)),
+            Index(CALL, args_sizes_get),
+            Op(DROP),
+            // double(argc)
+            Const(Value::I32(0)),
+            Mem(I32LOAD, 0),
+            Index(CALL, double),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+    let args: [&[u8]; 3] = [b"app", b"a", b"b"];
+    let dispatcher = ChainedDispatcher::new()
+        .with(EnvDispatcher {
+            calls: calls.clone(),
+        })
+        .with(DefaultImportDispatcher::new(&args));
+    assert!(dispatcher.handles("env", "double"));
+    assert!(dispatcher.handles(MODULE_NAME, "args_sizes_get"));
+    assert!(!dispatcher.handles("other", "double"));
+
+    let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+    assert_eq!(inst.call_export("main", []), Ok(Some(Value::I32(6))));
+    assert_eq!(calls.get(), 1);
+}
+
+#[test]
+fn test_with_imports() {
+    use crate::tests::builder::{Instr::*, ModuleBuilder};
+    use crate::{ImportDispatcher, Instance};
+    use roc_wasm_module::{opcodes::OpCode::*, ValueType::I32};
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let double = builder.import_function("env", "double", &[I32], Some(I32));
+    builder.function(
+        "main",
+        &[],
+        Some(I32),
+        &[],
+        &[Const(Value::I32(21)), Index(CALL, double), Op(END)],
+    );
+    let module = builder.build();
+
+    let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+    let dispatcher: Box<dyn ImportDispatcher> = Box::new(EnvDispatcher {
+        calls: calls.clone(),
+    });
+    let mut inst = Instance::with_imports(&arena, &module, dispatcher, false).unwrap();
+    assert_eq!(inst.call_export("main", []), Ok(Some(Value::I32(42))));
+    assert_eq!(calls.get(), 1);
+    assert!(inst.import_dispatcher.handles("env", "double"));
+}
+
+#[test]
+fn test_override_errno() {
+    use crate::tests::builder::{Instr::*, ModuleBuilder};
+    use crate::{wasi::MODULE_NAME, DefaultImportDispatcher, Instance};
+    use roc_wasm_module::{opcodes::OpCode::*, ValueType::I32};
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.memory(1);
+    let fd_write = builder.import_function(MODULE_NAME, "fd_write", &[I32; 4], Some(I32));
+
+    // iovec { iov_base: 16, iov_len: 2 } at address 8, pointing to "hi" at address 16
+    builder.data(8, &[16, 0, 0, 0, 2, 0, 0, 0, b'h', b'i']);
+    builder.function(
+        "main",
+        &[],
+        Some(I32),
+        &[],
+        &[
+            Const(Value::I32(1)),
+            Const(Value::I32(8)),
+            Const(Value::I32(1)),
+            Const(Value::I32(0)),
+            Index(CALL, fd_write),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let dispatcher = DefaultImportDispatcher::with_config(
+        &[],
+        WasiFile::WriteOnly(vec![]),
+        WasiFile::HostSystemFile,
+        &[],
+    );
+    let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+
+    let stdout =
+        |inst: &Instance<DefaultImportDispatcher>| match &inst.import_dispatcher.wasi.files[1] {
+            WasiFile::WriteOnly(content) => content.clone(),
+            _ => panic!("Expected stdout to be captured"),
+        };
+
+    inst.import_dispatcher
+        .wasi
+        .override_errno("fd_write", Errno::Io);
+    let result = inst.call_export("main", []);
+    assert_eq!(result, Ok(Some(Value::I32(Errno::Io as i32))));
+    assert_eq!(stdout(&inst), b"");
+
+    inst.import_dispatcher.wasi.clear_errno_overrides();
+    let result = inst.call_export("main", []);
+    assert_eq!(result, Ok(Some(Value::I32(Errno::Success as i32))));
+    assert_eq!(stdout(&inst), b"hi");
+}
+
+/// Produces its input a few bytes at a time, like a pipe
+struct ChunkedReader {
+    input: &'static [u8],
+    chunk_size: usize,
+}
+
+impl std::io::Read for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.chunk_size.min(buf.len()).min(self.input.len());
+        buf[..n].copy_from_slice(&self.input[..n]);
+        self.input = &self.input[n..];
+        Ok(n)
+    }
+}
+
+/// Read from `fd` into a single buffer of `len` bytes, returning the bytes read
+fn fd_read(wasi: &mut WasiDispatcher, fd: i32, len: usize) -> std::vec::Vec<u8> {
+    let mut memory = vec![0; 16 + len];
+    let ptr_iov = 0;
+    let ptr_nread = 8;
+    let ptr_buf = 16;
+    memory[ptr_iov..][..4].copy_from_slice(&(ptr_buf as u32).to_le_bytes());
+    memory[ptr_iov + 4..][..4].copy_from_slice(&(len as u32).to_le_bytes());
+
+    let args = [
+        Value::I32(fd),
+        Value::I32(ptr_iov as i32),
+        Value::I32(1),
+        Value::I32(ptr_nread as i32),
+    ];
+    let result = wasi.dispatch("fd_read", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+
+    let n_read = u32::from_le_bytes(memory[ptr_nread..][..4].try_into().unwrap()) as usize;
+    memory[ptr_buf..][..n_read].to_vec()
+}
+
+#[test]
+fn test_fd_read_stream() {
+    let input = b"The quick brown fox jumps over the lazy dog";
+    let mut wasi = WasiDispatcher::default();
+    wasi.files[0] = WasiFile::Stream(Box::new(ChunkedReader {
+        input,
+        chunk_size: 3,
+    }));
+
+    // Each call fills the whole buffer, even though the reader only gives 3 bytes at a time
+    let mut reassembled = vec![];
+    loop {
+        let bytes = fd_read(&mut wasi, 0, 10);
+        if bytes.is_empty() {
+            break;
+        }
+        assert!(bytes.len() == 10 || reassembled.len() + bytes.len() == input.len());
+        reassembled.extend(bytes);
+    }
+    assert_eq!(reassembled, input);
+
+    let mut memory = [0; 16];
+    let args = [Value::I32(0), Value::I64(0), Value::I32(0), Value::I32(0)];
+    let result = wasi.dispatch("fd_seek", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Spipe as i32);
+}
+
+#[test]
+fn test_fd_read_multiple_iovecs() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.files.push(WasiFile::ReadOnly(b"abcdefgh".to_vec()));
+    wasi.files.push(WasiFile::Stream(Box::new(ChunkedReader {
+        input: b"abcdefgh",
+        chunk_size: 1,
+    })));
+
+    for fd in [4, 5] {
+        // Two iovecs of 3 bytes each, pointing to separate buffers
+        let mut memory = [0; 32];
+        memory[0..16].copy_from_slice(&[16, 0, 0, 0, 3, 0, 0, 0, 24, 0, 0, 0, 3, 0, 0, 0]);
+        let ptr_nread = 28;
+        let args = [
+            Value::I32(fd),
+            Value::I32(0),
+            Value::I32(2),
+            Value::I32(ptr_nread),
+        ];
+        let result = wasi.dispatch("fd_read", &args, &mut memory);
+        assert_eq!(errno(result), Errno::Success as i32);
+        assert_eq!(&memory[ptr_nread as usize..], &6u32.to_le_bytes());
+        assert_eq!(&memory[16..19], b"abc", "fd {fd}");
+        assert_eq!(&memory[24..27], b"def", "fd {fd}");
+    }
+}
+
+fn seek(wasi: &mut WasiDispatcher, fd: i32, offset: i64, whence: i32) -> (i32, u64) {
+    let mut memory = [0; 16];
+    let args = [
+        Value::I32(fd),
+        Value::I64(offset),
+        Value::I32(whence),
+        Value::I32(8),
+    ];
+    let result = wasi.dispatch("fd_seek", &args, &mut memory);
+    let new_offset = u64::from_le_bytes(memory[8..16].try_into().unwrap());
+    (errno(result), new_offset)
+}
+
+fn tell(wasi: &mut WasiDispatcher, fd: i32) -> (i32, u64) {
+    let mut memory = [0; 16];
+    let result = wasi.dispatch("fd_tell", &[Value::I32(fd), Value::I32(8)], &mut memory);
+    let offset = u64::from_le_bytes(memory[8..16].try_into().unwrap());
+    (errno(result), offset)
+}
+
+#[test]
+fn test_fd_seek() {
+    const SET: i32 = 0;
+    const CUR: i32 = 1;
+    const END: i32 = 2;
+    let success = Errno::Success as i32;
+
+    let mut wasi = WasiDispatcher::default();
+    wasi.files.push(WasiFile::ReadOnly(b"0123456789".to_vec()));
+
+    assert_eq!(tell(&mut wasi, 4), (success, 0));
+    assert_eq!(seek(&mut wasi, 4, 4, SET), (success, 4));
+    assert_eq!(seek(&mut wasi, 4, 2, CUR), (success, 6));
+    assert_eq!(seek(&mut wasi, 4, -3, CUR), (success, 3));
+    assert_eq!(seek(&mut wasi, 4, -1, END), (success, 9));
+    assert_eq!(tell(&mut wasi, 4), (success, 9));
+
+    // can't seek before the start of the file
+    assert_eq!(seek(&mut wasi, 4, -11, END).0, Errno::Inval as i32);
+    assert_eq!(seek(&mut wasi, 4, -10, CUR).0, Errno::Inval as i32);
+    assert_eq!(seek(&mut wasi, 4, i64::MIN, CUR).0, Errno::Inval as i32);
+    assert_eq!(seek(&mut wasi, 4, -1, SET).0, Errno::Inval as i32);
+    assert_eq!(tell(&mut wasi, 4), (success, 9));
+
+    // unknown whence, including values that would look valid if truncated to a byte
+    for whence in [3, -1, 256, 257] {
+        assert_eq!(seek(&mut wasi, 4, 0, whence).0, Errno::Inval as i32);
+    }
+    assert_eq!(tell(&mut wasi, 4), (success, 9));
+
+    // seeking past the end is fine, and reads from there return nothing
+    assert_eq!(seek(&mut wasi, 4, 5, END), (success, 15));
+    assert_eq!(tell(&mut wasi, 4), (success, 15));
+    assert_eq!(fd_read(&mut wasi, 4, 4), b"");
+}
+
+#[test]
+fn test_fd_seek_then_read() {
+    let mut wasi = WasiDispatcher::default();
+    wasi.files.push(WasiFile::ReadOnly(b"0123456789".to_vec()));
+    seek(&mut wasi, 4, 7, 0);
+
+    // one iovec at address 0, pointing to a 4-byte buffer at address 8
+    let mut memory = [0; 16];
+    memory[0..4].copy_from_slice(&8u32.to_le_bytes());
+    memory[4..8].copy_from_slice(&4u32.to_le_bytes());
+    let args = [Value::I32(4), Value::I32(0), Value::I32(1), Value::I32(12)];
+    let result = wasi.dispatch("fd_read", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(&memory[8..11], b"789");
+    assert_eq!(&memory[12..16], &3u32.to_le_bytes());
+
+    assert_eq!(tell(&mut wasi, 4), (Errno::Success as i32, 10));
+}
+
+#[test]
+fn test_fd_seek_not_seekable() {
+    let mut wasi = WasiDispatcher::default();
+    assert_eq!(seek(&mut wasi, 0, 0, 1).0, Errno::Spipe as i32);
+    assert_eq!(tell(&mut wasi, 0).0, Errno::Spipe as i32);
+    // the preopened root directory
+    assert_eq!(seek(&mut wasi, 3, 0, 1).0, Errno::Badf as i32);
+    assert_eq!(tell(&mut wasi, 3).0, Errno::Badf as i32);
+    // not opened
+    assert_eq!(seek(&mut wasi, 4, 0, 1).0, Errno::Badf as i32);
+    assert_eq!(tell(&mut wasi, 4).0, Errno::Badf as i32);
+}
+
+const CREAT: i32 = 1;
+const DIRECTORY: i32 = 2;
+const EXCL: i32 = 4;
+const TRUNC: i32 = 8;
+const RIGHTS_READ: i64 = 1 << 1;
+const RIGHTS_WRITE: i64 = 1 << 6;
+
+fn path_open(
+    wasi: &mut WasiDispatcher,
+    memory: &mut [u8],
+    path: &str,
+    oflags: i32,
+    rights: i64,
+) -> (i32, i32) {
+    let ptr_path = 64;
+    let ptr_fd = 60;
+    memory[ptr_path..][..path.len()].copy_from_slice(path.as_bytes());
+    let args = [
+        Value::I32(3), // preopened root
+        Value::I32(0),
+        Value::I32(ptr_path as i32),
+        Value::I32(path.len() as i32),
+        Value::I32(oflags),
+        Value::I64(rights),
+        Value::I64(rights),
+        Value::I32(0),
+        Value::I32(ptr_fd as i32),
+    ];
+    let result = wasi.dispatch("path_open", &args, memory);
+    let fd = i32::from_le_bytes(memory[ptr_fd..][..4].try_into().unwrap());
+    (errno(result), fd)
+}
+
+#[test]
+fn test_fd_prestat() {
+    let mut wasi = WasiDispatcher::default();
+    let mut memory = [0xff; 16];
+
+    let result = wasi.dispatch(
+        "fd_prestat_get",
+        &[Value::I32(3), Value::I32(0)],
+        &mut memory,
+    );
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(&memory[0..8], &[0, 0, 0, 0, 1, 0, 0, 0]);
+
+    let args = [Value::I32(3), Value::I32(8), Value::I32(1)];
+    let result = wasi.dispatch("fd_prestat_dir_name", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Success as i32);
+    assert_eq!(memory[8], b'/');
+
+    // No more preopens
+    let result = wasi.dispatch(
+        "fd_prestat_get",
+        &[Value::I32(4), Value::I32(0)],
+        &mut memory,
+    );
+    assert_eq!(errno(result), Errno::Badf as i32);
+}
+
+#[test]
+fn test_path_open_write_then_read() {
+    let success = Errno::Success as i32;
+    let mut wasi = WasiDispatcher::default();
+    let mut memory = [0; 128];
+    let content = b"hello, file!";
+
+    // file doesn't exist yet
+    let (result, _) = path_open(&mut wasi, &mut memory, "data.txt", 0, RIGHTS_READ);
+    assert_eq!(result, Errno::Noent as i32);
+
+    let (result, fd) = path_open(&mut wasi, &mut memory, "data.txt", CREAT, RIGHTS_WRITE);
+    assert_eq!(result, success);
+    assert_eq!(fd, 4);
+
+    // one iovec at address 0, pointing to the content at address 16
+    memory[0..4].copy_from_slice(&16u32.to_le_bytes());
+    memory[4..8].copy_from_slice(&(content.len() as u32).to_le_bytes());
+    memory[16..][..content.len()].copy_from_slice(content);
+    let args = [Value::I32(fd), Value::I32(0), Value::I32(1), Value::I32(8)];
+    let result = wasi.dispatch("fd_write", &args, &mut memory);
+    assert_eq!(errno(result), success);
+
+    let result = wasi.dispatch("fd_close", &[Value::I32(fd)], &mut memory);
+    assert_eq!(errno(result), success);
+    assert_eq!(wasi.filesystem["data.txt"], content);
+
+    // reopen and read into address 32
+    memory[16..][..content.len()].fill(0);
+    let (result, fd) = path_open(&mut wasi, &mut memory, "/data.txt", 0, RIGHTS_READ);
+    assert_eq!(result, success);
+    memory[0..4].copy_from_slice(&32u32.to_le_bytes());
+    let args = [Value::I32(fd), Value::I32(0), Value::I32(1), Value::I32(8)];
+    let result = wasi.dispatch("fd_read", &args, &mut memory);
+    assert_eq!(errno(result), success);
+    assert_eq!(&memory[8..12], &(content.len() as u32).to_le_bytes());
+    assert_eq!(&memory[32..][..content.len()], content);
+
+    // truncating gives an empty file
+    let (result, fd) = path_open(&mut wasi, &mut memory, "data.txt", TRUNC, RIGHTS_WRITE);
+    assert_eq!(result, success);
+    let result = wasi.dispatch("fd_close", &[Value::I32(fd)], &mut memory);
+    assert_eq!(errno(result), success);
+    assert!(wasi.filesystem["data.txt"].is_empty());
+}
+
+#[test]
+fn test_path_open_errors() {
+    let mut wasi = WasiDispatcher::default();
+    let mut memory = [0; 128];
+
+    let (result, _) = path_open(&mut wasi, &mut memory, "a.txt", CREAT, RIGHTS_WRITE);
+    assert_eq!(result, Errno::Success as i32);
+    let (result, _) = path_open(&mut wasi, &mut memory, "a.txt", CREAT | EXCL, RIGHTS_WRITE);
+    assert_eq!(result, Errno::Exist as i32);
+    let (result, _) = path_open(&mut wasi, &mut memory, "a.txt", DIRECTORY, RIGHTS_READ);
+    assert_eq!(result, Errno::Notdir as i32);
+
+    let open_raw = |wasi: &mut WasiDispatcher, memory: &mut [u8], dir_fd, path_len| {
+        let args = [
+            Value::I32(dir_fd),
+            Value::I32(0),
+            Value::I32(64),
+            Value::I32(path_len),
+            Value::I32(0),
+            Value::I64(RIGHTS_READ),
+            Value::I64(RIGHTS_READ),
+            Value::I32(0),
+            Value::I32(60),
+        ];
+        errno(wasi.dispatch("path_open", &args, memory))
+    };
+    // stdout is not a directory we can open paths in
+    assert_eq!(open_raw(&mut wasi, &mut memory, 1, 5), Errno::Badf as i32);
+    // the path runs off the end of memory
+    assert_eq!(
+        open_raw(&mut wasi, &mut memory, 3, 100),
+        Errno::Fault as i32
+    );
+    // the path is not UTF-8
+    memory[64] = 0xff;
+    assert_eq!(open_raw(&mut wasi, &mut memory, 3, 5), Errno::Ilseq as i32);
+
+    // the buffer for the preopened directory's name is too small
+    let args = [Value::I32(3), Value::I32(0), Value::I32(0)];
+    let result = wasi.dispatch("fd_prestat_dir_name", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Nametoolong as i32);
+}
+
+#[test]
+fn test_clock_res_get() {
+    let mut wasi = WasiDispatcher::default();
+
+    for clock_id in 0..4 {
+        let mut memory = [0xff; 16];
+        let args = [Value::I32(clock_id), Value::I32(8)];
+        let result = wasi.dispatch("clock_res_get", &args, &mut memory);
+        assert_eq!(errno(result), Errno::Success as i32);
+        let resolution = u64::from_le_bytes(memory[8..16].try_into().unwrap());
+        assert!(resolution > 0 && resolution <= 1_000_000, "{resolution}");
+    }
+
+    let mut memory = [0; 16];
+    let args = [Value::I32(4), Value::I32(8)];
+    let result = wasi.dispatch("clock_res_get", &args, &mut memory);
+    assert_eq!(errno(result), Errno::Inval as i32);
+}
+
+#[test]
+fn test_sched_yield() {
+    let mut wasi = WasiDispatcher::default();
+    let result = wasi.dispatch("sched_yield", &[], &mut []);
+    assert_eq!(errno(result), Errno::Success as i32);
+}
+
+#[test]
+fn test_proc_exit() {
+    let mut wasi = WasiDispatcher::default();
+    let mut memory = [0; 8];
+    let result = wasi.dispatch("proc_exit", &[Value::I32(3)], &mut memory);
+    assert_eq!(result, WasiResult::Exit(3));
+}
+
+#[test]
+fn test_proc_exit_stops_the_instance() {
+    use crate::tests::builder::{Instr::*, ModuleBuilder};
+    use crate::wasi::MODULE_NAME;
+    use crate::{DefaultImportDispatcher, ImportDispatcher, Instance};
+    use roc_wasm_module::{opcodes::OpCode::*, ValueType::I32};
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let proc_exit = builder.import_function(MODULE_NAME, "proc_exit", &[I32], None);
+    builder.function(
+        "_start",
+        &[],
+        None,
+        &[],
+        &[
+            Const(Value::I32(3)),
+            Index(CALL, proc_exit),
+            Op(UNREACHABLE),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+
+    let dispatcher = DefaultImportDispatcher::default();
+    let mut inst = Instance::for_module(&arena, &module, dispatcher, false).unwrap();
+    assert_eq!(inst.call_export("_start", []), Ok(None));
+    assert_eq!(inst.import_dispatcher.exit_code(), Some(3));
+}
+
+#[test]
+fn test_run_wasi() {
+    use crate::tests::builder::{Instr::*, ModuleBuilder};
+    use crate::wasi::MODULE_NAME;
+    use crate::{run_wasi, Error, TrapKind};
+    use roc_wasm_module::{opcodes::OpCode::*, ValueType::I32};
+
+    let arena = Bump::new();
+    let mut builder = ModuleBuilder::new(&arena);
+    let proc_exit = builder.import_function(MODULE_NAME, "proc_exit", &[I32], None);
+    builder.function(
+        "_start",
+        &[],
+        None,
+        &[],
+        &[
+            Const(Value::I32(42)),
+            Index(CALL, proc_exit),
+            // proc_exit never returns
+            Op(UNREACHABLE),
+            Op(END),
+        ],
+    );
+    let module = builder.build();
+    assert_eq!(run_wasi(&module, &["exit.wasm"]), Ok(42));
+
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function("_start", &[], None, &[], &[Op(END)]);
+    let module = builder.build();
+    assert_eq!(run_wasi(&module, &["return.wasm"]), Ok(0));
+
+    let mut builder = ModuleBuilder::new(&arena);
+    let host_fn = builder.import_function("env", "host_fn", &[], None);
+    builder.function("_start", &[], None, &[], &[Index(CALL, host_fn), Op(END)]);
+    let module = builder.build();
+    assert_eq!(
+        run_wasi(&module, &["env.wasm"]),
+        Err(Error::UnsupportedImport {
+            module: "env".to_string(),
+            name: "host_fn".to_string(),
+        })
+    );
+
+    let mut builder = ModuleBuilder::new(&arena);
+    builder.function("_start", &[], None, &[], &[Op(UNREACHABLE), Op(END)]);
+    let module = builder.build();
+    assert_eq!(
+        run_wasi(&module, &["trap.wasm"]),
+        Err(Error::Trap(TrapKind::Unreachable))
+    );
+}
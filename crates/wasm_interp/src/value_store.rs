@@ -12,6 +12,9 @@ use crate::Error;
 ///
 /// We use a simple Vec. When we tried more densely-packed SoA structures,
 /// they were slower due to more logic, and harder to debug.
+///
+/// The Vec never shrinks. Returning from a function just truncates it, so the next call
+/// reuses the same buffer. It only reallocates when the stack gets deeper than ever before.
 pub struct ValueStore<'a> {
     values: Vec<'a, Value>,
 }
@@ -23,24 +26,31 @@ impl<'a> ValueStore<'a> {
         }
     }
 
-    pub(crate) fn depth(&self) -> usize {
-        self.values.len()
+    /// Make room for at least `capacity` values in total, so the stack won't reallocate until it's deeper than that
+    pub(crate) fn reserve(&mut self, capacity: usize) {
+        self.values
+            .reserve(capacity.saturating_sub(self.values.len()));
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
-        self.values.is_empty()
+    #[cfg(test)]
+    pub(crate) fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    pub(crate) fn depth(&self) -> usize {
+        self.values.len()
     }
 
     pub(crate) fn push(&mut self, value: Value) {
         self.values.push(value);
     }
 
-    pub(crate) fn pop(&mut self) -> Value {
-        self.values.pop().unwrap()
+    pub(crate) fn pop(&mut self) -> Result<Value, Error> {
+        self.values.pop().ok_or(Error::StackEmpty)
     }
 
-    pub(crate) fn peek(&self) -> Value {
-        *self.values.last().unwrap()
+    pub(crate) fn peek(&self) -> Result<Value, Error> {
+        self.values.last().copied().ok_or(Error::StackEmpty)
     }
 
     pub(crate) fn get(&self, index: usize) -> Option<&Value> {
@@ -112,9 +122,30 @@ impl<'a> ValueStore<'a> {
         self.values.truncate(depth)
     }
 
+    /// Truncate to `depth`, but first move the top `n_kept` values down to sit on top of it.
+    /// This is what a branch does to the values carried out of (or back into) a block.
+    /// Fails if there are fewer than `n_kept` values above `depth`, rather than dipping below it.
+    pub(crate) fn truncate_keeping_top(
+        &mut self,
+        depth: usize,
+        n_kept: usize,
+    ) -> Result<(), Error> {
+        let top = match self.values.len().checked_sub(n_kept) {
+            Some(top) if top >= depth => top,
+            _ => return Err(Error::StackEmpty),
+        };
+        self.values.copy_within(top.., depth);
+        self.values.truncate(depth + n_kept);
+        Ok(())
+    }
+
     pub(crate) fn get_slice(&mut self, from: usize) -> &[Value] {
         &self.values[from..]
     }
+
+    pub(crate) fn get_range(&self, range: std::ops::Range<usize>) -> &[Value] {
+        &self.values[range]
+    }
 }
 
 impl Debug for ValueStore<'_> {
@@ -145,7 +176,7 @@ mod tests {
 
         for val in VALUES.iter().rev() {
             let popped = stack.pop();
-            assert_eq!(popped, *val);
+            assert_eq!(popped, Ok(*val));
         }
     }
 
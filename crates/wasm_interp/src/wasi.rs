@@ -1,14 +1,59 @@
+use crate::{read_utf8, StringError};
 use rand::prelude::*;
 use roc_wasm_module::Value;
-use std::io::{self, Read, StderrLock, StdoutLock, Write};
-use std::process::exit;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read, StderrLock, StdoutLock, Write};
 
 pub const MODULE_NAME: &str = "wasi_snapshot_preview1";
 
+const WASI_FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const WASI_FILETYPE_DIRECTORY: u8 = 3;
+const WASI_FILETYPE_REGULAR_FILE: u8 = 4;
+
+const WASI_RIGHTS_FD_READ: u64 = 1 << 1;
+const WASI_RIGHTS_FD_WRITE: u64 = 1 << 6;
+const WASI_RIGHTS_PATH_OPEN: u64 = 1 << 13;
+
+const WASI_OFLAGS_CREAT: u16 = 1 << 0;
+const WASI_OFLAGS_DIRECTORY: u16 = 1 << 1;
+const WASI_OFLAGS_EXCL: u16 = 1 << 2;
+const WASI_OFLAGS_TRUNC: u16 = 1 << 3;
+
+const WASI_PREOPENTYPE_DIR: u8 = 0;
+
+/// Clock IDs: realtime, monotonic, process CPU time, thread CPU time
+const WASI_CLOCK_COUNT: u32 = 4;
+/// Resolution of all clocks, in nanoseconds
+const WASI_CLOCK_RESOLUTION_NS: u64 = 1;
+
+/// Name of the preopened directory at the root of the in-memory filesystem
+const ROOT_DIR_NAME: &[u8] = b"/";
+
+const WASI_WHENCE_SET: u8 = 0;
+const WASI_WHENCE_CUR: u8 = 1;
+const WASI_WHENCE_END: u8 = 2;
+
 pub struct WasiDispatcher<'a> {
     pub args: &'a [&'a [u8]],
+    /// Environment variables, each in `KEY=value` form
+    pub env: &'a [&'a [u8]],
     pub rng: ThreadRng,
     pub files: Vec<WasiFile>,
+    /// In-memory filesystem, mapping paths relative to the preopened root to file contents.
+    /// Files opened with `path_open` are written back here when they are closed.
+    pub filesystem: HashMap<String, Vec<u8>>,
+    /// Extra state for in-memory files, indexed by file descriptor
+    fd_states: Vec<FdState>,
+    /// Functions that return a fixed error instead of running, to test error handling
+    errno_overrides: HashMap<String, Errno>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct FdState {
+    /// Current read/write position
+    position: u64,
+    /// Path in the in-memory filesystem, if opened with `path_open`
+    path: Option<String>,
 }
 
 impl Default for WasiDispatcher<'_> {
@@ -22,12 +67,21 @@ pub enum WasiFile {
     WriteOnly(Vec<u8>),
     ReadWrite(Vec<u8>),
     HostSystemFile,
+    /// Input that is read on demand, like a pipe, so it doesn't all need to be in memory at once.
+    /// For example, set `files[0]` to one of these to feed stdin incrementally. Not seekable.
+    Stream(Box<dyn Read>),
+    /// The root directory of the in-memory filesystem.
+    /// WASI programs can only open paths relative to a preopened directory.
+    PreopenedRoot,
+    /// Placeholder for a file descriptor that has been closed.
+    /// File descriptors are indices into the table, so we can't remove entries from it.
+    Closed,
 }
 
 enum WriteLock<'a> {
     StdOut(StdoutLock<'a>),
     Stderr(StderrLock<'a>),
-    RegularFile(&'a mut Vec<u8>),
+    RegularFile(Cursor<&'a mut Vec<u8>>),
 }
 
 /// Implementation of WASI syscalls
@@ -38,37 +92,82 @@ impl<'a> WasiDispatcher<'a> {
     pub fn new(args: &'a [&'a [u8]]) -> Self {
         WasiDispatcher {
             args,
+            env: &[],
             rng: thread_rng(),
             files: vec![
                 WasiFile::HostSystemFile,
                 WasiFile::HostSystemFile,
                 WasiFile::HostSystemFile,
+                WasiFile::PreopenedRoot,
             ],
+            filesystem: HashMap::new(),
+            fd_states: vec![],
+            errno_overrides: HashMap::new(),
         }
     }
 
+    /// Make every call to the WASI function `function_name` return `errno` without doing anything.
+    /// This makes it possible to test how a program handles I/O failures.
+    pub fn override_errno(&mut self, function_name: &str, errno: Errno) {
+        self.errno_overrides
+            .insert(function_name.to_string(), errno);
+    }
+
+    /// Remove all overrides set by [WasiDispatcher::override_errno]
+    pub fn clear_errno_overrides(&mut self) {
+        self.errno_overrides.clear();
+    }
+
+    fn fd_state_mut(&mut self, fd: usize) -> &mut FdState {
+        if self.fd_states.len() <= fd {
+            self.fd_states.resize(fd + 1, FdState::default());
+        }
+        &mut self.fd_states[fd]
+    }
+
+    fn position(&self, fd: usize) -> u64 {
+        self.fd_states
+            .get(fd)
+            .map(|state| state.position)
+            .unwrap_or(0)
+    }
+
     pub fn dispatch(
         &mut self,
         function_name: &str,
         arguments: &[Value],
         memory: &mut [u8],
+    ) -> WasiResult {
+        match function_name {
+            "proc_exit" => {
+                let exit_code = arguments[0].expect_i32().unwrap();
+                WasiResult::Exit(exit_code)
+            }
+            _ => match self.errno_overrides.get(function_name) {
+                Some(&errno) => WasiResult::Return(Some(Value::I32(errno as i32))),
+                None => {
+                    WasiResult::Return(self.dispatch_returning(function_name, arguments, memory))
+                }
+            },
+        }
+    }
+
+    /// Dispatch any WASI function that returns normally to the Wasm program
+    fn dispatch_returning(
+        &mut self,
+        function_name: &str,
+        arguments: &[Value],
+        memory: &mut [u8],
     ) -> Option<Value> {
         let success_code = Some(Value::I32(Errno::Success as i32));
         match function_name {
             "args_get" => {
                 // uint8_t ** argv,
-                let mut ptr_ptr_argv = arguments[0].expect_i32().unwrap() as usize;
+                let ptr_ptr_argv = arguments[0].expect_i32().unwrap() as usize;
                 // uint8_t * argv_buf
-                let mut ptr_argv_buf = arguments[1].expect_i32().unwrap() as usize;
-
-                for arg in self.args {
-                    write_u32(memory, ptr_ptr_argv, ptr_argv_buf as u32);
-                    let bytes_target = &mut memory[ptr_argv_buf..][..arg.len()];
-                    bytes_target.copy_from_slice(arg);
-                    memory[ptr_argv_buf + arg.len()] = 0; // C string zero termination
-                    ptr_argv_buf += arg.len() + 1;
-                    ptr_ptr_argv += 4;
-                }
+                let ptr_argv_buf = arguments[1].expect_i32().unwrap() as usize;
+
+                write_c_strings(memory, ptr_ptr_argv, ptr_argv_buf, self.args);
 
                 success_code
             }
@@ -80,17 +179,17 @@ impl<'a> WasiDispatcher<'a> {
                 // size of string arguments buffer
                 let ptr_argv_buf_size = arguments[1].expect_i32().unwrap() as usize;
 
-                let argc = self.args.len() as u32;
-                write_u32(memory, ptr_argc, argc);
-
-                let argv_buf_size: u32 = self.args.iter().map(|a| 1 + a.len() as u32).sum();
-                write_u32(memory, ptr_argv_buf_size, argv_buf_size);
+                write_c_strings_sizes(memory, ptr_argc, ptr_argv_buf_size, self.args);
 
                 success_code
             }
             "environ_get" => {
-                // `environ_sizes_get` always reports 0 environment variables
-                // so we don't have to do anything here.
+                // uint8_t ** environ,
+                let ptr_ptr_environ = arguments[0].expect_i32().unwrap() as usize;
+                // uint8_t * environ_buf
+                let ptr_environ_buf = arguments[1].expect_i32().unwrap() as usize;
+
+                write_c_strings(memory, ptr_ptr_environ, ptr_environ_buf, self.env);
 
                 success_code
             }
@@ -98,22 +197,52 @@ impl<'a> WasiDispatcher<'a> {
                 let num_env_ptr = arguments[0].expect_i32().unwrap() as usize;
                 let size_env_ptr = arguments[1].expect_i32().unwrap() as usize;
 
-                // Calculate the total size required for environment variables
-                let total_size = 0;
-                let count = 0;
-
-                write_u32(memory, num_env_ptr, count);
-                write_u32(memory, size_env_ptr, total_size as u32);
+                write_c_strings_sizes(memory, num_env_ptr, size_env_ptr, self.env);
 
                 success_code
             }
-            "clock_res_get" => success_code, // this dummy implementation seems to be good enough for some functions
+            "clock_res_get" => {
+                // the clock to query
+                let clock_id = arguments[0].expect_i32().unwrap() as u32;
+                // Out param: the resolution in nanoseconds
+                let ptr_resolution = arguments[1].expect_i32().unwrap() as usize;
+
+                if clock_id < WASI_CLOCK_COUNT {
+                    write_u64(memory, ptr_resolution, WASI_CLOCK_RESOLUTION_NS);
+                    success_code
+                } else {
+                    Some(Value::I32(Errno::Inval as i32))
+                }
+            }
             "clock_time_get" => success_code,
             "fd_advise" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_allocate" => todo!("WASI {}({:?})", function_name, arguments),
-            "fd_close" => todo!("WASI {}({:?})", function_name, arguments),
+            "fd_close" => {
+                // file descriptor
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+
+                let file = match self.files.get_mut(fd) {
+                    Some(WasiFile::Closed) | None => return Some(Value::I32(Errno::Badf as i32)),
+                    Some(file) => std::mem::replace(file, WasiFile::Closed),
+                };
+
+                let state = std::mem::take(self.fd_state_mut(fd));
+                if let (
+                    Some(path),
+                    WasiFile::ReadOnly(content)
+                    | WasiFile::WriteOnly(content)
+                    | WasiFile::ReadWrite(content),
+                ) = (state.path, file)
+                {
+                    self.filesystem.insert(path, content);
+                }
+
+                success_code
+            }
             "fd_datasync" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_fdstat_get" => {
+                use WasiFile::*;
+
                 // (i32, i32) -> i32
 
                 // file descriptor
@@ -121,26 +250,37 @@ impl<'a> WasiDispatcher<'a> {
                 // ptr to a wasi_fdstat_t
                 let stat_mut_ptr = arguments[1].expect_i32().unwrap() as usize;
 
-                match fd {
-                    1 => {
-                        // Tell WASI that stdout is a tty (no seek or tell)
-                        // https://github.com/WebAssembly/wasi-libc/blob/659ff414560721b1660a19685110e484a081c3d4/libc-bottom-half/sources/isatty.c
-                        // *Not* a tty if:
-                        //     (statbuf.fs_filetype != __WASI_FILETYPE_CHARACTER_DEVICE ||
-                        //         (statbuf.fs_rights_base & (__WASI_RIGHTS_FD_SEEK | __WASI_RIGHTS_FD_TELL)) != 0)
-                        // So it's sufficient to set:
-                        //     .fs_filetype = __WASI_FILETYPE_CHARACTER_DEVICE
-                        //     .fs_rights_base = 0
-
-                        const WASI_FILETYPE_CHARACTER_DEVICE: u8 = 2;
-                        memory[stat_mut_ptr] = WASI_FILETYPE_CHARACTER_DEVICE;
-
-                        for b in memory[stat_mut_ptr + 1..stat_mut_ptr + 24].iter_mut() {
-                            *b = 0;
-                        }
-                    }
-                    _ => todo!("WASI {}({:?})", function_name, arguments),
-                }
+                // typedef struct __wasi_fdstat_t {
+                //     __wasi_filetype_t fs_filetype;         // u8, offset 0
+                //     __wasi_fdflags_t fs_flags;             // u16, offset 2
+                //     __wasi_rights_t fs_rights_base;        // u64, offset 8
+                //     __wasi_rights_t fs_rights_inheriting;  // u64, offset 16
+                // } __wasi_fdstat_t;
+                let (filetype, rights) = match self.files.get(fd) {
+                    // Tell WASI that the std streams are ttys (no seek or tell)
+                    // https://github.com/WebAssembly/wasi-libc/blob/659ff414560721b1660a19685110e484a081c3d4/libc-bottom-half/sources/isatty.c
+                    // *Not* a tty if:
+                    //     (statbuf.fs_filetype != __WASI_FILETYPE_CHARACTER_DEVICE ||
+                    //         (statbuf.fs_rights_base & (__WASI_RIGHTS_FD_SEEK | __WASI_RIGHTS_FD_TELL)) != 0)
+                    // So it's sufficient to set:
+                    //     .fs_filetype = __WASI_FILETYPE_CHARACTER_DEVICE
+                    //     .fs_rights_base = 0
+                    Some(HostSystemFile) => (WASI_FILETYPE_CHARACTER_DEVICE, 0),
+                    Some(Stream(_)) => (WASI_FILETYPE_CHARACTER_DEVICE, WASI_RIGHTS_FD_READ),
+                    Some(PreopenedRoot) => (WASI_FILETYPE_DIRECTORY, WASI_RIGHTS_PATH_OPEN),
+                    Some(ReadOnly(_)) => (WASI_FILETYPE_REGULAR_FILE, WASI_RIGHTS_FD_READ),
+                    Some(WriteOnly(_)) => (WASI_FILETYPE_REGULAR_FILE, WASI_RIGHTS_FD_WRITE),
+                    Some(ReadWrite(_)) => (
+                        WASI_FILETYPE_REGULAR_FILE,
+                        WASI_RIGHTS_FD_READ | WASI_RIGHTS_FD_WRITE,
+                    ),
+                    Some(Closed) | None => return Some(Value::I32(Errno::Badf as i32)),
+                };
+
+                memory[stat_mut_ptr..][..24].fill(0);
+                memory[stat_mut_ptr] = filetype;
+                memory[stat_mut_ptr + 8..][..8].copy_from_slice(&rights.to_le_bytes());
+                memory[stat_mut_ptr + 16..][..8].copy_from_slice(&rights.to_le_bytes());
 
                 success_code
             }
@@ -157,17 +297,32 @@ impl<'a> WasiDispatcher<'a> {
                 //  preopen type: 4 bytes, where 0=dir is the only one supported, it seems
                 //  preopen name length: 4 bytes
                 let ptr_buf = arguments[1].expect_i32().unwrap() as usize;
-                memory[ptr_buf..][..8].copy_from_slice(&0u64.to_le_bytes());
-                if fd < self.files.len() {
-                    success_code
-                } else {
-                    println!("WASI warning: file descriptor {fd} does not exist");
-                    Some(Value::I32(Errno::Badf as i32))
+                match self.files.get(fd) {
+                    Some(WasiFile::PreopenedRoot) => {
+                        write_u32(memory, ptr_buf, WASI_PREOPENTYPE_DIR as u32);
+                        write_u32(memory, ptr_buf + 4, ROOT_DIR_NAME.len() as u32);
+                        success_code
+                    }
+                    // WASI libc probes file descriptors until it gets Badf, to find all the preopens
+                    _ => Some(Value::I32(Errno::Badf as i32)),
                 }
             }
             "fd_prestat_dir_name" => {
-                // We're not giving names to any of our files so just return success
-                success_code
+                // The preopened file descriptor to query
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+                // Out param: the directory name
+                let ptr_path = arguments[1].expect_i32().unwrap() as usize;
+                // Length of the buffer for the name
+                let path_len = arguments[2].expect_i32().unwrap() as usize;
+
+                match self.files.get(fd) {
+                    Some(WasiFile::PreopenedRoot) if path_len >= ROOT_DIR_NAME.len() => {
+                        memory[ptr_path..][..ROOT_DIR_NAME.len()].copy_from_slice(ROOT_DIR_NAME);
+                        success_code
+                    }
+                    Some(WasiFile::PreopenedRoot) => Some(Value::I32(Errno::Nametoolong as i32)),
+                    _ => Some(Value::I32(Errno::Badf as i32)),
+                }
             }
             "fd_pwrite" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_read" => {
@@ -189,25 +344,47 @@ impl<'a> WasiDispatcher<'a> {
                 // };
 
                 let mut n_read: usize = 0;
-                match self.files.get(fd) {
+                let position = self.position(fd) as usize;
+                match self.files.get_mut(fd) {
                     Some(ReadOnly(content) | ReadWrite(content)) => {
-                        for _ in 0..iovs_len {
-                            let iov_base = read_u32(memory, ptr_iovs) as usize;
-                            let iov_len = read_i32(memory, ptr_iovs + 4) as usize;
-                            let remaining = content.len() - n_read;
+                        let unread = content.get(position..).unwrap_or_default();
+                        for i in 0..iovs_len as usize {
+                            let ptr_iov = ptr_iovs + 8 * i;
+                            let iov_base = read_u32(memory, ptr_iov) as usize;
+                            let iov_len = read_i32(memory, ptr_iov + 4) as usize;
+                            let remaining = unread.len() - n_read;
                             let len = remaining.min(iov_len);
                             if len == 0 {
                                 break;
                             }
-                            memory[iov_base..][..len].copy_from_slice(&content[n_read..][..len]);
+                            memory[iov_base..][..len].copy_from_slice(&unread[n_read..][..len]);
                             n_read += len;
                         }
+                        self.fd_state_mut(fd).position += n_read as u64;
+                    }
+                    Some(Stream(reader)) => {
+                        for i in 0..iovs_len as usize {
+                            let ptr_iov = ptr_iovs + 8 * i;
+                            let iov_base = read_u32(memory, ptr_iov) as usize;
+                            let iov_len = read_i32(memory, ptr_iov + 4) as usize;
+                            let buffer = &mut memory[iov_base..][..iov_len];
+                            match read_until_full(reader, buffer) {
+                                Ok(n) => {
+                                    n_read += n;
+                                    if n < iov_len {
+                                        break; // end of input
+                                    }
+                                }
+                                Err(_) => return Some(Value::I32(Errno::Io as i32)),
+                            }
+                        }
                     }
                     Some(HostSystemFile) if fd == 0 => {
                         let mut stdin = io::stdin();
-                        for _ in 0..iovs_len {
-                            let iov_base = read_u32(memory, ptr_iovs) as usize;
-                            let iov_len = read_i32(memory, ptr_iovs + 4) as usize;
+                        for i in 0..iovs_len as usize {
+                            let ptr_iov = ptr_iovs + 8 * i;
+                            let iov_base = read_u32(memory, ptr_iov) as usize;
+                            let iov_len = read_i32(memory, ptr_iov + 4) as usize;
                             match stdin.read(&mut memory[iov_base..][..iov_len]) {
                                 Ok(n) => {
                                     n_read += n;
@@ -226,9 +403,65 @@ impl<'a> WasiDispatcher<'a> {
             }
             "fd_readdir" => todo!("WASI {}({:?})", function_name, arguments),
             "fd_renumber" => todo!("WASI {}({:?})", function_name, arguments),
-            "fd_seek" => todo!("WASI {}({:?})", function_name, arguments),
+            "fd_seek" => {
+                use WasiFile::*;
+
+                // file descriptor
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+                // number of bytes to move the cursor
+                let offset = arguments[1].expect_i64().unwrap();
+                // base of the offset: start of file, current position, or end of file
+                let whence = arguments[2].expect_i32().unwrap();
+                // Out param: new position relative to the start of the file
+                let ptr_newoffset = arguments[3].expect_i32().unwrap() as usize;
+
+                let file_len = match self.files.get(fd) {
+                    Some(ReadOnly(content) | WriteOnly(content) | ReadWrite(content)) => {
+                        content.len() as u64
+                    }
+                    Some(HostSystemFile | Stream(_)) => {
+                        return Some(Value::I32(Errno::Spipe as i32))
+                    }
+                    Some(PreopenedRoot | Closed) | None => {
+                        return Some(Value::I32(Errno::Badf as i32))
+                    }
+                };
+
+                let position = &mut self.fd_state_mut(fd).position;
+                // Don't truncate: a whence of 256 is invalid, not SET
+                let base = match u8::try_from(whence) {
+                    Ok(WASI_WHENCE_SET) => 0,
+                    Ok(WASI_WHENCE_CUR) => *position,
+                    Ok(WASI_WHENCE_END) => file_len,
+                    _ => return Some(Value::I32(Errno::Inval as i32)),
+                };
+                match base.checked_add_signed(offset) {
+                    Some(new_position) => {
+                        *position = new_position;
+                        write_u64(memory, ptr_newoffset, new_position);
+                        success_code
+                    }
+                    None => Some(Value::I32(Errno::Inval as i32)),
+                }
+            }
             "fd_sync" => todo!("WASI {}({:?})", function_name, arguments),
-            "fd_tell" => todo!("WASI {}({:?})", function_name, arguments),
+            "fd_tell" => {
+                use WasiFile::*;
+
+                // file descriptor
+                let fd = arguments[0].expect_i32().unwrap() as usize;
+                // Out param: current position relative to the start of the file
+                let ptr_offset = arguments[1].expect_i32().unwrap() as usize;
+
+                match self.files.get(fd) {
+                    Some(ReadOnly(_) | WriteOnly(_) | ReadWrite(_)) => {
+                        write_u64(memory, ptr_offset, self.position(fd));
+                        success_code
+                    }
+                    Some(HostSystemFile | Stream(_)) => Some(Value::I32(Errno::Spipe as i32)),
+                    Some(PreopenedRoot | Closed) | None => Some(Value::I32(Errno::Badf as i32)),
+                }
+            }
             "fd_write" => {
                 use WasiFile::*;
 
@@ -243,6 +476,7 @@ impl<'a> WasiDispatcher<'a> {
 
                 // Grab a lock for stdout/stderr before the loop rather than re-acquiring over and over.
                 // Not really necessary for other files, but it's easier to use the same structure.
+                let position = self.position(fd);
                 let mut write_lock = match self.files.get_mut(fd) {
                     Some(HostSystemFile) => match fd {
                         1 => WriteLock::StdOut(io::stdout().lock()),
@@ -250,7 +484,9 @@ impl<'a> WasiDispatcher<'a> {
                         _ => return Some(Value::I32(Errno::Inval as i32)),
                     },
                     Some(WriteOnly(content) | ReadWrite(content)) => {
-                        WriteLock::RegularFile(content)
+                        let mut cursor = Cursor::new(content);
+                        cursor.set_position(position);
+                        WriteLock::RegularFile(cursor)
                     }
                     _ => return Some(Value::I32(Errno::Badf as i32)),
                 };
@@ -287,6 +523,12 @@ impl<'a> WasiDispatcher<'a> {
                     n_written += bytes.len() as i32;
                 }
 
+                if let WriteLock::RegularFile(cursor) = &write_lock {
+                    let new_position = cursor.position();
+                    drop(write_lock);
+                    self.fd_state_mut(fd).position = new_position;
+                }
+
                 write_i32(memory, ptr_nwritten, n_written);
                 if negative_length_count > 0 {
                     // Let's see if we ever get this message. If not, we can remove this negative-length stuff.
@@ -304,19 +546,81 @@ impl<'a> WasiDispatcher<'a> {
             "path_filestat_get" => todo!("WASI {}({:?})", function_name, arguments),
             "path_filestat_set_times" => todo!("WASI {}({:?})", function_name, arguments),
             "path_link" => todo!("WASI {}({:?})", function_name, arguments),
-            "path_open" => todo!("WASI {}({:?})", function_name, arguments),
+            "path_open" => {
+                // file descriptor of the directory the path is relative to
+                let dir_fd = arguments[0].expect_i32().unwrap() as usize;
+                // arguments[1] is dirflags, for following symlinks, which we don't have
+                // the path to open
+                let ptr_path = arguments[2].expect_i32().unwrap() as u32;
+                let path_len = arguments[3].expect_i32().unwrap() as u32;
+                // how to open the file: create, truncate, etc.
+                let oflags = arguments[4].expect_i32().unwrap() as u16;
+                // what the program wants to do with the file: read, write, etc.
+                let rights = arguments[5].expect_i64().unwrap() as u64;
+                // arguments[6] is the rights inherited by files opened from this one
+                // arguments[7] is fdflags (append, sync, etc.), which we ignore
+                // Out param: the new file descriptor
+                let ptr_fd = arguments[8].expect_i32().unwrap() as usize;
+
+                if !matches!(self.files.get(dir_fd), Some(WasiFile::PreopenedRoot)) {
+                    return Some(Value::I32(Errno::Badf as i32));
+                }
+                if oflags & WASI_OFLAGS_DIRECTORY != 0 {
+                    return Some(Value::I32(Errno::Notdir as i32));
+                }
+
+                let path = match read_utf8(memory, ptr_path, path_len) {
+                    Ok(path) => path.trim_start_matches('/').to_string(),
+                    Err(StringError::InvalidUtf8(_)) => {
+                        return Some(Value::I32(Errno::Ilseq as i32))
+                    }
+                    Err(StringError::OutOfBounds { .. }) => {
+                        return Some(Value::I32(Errno::Fault as i32))
+                    }
+                };
+
+                let content = match self.filesystem.get(&path) {
+                    Some(_)
+                        if oflags & WASI_OFLAGS_CREAT != 0 && oflags & WASI_OFLAGS_EXCL != 0 =>
+                    {
+                        return Some(Value::I32(Errno::Exist as i32))
+                    }
+                    Some(_) if oflags & WASI_OFLAGS_TRUNC != 0 => vec![],
+                    Some(existing) => existing.clone(),
+                    None if oflags & WASI_OFLAGS_CREAT != 0 => vec![],
+                    None => return Some(Value::I32(Errno::Noent as i32)),
+                };
+                self.filesystem.insert(path.clone(), content.clone());
+
+                let can_read = rights & WASI_RIGHTS_FD_READ != 0;
+                let can_write = rights & WASI_RIGHTS_FD_WRITE != 0;
+                let file = match (can_read, can_write) {
+                    (true, true) => WasiFile::ReadWrite(content),
+                    (false, true) => WasiFile::WriteOnly(content),
+                    _ => WasiFile::ReadOnly(content),
+                };
+
+                let fd = self.files.len();
+                self.files.push(file);
+                *self.fd_state_mut(fd) = FdState {
+                    position: 0,
+                    path: Some(path),
+                };
+
+                write_u32(memory, ptr_fd, fd as u32);
+                success_code
+            }
             "path_readlink" => todo!("WASI {}({:?})", function_name, arguments),
             "path_remove_directory" => todo!("WASI {}({:?})", function_name, arguments),
             "path_rename" => todo!("WASI {}({:?})", function_name, arguments),
             "path_symlink" => todo!("WASI {}({:?})", function_name, arguments),
             "path_unlink_file" => todo!("WASI {}({:?})", function_name, arguments),
             "poll_oneoff" => todo!("WASI {}({:?})", function_name, arguments),
-            "proc_exit" => {
-                let exit_code = arguments[0].expect_i32().unwrap();
-                exit(exit_code);
-            }
             "proc_raise" => todo!("WASI {}({:?})", function_name, arguments),
-            "sched_yield" => todo!("WASI {}({:?})", function_name, arguments),
+            "sched_yield" => {
+                // The interpreter is single-threaded, so there's nothing to yield to
+                success_code
+            }
             "random_get" => {
                 // A pointer to a buffer where the random bytes will be written
                 let ptr_buf = arguments[0].expect_i32().unwrap() as usize;
@@ -335,6 +639,54 @@ impl<'a> WasiDispatcher<'a> {
     }
 }
 
+/// The outcome of a WASI function call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WasiResult {
+    /// Return to the Wasm program. WASI preview 1 functions return at most one value.
+    Return(Option<Value>),
+    /// The program called `proc_exit` and should stop running, with this exit code
+    Exit(i32),
+}
+
+/// Write a list of strings for `args_get` or `environ_get`:
+/// an array of pointers, and a buffer of the zero-terminated strings they point to
+fn write_c_strings(memory: &mut [u8], mut ptr_ptrs: usize, mut ptr_buf: usize, strings: &[&[u8]]) {
+    for s in strings {
+        write_u32(memory, ptr_ptrs, ptr_buf as u32);
+        memory[ptr_buf..][..s.len()].copy_from_slice(s);
+        memory[ptr_buf + s.len()] = 0; // C string zero termination
+        ptr_buf += s.len() + 1;
+        ptr_ptrs += 4;
+    }
+}
+
+/// Write the number of strings, and the buffer size needed for them, for `args_sizes_get` or `environ_sizes_get`
+fn write_c_strings_sizes(
+    memory: &mut [u8],
+    ptr_count: usize,
+    ptr_buf_size: usize,
+    strings: &[&[u8]],
+) {
+    write_u32(memory, ptr_count, strings.len() as u32);
+    let buf_size: u32 = strings.iter().map(|s| 1 + s.len() as u32).sum();
+    write_u32(memory, ptr_buf_size, buf_size);
+}
+
+/// Read until the buffer is full or the input ends, returning the number of bytes read.
+/// A single `read` may return fewer bytes even if there's more input to come.
+fn read_until_full(reader: &mut dyn Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
 fn read_u32(memory: &[u8], addr: usize) -> u32 {
     let mut bytes = [0; 4];
     bytes.copy_from_slice(&memory[addr..][..4]);
@@ -355,6 +707,10 @@ fn write_i32(memory: &mut [u8], addr: usize, value: i32) {
     memory[addr..][..4].copy_from_slice(&value.to_le_bytes());
 }
 
+fn write_u64(memory: &mut [u8], addr: usize, value: u64) {
+    memory[addr..][..8].copy_from_slice(&value.to_le_bytes());
+}
+
 /// Error codes returned by functions.
 /// Not all of these error codes are returned by the functions provided by this
 /// API; some are used in higher-level library layers, and others are provided
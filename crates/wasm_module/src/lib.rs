@@ -15,7 +15,7 @@ use bitvec::vec::BitVec;
 use bumpalo::{collections::Vec, Bump};
 
 use self::linking::{IndexRelocType, LinkingSection, RelocationSection, WasmObjectSymbol};
-use self::parse::{Parse, ParseError};
+use self::parse::{Parse, ParseError, SkipBytes};
 use self::sections::{
     CodeSection, DataSection, ElementSection, ExportSection, FunctionSection, GlobalSection,
     ImportDesc, ImportSection, MemorySection, NameSection, OpaqueSection, Section, SectionId,
@@ -115,6 +115,43 @@ impl<'a> WasmModule<'a> {
             + self.names.size()
     }
 
+    /// Find the instruction at a file offset, for mapping interpreter or debugger positions back
+    /// to source. Returns the function index (counting imports) and the instruction's ordinal
+    /// within the function body, starting at 0. An offset inside an instruction's immediates
+    /// maps to that instruction. Returns `None` for offsets outside any function body,
+    /// including the size and local declarations at the start of each function.
+    pub fn instruction_index_at(&self, offset: usize) -> Option<(usize, usize)> {
+        let bytes = &self.code.bytes;
+        let code_offset = offset.checked_sub(self.code.section_offset as usize)?;
+        let offsets = &self.code.function_offsets;
+        let internal_fn_index = offsets
+            .partition_point(|fn_offset| *fn_offset as usize <= code_offset)
+            .checked_sub(1)?;
+
+        let mut cursor = offsets[internal_fn_index] as usize;
+        let body_size = u32::parse((), bytes, &mut cursor).ok()? as usize;
+        let body_end = cursor + body_size;
+        let local_group_count = u32::parse((), bytes, &mut cursor).ok()?;
+        for _ in 0..local_group_count {
+            <(u32, ValueType)>::parse((), bytes, &mut cursor).ok()?;
+        }
+        if code_offset < cursor || code_offset >= body_end {
+            return None;
+        }
+
+        let mut ordinal = 0;
+        loop {
+            OpCode::skip_bytes(bytes, &mut cursor).ok()?;
+            if cursor > code_offset {
+                break;
+            }
+            ordinal += 1;
+        }
+
+        let fn_index = self.import.function_count() + internal_fn_index;
+        Some((fn_index, ordinal))
+    }
+
     pub fn preload(
         arena: &'a Bump,
         bytes: &[u8],
@@ -696,6 +733,8 @@ pub enum ValueType {
 
 impl ValueType {
     pub const VOID: u8 = 0x40;
+    /// The SIMD vector type. It's not a variant, because nothing here supports SIMD.
+    pub const V128: u8 = 0x7b;
 }
 
 impl Serialize for ValueType {
@@ -752,6 +791,16 @@ pub enum Value {
 }
 
 impl Value {
+    /// Check that this value can go in a slot of the `expected` type, like a global or a call argument
+    pub fn check_type(&self, expected: ValueType) -> Result<(), (ValueType, ValueType)> {
+        let actual = ValueType::from(*self);
+        if actual == expected {
+            Ok(())
+        } else {
+            Err((expected, actual))
+        }
+    }
+
     pub fn expect_i32(&self) -> Result<i32, (ValueType, ValueType)> {
         match self {
             Value::I32(x) => Ok(*x),
@@ -802,6 +851,39 @@ impl From<i64> for Value {
     }
 }
 
+impl From<f32> for Value {
+    fn from(x: f32) -> Self {
+        Value::F32(x)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::F64(x)
+    }
+}
+
+/// Convert a Value back to a Rust number, failing with (expected, actual) types on a mismatch.
+/// Unsigned integers are reinterpreted from the signed Wasm representation, like `From` does.
+macro_rules! try_from_value {
+    ($ty: ty, $expect: ident) => {
+        impl TryFrom<Value> for $ty {
+            type Error = (ValueType, ValueType);
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                value.$expect().map(|x| x as $ty)
+            }
+        }
+    };
+}
+
+try_from_value!(i32, expect_i32);
+try_from_value!(u32, expect_i32);
+try_from_value!(i64, expect_i64);
+try_from_value!(u64, expect_i64);
+try_from_value!(f32, expect_f32);
+try_from_value!(f64, expect_f64);
+
 /// Wasm memory alignment for load/store instructions.
 /// Rust representation matches Wasm encoding.
 /// It's an error to specify alignment higher than the "natural" alignment of the instruction
@@ -19,8 +19,13 @@ pub enum OpCode {
     RETURN = 0x0f,
     CALL = 0x10,
     CALLINDIRECT = 0x11,
+    // tail call proposal
+    RETURNCALL = 0x12,
+    RETURNCALLINDIRECT = 0x13,
     DROP = 0x1a,
     SELECT = 0x1b,
+    // reference types proposal
+    SELECTT = 0x1c,
     GETLOCAL = 0x20,
     SETLOCAL = 0x21,
     TEELOCAL = 0x22,
@@ -52,6 +57,7 @@ pub enum OpCode {
     CURRENTMEMORY = 0x3f,
     GROWMEMORY = 0x40,
     MEMORY = 0xFC,
+    ATOMIC = 0xFE,
     I32CONST = 0x41,
     I64CONST = 0x42,
     F32CONST = 0x43,
@@ -211,8 +217,11 @@ pub const LOOKUP_TABLE: [Option<OpCode>; 256] = {
     result[0x0f] = Some(RETURN);
     result[0x10] = Some(CALL);
     result[0x11] = Some(CALLINDIRECT);
+    result[0x12] = Some(RETURNCALL);
+    result[0x13] = Some(RETURNCALLINDIRECT);
     result[0x1a] = Some(DROP);
     result[0x1b] = Some(SELECT);
+    result[0x1c] = Some(SELECTT);
     result[0x20] = Some(GETLOCAL);
     result[0x21] = Some(SETLOCAL);
     result[0x22] = Some(TEELOCAL);
@@ -244,6 +253,7 @@ pub const LOOKUP_TABLE: [Option<OpCode>; 256] = {
     result[0x3f] = Some(CURRENTMEMORY);
     result[0x40] = Some(GROWMEMORY);
     result[0xfc] = Some(MEMORY);
+    result[0xfe] = Some(ATOMIC);
     result[0x41] = Some(I32CONST);
     result[0x42] = Some(I64CONST);
     result[0x43] = Some(F32CONST);
@@ -421,20 +431,48 @@ impl TryFrom<u8> for MemoryInstruction {
     }
 }
 
+/// Instructions from the threads proposal, after the 0xFE prefix byte.
+/// Only the ones needed for a single-threaded implementation are here.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtomicInstruction {
+    MemoryAtomicNotify = 0,
+    MemoryAtomicWait32 = 1,
+    MemoryAtomicWait64 = 2,
+}
+
+impl TryFrom<u8> for AtomicInstruction {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::MemoryAtomicNotify),
+            1 => Ok(Self::MemoryAtomicWait32),
+            2 => Ok(Self::MemoryAtomicWait64),
+            _ => Err(value),
+        }
+    }
+}
+
 /// The format of the *immediate* operands of an operator
 /// Immediates appear directly in the byte stream after the opcode,
 /// rather than being popped off the value stack. These are the possible forms.
 #[derive(Debug)]
 enum OpImmediates {
     NoImmediate,
-    Byte1,
     Bytes4,
     Bytes8,
     Leb32x1,
     Leb64x1,
     Leb32x2,
+    /// A value type byte, or a type index as a signed LEB. The value types are one-byte LEBs too.
+    BlockType,
     BrTable,
+    /// A vector of value types. Reference types are one byte each, like the numeric ones.
+    SelectTypes,
+    MemArg,
     Memory,
+    Atomic,
 }
 
 fn immediates_for(op: OpCode) -> Result<OpImmediates, String> {
@@ -444,7 +482,7 @@ fn immediates_for(op: OpCode) -> Result<OpImmediates, String> {
     let imm = match op {
         UNREACHABLE => NoImmediate,
         NOP => NoImmediate,
-        BLOCK | LOOP | IF => Byte1,
+        BLOCK | LOOP | IF => BlockType,
         ELSE => NoImmediate,
         END => NoImmediate,
         BR | BRIF => Leb32x1,
@@ -452,18 +490,22 @@ fn immediates_for(op: OpCode) -> Result<OpImmediates, String> {
         RETURN => NoImmediate,
         CALL => Leb32x1,
         CALLINDIRECT => Leb32x2,
+        RETURNCALL => Leb32x1,
+        RETURNCALLINDIRECT => Leb32x2,
         DROP => NoImmediate,
         SELECT => NoImmediate,
+        SELECTT => SelectTypes,
         GETLOCAL | SETLOCAL | TEELOCAL => Leb32x1,
         GETGLOBAL | SETGLOBAL => Leb32x1,
 
         I32LOAD | I64LOAD | F32LOAD | F64LOAD | I32LOAD8S | I32LOAD8U | I32LOAD16S | I32LOAD16U
         | I64LOAD8S | I64LOAD8U | I64LOAD16S | I64LOAD16U | I64LOAD32S | I64LOAD32U | I32STORE
         | I64STORE | F32STORE | F64STORE | I32STORE8 | I32STORE16 | I64STORE8 | I64STORE16
-        | I64STORE32 => Leb32x2,
+        | I64STORE32 => MemArg,
 
-        CURRENTMEMORY | GROWMEMORY => Byte1,
+        CURRENTMEMORY | GROWMEMORY => Leb32x1,
         MEMORY => Memory,
+        ATOMIC => Atomic,
 
         I32CONST => Leb32x1,
         I64CONST => Leb64x1,
@@ -497,13 +539,32 @@ fn immediates_for(op: OpCode) -> Result<OpImmediates, String> {
     Ok(imm)
 }
 
+/// Bit in the alignment immediate of a memory access, meaning that a memory index comes next.
+/// Only set by modules using the multi-memory proposal. Otherwise the memory index is 0.
+pub const MEMARG_MEMORY_INDEX_FLAG: u32 = 0x40;
+
+/// Skip the alignment, optional memory index, and offset of a memory access
+fn skip_memarg(bytes: &[u8], cursor: &mut usize) -> Result<(), ParseError> {
+    let alignment = u32::parse((), bytes, cursor)?;
+    if alignment & MEMARG_MEMORY_INDEX_FLAG != 0 {
+        u32::skip_bytes(bytes, cursor)?;
+    }
+    u32::skip_bytes(bytes, cursor)
+}
+
 impl SkipBytes for OpCode {
     fn skip_bytes(bytes: &[u8], cursor: &mut usize) -> Result<(), ParseError> {
         use OpImmediates::*;
 
-        let opcode_byte: u8 = bytes[*cursor];
+        let opcode_byte: u8 = *bytes.get(*cursor).ok_or_else(|| ParseError {
+            message: "Reached the end of the code".into(),
+            offset: *cursor,
+        })?;
 
-        let opcode: OpCode = OpCode::from(opcode_byte);
+        let opcode: OpCode = LOOKUP_TABLE[opcode_byte as usize].ok_or_else(|| ParseError {
+            message: format!("Unsupported instruction byte {opcode_byte:#x?}"),
+            offset: *cursor,
+        })?;
         // will return Err if transmute was invalid
         let immediates = immediates_for(opcode).map_err(|message| ParseError {
             message,
@@ -514,9 +575,6 @@ impl SkipBytes for OpCode {
             NoImmediate => {
                 *cursor += 1;
             }
-            Byte1 => {
-                *cursor += 1 + 1;
-            }
             Bytes4 => {
                 *cursor += 1 + 4;
             }
@@ -536,6 +594,10 @@ impl SkipBytes for OpCode {
                 u32::skip_bytes(bytes, cursor)?;
                 u32::skip_bytes(bytes, cursor)?;
             }
+            BlockType => {
+                *cursor += 1;
+                u64::skip_bytes(bytes, cursor)?;
+            }
             BrTable => {
                 *cursor += 1;
                 let n_labels = 1 + u32::parse((), bytes, cursor)?;
@@ -543,29 +605,57 @@ impl SkipBytes for OpCode {
                     u32::skip_bytes(bytes, cursor)?;
                 }
             }
+            SelectTypes => {
+                *cursor += 1;
+                let n_types = u32::parse((), bytes, cursor)?;
+                for _ in 0..n_types {
+                    u8::parse((), bytes, cursor)?;
+                }
+            }
+            MemArg => {
+                *cursor += 1;
+                skip_memarg(bytes, cursor)?;
+            }
             Memory => {
-                match MemoryInstruction::try_from(bytes[*cursor + 1]) {
+                let op_byte = bytes.get(*cursor + 1).copied().unwrap_or(u8::MAX);
+                match MemoryInstruction::try_from(op_byte) {
                     Ok(op) => match op {
                         MemoryInstruction::MemoryInit => {
-                            // memory.init
-                            todo!("WASM instruction: memory.init")
+                            // memory.init segment memory
+                            *cursor += 1 + 1;
+                            u32::skip_bytes(bytes, cursor)?;
+                            u32::skip_bytes(bytes, cursor)?;
                         }
                         MemoryInstruction::DataDrop => {
                             // data.drop x
-                            todo!("WASM instruction: data.drop")
+                            *cursor += 1 + 1;
+                            u32::skip_bytes(bytes, cursor)?;
                         }
                         MemoryInstruction::MemoryCopy => {
-                            // memory.copy
-                            *cursor += 1 + 1 + 2;
+                            // memory.copy destination source
+                            *cursor += 1 + 1;
+                            u32::skip_bytes(bytes, cursor)?;
+                            u32::skip_bytes(bytes, cursor)?;
                         }
                         MemoryInstruction::MemoryFill => {
-                            // memory.fill
-                            *cursor += 1 + 1 + 1;
+                            // memory.fill memory
+                            *cursor += 1 + 1;
+                            u32::skip_bytes(bytes, cursor)?;
                         }
                     },
-                    Err(other) => unreachable!("invalid memory instruction {other:?}"),
+                    Err(other) => {
+                        return Err(ParseError {
+                            message: format!("Invalid memory instruction {other:?}"),
+                            offset: *cursor + 1,
+                        });
+                    }
                 }
             }
+            Atomic => {
+                // All the atomic instructions we support have a memarg, like loads
+                *cursor += 1 + 1;
+                skip_memarg(bytes, cursor)?;
+            }
         }
         Ok(())
     }
@@ -40,7 +40,7 @@ impl Parse<()> for u32 {
                 offset: *cursor,
                 message: format!(
                     "Failed to decode u32 as LEB-128 from bytes: {:2x?}",
-                    &bytes[*cursor..][..MAX_SIZE_ENCODED_U32]
+                    &bytes[*cursor..(*cursor + MAX_SIZE_ENCODED_U32).min(bytes.len())]
                 ),
             }),
         }
@@ -49,7 +49,10 @@ impl Parse<()> for u32 {
 
 impl Parse<()> for u8 {
     fn parse(_ctx: (), bytes: &[u8], cursor: &mut usize) -> Result<Self, ParseError> {
-        let byte = bytes[*cursor];
+        let byte = *bytes.get(*cursor).ok_or_else(|| ParseError {
+            offset: *cursor,
+            message: "Expected a byte, but reached the end".into(),
+        })?;
         *cursor += 1;
         Ok(byte)
     }
@@ -85,7 +88,7 @@ impl Parse<()> for i32 {
                 offset: *cursor,
                 message: format!(
                     "Failed to decode i32 as LEB-128 from bytes: {:2x?}",
-                    &bytes[*cursor..][..MAX_SIZE_ENCODED_U32]
+                    &bytes[*cursor..(*cursor + MAX_SIZE_ENCODED_U32).min(bytes.len())]
                 ),
             }),
         }
@@ -122,7 +125,7 @@ impl Parse<()> for i64 {
                 offset: *cursor,
                 message: format!(
                     "Failed to decode i64 as LEB-128 from bytes: {:2x?}",
-                    &bytes[*cursor..][..MAX_SIZE_ENCODED_U64]
+                    &bytes[*cursor..(*cursor + MAX_SIZE_ENCODED_U64).min(bytes.len())]
                 ),
             }),
         }
@@ -241,7 +244,10 @@ impl SkipBytes for String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{parse::decode_u32, SerialBuffer};
+    use crate::{
+        parse::{decode_i32, decode_i64, decode_u32},
+        SerialBuffer,
+    };
 
     #[test]
     fn test_decode_u32() {
@@ -276,6 +282,15 @@ mod tests {
         assert_eq!(cursor, 8);
     }
 
+    #[test]
+    fn test_parse_u8_at_end() {
+        let bytes = &[7];
+        let mut cursor = 0;
+        assert_eq!(u8::parse((), bytes, &mut cursor).unwrap(), 7);
+        assert!(u8::parse((), bytes, &mut cursor).is_err());
+        assert_eq!(cursor, 1);
+    }
+
     #[test]
     fn test_encode_decode_i32() {
         encode_decode_i32_help(3);
@@ -286,6 +301,98 @@ mod tests {
         encode_decode_i32_help(i32::MAX);
     }
 
+    #[test]
+    fn test_decode_i32() {
+        assert_eq!(decode_i32(&[0x7f]), Ok((-1, 1)));
+        assert_eq!(decode_i32(&[0x40]), Ok((-64, 1)));
+        assert_eq!(decode_i32(&[0xc0, 0x00]), Ok((64, 2)));
+        assert_eq!(
+            decode_i32(&[0xff, 0xff, 0xff, 0xff, 0x07]),
+            Ok((i32::MAX, MAX_SIZE_ENCODED_U32))
+        );
+        assert_eq!(
+            decode_i32(&[0x80, 0x80, 0x80, 0x80, 0x78]),
+            Ok((i32::MIN, MAX_SIZE_ENCODED_U32))
+        );
+        // Padded to the maximum length, like relocatable code
+        assert_eq!(
+            decode_i32(&[0xff, 0xff, 0xff, 0xff, 0x7f]),
+            Ok((-1, MAX_SIZE_ENCODED_U32))
+        );
+        assert_eq!(
+            decode_i32(&[0x85, 0x80, 0x80, 0x80, 0x00]),
+            Ok((5, MAX_SIZE_ENCODED_U32))
+        );
+        assert!(decode_i32(&[0x80; 6]).is_err());
+    }
+
+    #[test]
+    fn test_decode_i64() {
+        assert_eq!(decode_i64(&[0x7f]), Ok((-1, 1)));
+        assert_eq!(decode_i64(&[0xbf, 0x7f]), Ok((-65, 2)));
+        assert_eq!(
+            decode_i64(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]),
+            Ok((i64::MAX, MAX_SIZE_ENCODED_U64))
+        );
+        assert_eq!(
+            decode_i64(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f]),
+            Ok((i64::MIN, MAX_SIZE_ENCODED_U64))
+        );
+        // i64::MIN + 1 and i64::MAX - 1 also need all 10 bytes
+        assert_eq!(
+            decode_i64(&[0x81, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f]),
+            Ok((i64::MIN + 1, MAX_SIZE_ENCODED_U64))
+        );
+        assert_eq!(
+            decode_i64(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]),
+            Ok((i64::MAX - 1, MAX_SIZE_ENCODED_U64))
+        );
+        // Just outside the i32 range, where a 32-bit shift or sign extension would go wrong
+        assert_eq!(
+            decode_i64(&[0x80, 0x80, 0x80, 0x80, 0x78]),
+            Ok((i32::MIN as i64, 5))
+        );
+        assert_eq!(
+            decode_i64(&[0xff, 0xff, 0xff, 0xff, 0x77]),
+            Ok((i32::MIN as i64 - 1, 5))
+        );
+        assert_eq!(
+            decode_i64(&[0x80, 0x80, 0x80, 0x80, 0x08]),
+            Ok((1 << 31, 5))
+        );
+        // -1 padded to the maximum length
+        assert_eq!(
+            decode_i64(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]),
+            Ok((-1, MAX_SIZE_ENCODED_U64))
+        );
+        assert!(decode_i64(&[0x80; 11]).is_err());
+        assert!(decode_i64(&[0x80; 3]).is_err());
+    }
+
+    #[test]
+    fn test_encode_decode_i64() {
+        for value in [
+            0,
+            1,
+            -1,
+            63,
+            64,
+            -64,
+            -65,
+            i64::MIN,
+            i64::MAX,
+            1 << 32,
+            -(1 << 32),
+        ] {
+            let arena = &Bump::new();
+            let mut buffer = Vec::with_capacity_in(MAX_SIZE_ENCODED_U64, arena);
+            buffer.encode_i64(value);
+            let mut cursor = 0;
+            assert_eq!(i64::parse((), &buffer, &mut cursor).unwrap(), value);
+            assert_eq!(cursor, buffer.len());
+        }
+    }
+
     fn encode_decode_i32_help(value: i32) {
         let arena = &Bump::new();
         let mut buffer = Vec::with_capacity_in(MAX_SIZE_ENCODED_U32, arena);
@@ -299,6 +299,11 @@ impl<'a> TypeSection<'a> {
         self.bytes.is_empty()
     }
 
+    /// The number of signatures. Valid signature indices are below this.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
     pub fn look_up(&'a self, sig_index: u32) -> (SignatureParamsIter<'a>, Option<ValueType>) {
         let mut offset = self.offsets[sig_index as usize];
         offset += 1; // separator
@@ -515,6 +520,15 @@ impl<'a> ImportSection<'a> {
     pub fn function_count(&self) -> usize {
         self.imports.iter().filter(|imp| imp.is_function()).count()
     }
+
+    /// Find an imported function by its index in the function index space.
+    /// Other kinds of import have their own index spaces, so they don't count.
+    pub fn function(&self, fn_index: usize) -> Option<&Import<'a>> {
+        self.imports
+            .iter()
+            .filter(|imp| imp.is_function())
+            .nth(fn_index)
+    }
 }
 
 impl<'a> Parse<&'a Bump> for ImportSection<'a> {
@@ -818,6 +832,8 @@ pub struct MemorySection<'a> {
 
 impl<'a> MemorySection<'a> {
     pub const PAGE_SIZE: u32 = 64 * 1024;
+    /// The spec's limit on the size of a 32-bit memory, 4 GiB in total
+    pub const MAX_PAGES: u32 = 64 * 1024;
 
     pub fn new(arena: &'a Bump, memory_bytes: u32) -> Self {
         if memory_bytes == 0 {
@@ -836,24 +852,44 @@ impl<'a> MemorySection<'a> {
         }
     }
 
+    /// Add another memory. More than one memory requires the multi-memory proposal.
+    pub fn append(&mut self, limits: Limits) -> u32 {
+        let index = self.count;
+        self.count += 1;
+        limits.serialize(&mut self.bytes);
+        index
+    }
+
     pub fn min_bytes(&self) -> Result<u32, ParseError> {
-        let mut cursor = 0;
-        let memory_limits = Limits::parse((), &self.bytes, &mut cursor)?;
-        let min_pages = match memory_limits {
+        self.min_bytes_at(0)
+    }
+
+    pub fn max_bytes(&self) -> Result<Option<u32>, ParseError> {
+        self.max_bytes_at(0)
+    }
+
+    pub fn min_bytes_at(&self, index: u32) -> Result<u32, ParseError> {
+        let min_pages = match self.limits_at(index)? {
             Limits::Min(pages) | Limits::MinMax(pages, _) => pages,
         };
         Ok(min_pages * MemorySection::PAGE_SIZE)
     }
 
-    pub fn max_bytes(&self) -> Result<Option<u32>, ParseError> {
-        let mut cursor = 0;
-        let memory_limits = Limits::parse((), &self.bytes, &mut cursor)?;
-        let bytes = match memory_limits {
+    pub fn max_bytes_at(&self, index: u32) -> Result<Option<u32>, ParseError> {
+        let bytes = match self.limits_at(index)? {
             Limits::Min(_) => None,
             Limits::MinMax(_, pages) => Some(pages * MemorySection::PAGE_SIZE),
         };
         Ok(bytes)
     }
+
+    fn limits_at(&self, index: u32) -> Result<Limits, ParseError> {
+        let mut cursor = 0;
+        for _ in 0..index {
+            Limits::skip_bytes(&self.bytes, &mut cursor)?;
+        }
+        Limits::parse((), &self.bytes, &mut cursor)
+    }
 }
 
 section_impl!(MemorySection, SectionId::Memory);
@@ -898,13 +934,16 @@ impl Parse<()> for GlobalType {
 }
 
 /// Constant expression for initialising globals or data segments
-/// Note: This is restricted for simplicity, but the spec allows arbitrary constant expressions
+/// Note: This is restricted to a single instruction for simplicity. The extended-const proposal
+/// also allows integer arithmetic, but neither Roc nor the toolchains it links with use it.
 #[derive(Debug)]
 pub enum ConstExpr {
     I32(i32),
     I64(i64),
     F32(f32),
     F64(f64),
+    /// The value of a global defined before this one, usually an imported global
+    GlobalGet(u32),
 }
 
 impl ConstExpr {
@@ -936,14 +975,15 @@ impl ConstExpr {
         }
     }
 
-    // ConstExpr and Value are separate types in case we ever need to support
-    // arbitrary constant expressions, rather than just i32.const and friends.
-    fn as_value(&self) -> Value {
+    /// Evaluate the expression, given the values of the globals it can refer to.
+    /// Returns `None` if it refers to a global that isn't in `globals`.
+    pub fn eval(&self, globals: &[Value]) -> Option<Value> {
         match self {
-            ConstExpr::I32(x) => Value::I32(*x),
-            ConstExpr::I64(x) => Value::I64(*x),
-            ConstExpr::F32(x) => Value::F32(*x),
-            ConstExpr::F64(x) => Value::F64(*x),
+            ConstExpr::I32(x) => Some(Value::I32(*x)),
+            ConstExpr::I64(x) => Some(Value::I64(*x)),
+            ConstExpr::F32(x) => Some(Value::F32(*x)),
+            ConstExpr::F64(x) => Some(Value::F64(*x)),
+            ConstExpr::GlobalGet(index) => globals.get(*index as usize).copied(),
         }
     }
 }
@@ -972,6 +1012,10 @@ impl Parse<()> for ConstExpr {
                 b.copy_from_slice(&bytes[*cursor..][..8]);
                 Ok(ConstExpr::F64(f64::from_le_bytes(b)))
             }
+            OpCode::GETGLOBAL => {
+                let index = u32::parse((), bytes, cursor)?;
+                Ok(ConstExpr::GlobalGet(index))
+            }
             _ => Err(ParseError {
                 offset: *cursor,
                 message: format!("Unsupported opcode {opcode:?} in constant expression."),
@@ -1009,6 +1053,10 @@ impl Serialize for ConstExpr {
                 buffer.append_u8(OpCode::F64CONST as u8);
                 buffer.encode_f64(*x);
             }
+            ConstExpr::GlobalGet(index) => {
+                buffer.append_u8(OpCode::GETGLOBAL as u8);
+                buffer.encode_u32(*index);
+            }
         }
         buffer.append_u8(OpCode::END as u8);
     }
@@ -1068,14 +1116,33 @@ impl<'a> GlobalSection<'a> {
         self.count += 1;
     }
 
-    pub fn initial_values<'b>(&self, arena: &'b Bump) -> Vec<'b, Value> {
+    /// Evaluate the initial value of each global, and append them to `globals`.
+    /// `globals` should start with the values of any imported globals, since they come first in
+    /// the index space and the initial values can refer to them.
+    pub fn append_initial_values(&self, globals: &mut Vec<'_, Value>) -> Result<(), ParseError> {
+        let mut cursor = 0;
+        for _ in 0..self.count {
+            GlobalType::skip_bytes(&self.bytes, &mut cursor)?;
+            let init_offset = cursor;
+            let init = ConstExpr::parse((), &self.bytes, &mut cursor)?;
+            let value = init.eval(globals).ok_or_else(|| ParseError {
+                offset: init_offset,
+                message: format!("Global initializer refers to undefined global in {init:?}"),
+            })?;
+            globals.push(value);
+        }
+        Ok(())
+    }
+
+    pub fn global_types<'b>(&self, arena: &'b Bump) -> Vec<'b, GlobalType> {
         let mut cursor = 0;
         let iter = (0..self.count)
             .map(|_| {
-                GlobalType::skip_bytes(&self.bytes, &mut cursor)?;
-                ConstExpr::parse((), &self.bytes, &mut cursor).map(|x| x.as_value())
+                let ty = GlobalType::parse((), &self.bytes, &mut cursor)?;
+                ConstExpr::skip_bytes(&self.bytes, &mut cursor)?;
+                Ok(ty)
             })
-            .filter_map(|r| r.ok());
+            .filter_map(|r: Result<GlobalType, ParseError>| r.ok());
         Vec::from_iter_in(iter, arena)
     }
 }
@@ -1109,7 +1176,7 @@ impl From<u8> for ExportType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Export<'a> {
     pub name: &'a str,
     pub ty: ExportType,
@@ -1478,7 +1545,10 @@ impl<'a> Serialize for CodeSection<'a> {
 #[derive(Debug)]
 pub enum DataMode {
     /// A data segment that auto-loads into memory on instantiation
-    Active { offset: ConstExpr },
+    Active {
+        memory_index: u32,
+        offset: ConstExpr,
+    },
     /// A data segment that can be loaded with the `memory.init` instruction
     Passive,
 }
@@ -1486,21 +1556,50 @@ pub enum DataMode {
 impl DataMode {
     const ACTIVE: u8 = 0;
     const PASSIVE: u8 = 1;
+    const ACTIVE_WITH_MEMORY_INDEX: u8 = 2;
 
     pub fn active_at(offset: u32) -> Self {
+        Self::active_in(0, offset)
+    }
+
+    pub fn active_in(memory_index: u32, offset: u32) -> Self {
         DataMode::Active {
+            memory_index,
             offset: ConstExpr::I32(offset as i32),
         }
     }
+
+    /// An active segment's address is an i32 constant, or an imported global like `__memory_base`
+    fn parse_offset(bytes: &[u8], cursor: &mut usize) -> Result<ConstExpr, ParseError> {
+        let start = *cursor;
+        match ConstExpr::parse((), bytes, cursor)? {
+            offset @ (ConstExpr::I32(_) | ConstExpr::GlobalGet(_)) => Ok(offset),
+            other => Err(ParseError {
+                offset: start,
+                message: format!("Data segment offset must be an i32, but found {other:?}"),
+            }),
+        }
+    }
 }
 
 impl Serialize for DataMode {
     fn serialize<T: SerialBuffer>(&self, buffer: &mut T) {
         match self {
-            Self::Active { offset } => {
+            Self::Active {
+                memory_index: 0,
+                offset,
+            } => {
                 buffer.append_u8(Self::ACTIVE);
                 offset.serialize(buffer);
             }
+            Self::Active {
+                memory_index,
+                offset,
+            } => {
+                buffer.append_u8(Self::ACTIVE_WITH_MEMORY_INDEX);
+                buffer.encode_u32(*memory_index);
+                offset.serialize(buffer);
+            }
             Self::Passive => {
                 buffer.append_u8(Self::PASSIVE);
             }
@@ -1514,9 +1613,17 @@ impl Parse<()> for DataMode {
         *cursor += 1;
 
         if variant_id == Self::ACTIVE {
-            let offset = ConstExpr::parse_u32(bytes, cursor)?;
+            let offset = Self::parse_offset(bytes, cursor)?;
             Ok(DataMode::Active {
-                offset: ConstExpr::I32(offset as i32),
+                memory_index: 0,
+                offset,
+            })
+        } else if variant_id == Self::ACTIVE_WITH_MEMORY_INDEX {
+            let memory_index = u32::parse((), bytes, cursor)?;
+            let offset = Self::parse_offset(bytes, cursor)?;
+            Ok(DataMode::Active {
+                memory_index,
+                offset,
             })
         } else if variant_id == Self::PASSIVE {
             Ok(DataMode::Passive)
@@ -1571,26 +1678,53 @@ impl<'a> DataSection<'a> {
         index
     }
 
-    pub fn load_into(&self, memory: &mut [u8]) -> Result<(), String> {
+    /// Iterate over the segments in index order, yielding the mode and contents of each
+    pub fn segments(&self) -> impl Iterator<Item = Result<(DataMode, &[u8]), String>> + '_ {
         let mut cursor = 0;
-        for _ in 0..self.count {
+        (0..self.count).map(move |_| {
             let mode =
                 DataMode::parse((), &self.bytes, &mut cursor).map_err(|e| format!("{e:?}"))?;
-            let start = match mode {
+            let len32 = u32::parse((), &self.bytes, &mut cursor).map_err(|e| format!("{e:?}"))?;
+            let len = len32 as usize;
+            let init = &self.bytes[cursor..][..len];
+            cursor += len;
+            Ok((mode, init))
+        })
+    }
+
+    /// Copy the active segments for one memory into it.
+    /// `globals` are the values of the globals that segment offsets can refer to.
+    pub fn load_into(
+        &self,
+        memory_index: u32,
+        memory: &mut [u8],
+        globals: &[Value],
+    ) -> Result<(), String> {
+        for segment in self.segments() {
+            let (mode, init) = segment?;
+            let offset = match mode {
                 DataMode::Active {
-                    offset: ConstExpr::I32(addr),
-                } => addr as usize,
+                    memory_index: index,
+                    offset,
+                } if index == memory_index => offset,
                 _ => {
                     continue;
                 }
             };
-            let len32 = u32::parse((), &self.bytes, &mut cursor).map_err(|e| format!("{e:?}"))?;
-            let len = len32 as usize;
-            let mut target_slice = &mut memory[start..][..len];
-            target_slice
-                .write(&self.bytes[cursor..][..len])
-                .map_err(|e| format!("{e:?}"))?;
-            cursor += len;
+            let start = match offset.eval(globals) {
+                Some(Value::I32(addr)) => addr as u32 as usize,
+                _ => return Err(format!("Invalid data segment offset {offset:?}")),
+            };
+            let mut target_slice = start
+                .checked_add(init.len())
+                .and_then(|end| memory.get_mut(start..end))
+                .ok_or_else(|| {
+                    format!(
+                        "Data segment at {start:#x} with length {:#x} is outside of memory {memory_index}",
+                        init.len()
+                    )
+                })?;
+            target_slice.write(init).map_err(|e| format!("{e:?}"))?;
         }
         Ok(())
     }
@@ -1616,6 +1750,7 @@ impl<'a> Parse<&'a Bump> for DataSection<'a> {
             let mode = DataMode::parse((), module_bytes, cursor)?;
             match mode {
                 DataMode::Active {
+                    memory_index: 0,
                     offset: ConstExpr::I32(offset_addr),
                 } if offset_addr > end_addr => {
                     end_addr = offset_addr;
@@ -1713,6 +1848,8 @@ enum NameSubSections {
 
 pub struct NameSection<'a> {
     pub function_names: Vec<'a, (u32, &'a str)>,
+    /// For each function that has any, the names of its locals (including args) by local index
+    pub local_names: Vec<'a, (u32, Vec<'a, (u32, &'a str)>)>,
 }
 
 impl<'a> NameSection<'a> {
@@ -1720,10 +1857,18 @@ impl<'a> NameSection<'a> {
     const NAME: &'static str = "name";
 
     pub fn size(&self) -> usize {
-        self.function_names
+        let names_size = |names: &[(u32, &str)]| -> usize {
+            names
+                .iter()
+                .map(|(_, s)| MAX_SIZE_ENCODED_U32 + s.len())
+                .sum()
+        };
+        let local_names_size: usize = self
+            .local_names
             .iter()
-            .map(|(_, s)| MAX_SIZE_ENCODED_U32 + s.len())
-            .sum()
+            .map(|(_, names)| 2 * MAX_SIZE_ENCODED_U32 + names_size(names))
+            .sum();
+        names_size(&self.function_names) + local_names_size
     }
 
     pub fn append_function(&mut self, index: u32, name: &'a str) {
@@ -1733,9 +1878,16 @@ impl<'a> NameSection<'a> {
     pub fn new(arena: &'a Bump) -> Self {
         NameSection {
             function_names: bumpalo::vec![in arena],
+            local_names: bumpalo::vec![in arena],
         }
     }
 
+    /// Find a local variable (or arg) of a function by its name
+    pub fn local_index(&self, fn_index: u32, name: &str) -> Option<u32> {
+        let (_, names) = self.local_names.iter().find(|(i, _)| *i == fn_index)?;
+        names.iter().find(|(_, n)| *n == name).map(|(i, _)| *i)
+    }
+
     pub fn from_imports_and_linking_data(
         arena: &'a Bump,
         import: &ImportSection<'a>,
@@ -1758,7 +1910,10 @@ impl<'a> NameSection<'a> {
         let mut function_names = Vec::from_iter_in(names, arena);
         function_names.sort_by_key(|(idx, _name)| *idx);
 
-        NameSection { function_names }
+        NameSection {
+            function_names,
+            local_names: bumpalo::vec![in arena],
+        }
     }
 }
 
@@ -1789,45 +1944,45 @@ impl<'a> Parse<&'a Bump> for NameSection<'a> {
             return Ok(Self::new(arena));
         }
 
-        // Find function names subsection
+        let parse_name_map = |cursor: &mut usize| -> Result<Vec<'a, (u32, &'a str)>, ParseError> {
+            let count = u32::parse((), module_bytes, cursor)?;
+            let mut names = Vec::with_capacity_in(count as usize, arena);
+            for _ in 0..count {
+                let index = u32::parse((), module_bytes, cursor)?;
+                let name = <&'a str>::parse(arena, module_bytes, cursor)?;
+                names.push((index, name));
+            }
+            Ok(names)
+        };
+
+        // Subsections are in order of ID, and any of them may be missing
+        let mut section = Self::new(arena);
         let mut found_function_names = false;
-        for _possible_subsection_id in 0..2 {
+        while *cursor < section_end {
             let subsection_id = module_bytes[*cursor];
             *cursor += 1;
             let subsection_size = u32::parse((), module_bytes, cursor)?;
+            let subsection_end = *cursor + subsection_size as usize;
             if subsection_id == NameSubSections::FunctionNames as u8 {
                 found_function_names = true;
-                break;
-            }
-            *cursor += subsection_size as usize;
-            if *cursor >= section_end {
-                return Err(ParseError {
-                    message: "Failed to parse Name section".into(),
-                    offset: *cursor,
-                });
+                section.function_names = parse_name_map(cursor)?;
+            } else if subsection_id == NameSubSections::LocalNames as u8 {
+                let count = u32::parse((), module_bytes, cursor)?;
+                for _ in 0..count {
+                    let fn_index = u32::parse((), module_bytes, cursor)?;
+                    let names = parse_name_map(cursor)?;
+                    section.local_names.push((fn_index, names));
+                }
             }
+            *cursor = subsection_end;
         }
-        if !found_function_names {
+        if !found_function_names || *cursor != section_end {
             return Err(ParseError {
                 message: "Failed to parse Name section".into(),
                 offset: *cursor,
             });
         }
 
-        let count = u32::parse((), module_bytes, cursor)?;
-        let mut section = NameSection {
-            function_names: Vec::with_capacity_in(count as usize, arena),
-        };
-
-        // Function names
-        for _ in 0..count {
-            let index = u32::parse((), module_bytes, cursor)?;
-            let name = <&'a str>::parse(arena, module_bytes, cursor)?;
-            section.function_names.push((index, name));
-        }
-
-        *cursor = section_end;
-
         Ok(section)
     }
 }
@@ -1850,6 +2005,25 @@ impl<'a> Serialize for NameSection<'a> {
                 (buffer.size() - subsection_start) as u32,
             );
 
+            if !self.local_names.is_empty() {
+                let subsection_id = NameSubSections::LocalNames as u8;
+                subsection_id.serialize(buffer);
+
+                let subsection_size_index = buffer.encode_padded_u32(0);
+                let subsection_start = buffer.size();
+
+                buffer.encode_u32(self.local_names.len() as u32);
+                for (fn_index, names) in self.local_names.iter() {
+                    buffer.encode_u32(*fn_index);
+                    names.serialize(buffer);
+                }
+
+                buffer.overwrite_padded_u32(
+                    subsection_size_index,
+                    (buffer.size() - subsection_start) as u32,
+                );
+            }
+
             update_section_size(buffer, header_indices);
         }
     }